@@ -0,0 +1,145 @@
+//! Interpreter loop throughput, run with `cargo bench`
+
+// the crate is a binary, so the core modules are compiled into the benchmark directly
+#![allow(dead_code)]
+extern crate alloc;
+
+#[path = "../src/chip8.rs"]
+mod chip8;
+#[path = "../src/common.rs"]
+mod common;
+#[path = "../src/coverage.rs"]
+mod coverage;
+#[path = "../src/font.rs"]
+mod font;
+#[path = "../src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../src/journal.rs"]
+mod journal;
+#[cfg(feature = "megachip")]
+#[path = "../src/megachip.rs"]
+mod megachip;
+#[path = "../src/opcode.rs"]
+mod opcode;
+#[path = "../src/platform.rs"]
+mod platform;
+#[path = "../src/quirks.rs"]
+mod quirks;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use chip8::Chip8;
+
+const STEPS: u64 = 10_000;
+const MACHINES_COUNT: usize = 100;
+
+// register arithmetic, skips and index updates in an endless loop
+const ALU_ROM: [u8; 20] = [
+    0x61, 0x01, // 200: LD V1, 01
+    0x70, 0x01, // 202: ADD V0, 01
+    0x80, 0x14, // 204: ADD V0, V1
+    0x82, 0x06, // 206: SHR V2
+    0x81, 0x03, // 208: XOR V1, V0
+    0x40, 0x00, // 20A: SNE V0, 00
+    0x61, 0x01, // 20C: LD V1, 01
+    0xa3, 0x00, // 20E: LD I, 300
+    0xf0, 0x1e, // 210: ADD I, V0
+    0x12, 0x02, // 212: JP 202
+];
+
+// font sprites drawn all over the screen
+const DRAW_ROM: [u8; 12] = [
+    0x70, 0x01, // 200: ADD V0, 01
+    0xf0, 0x29, // 202: LD F, V0
+    0xd1, 0x25, // 204: DRW V1, V2, 5
+    0x71, 0x03, // 206: ADD V1, 03
+    0x72, 0x02, // 208: ADD V2, 02
+    0x12, 0x00, // 20A: JP 200
+];
+
+// subroutine calls with memory stores and loads
+const CALL_ROM: [u8; 16] = [
+    0xa3, 0x00, // 200: LD I, 300
+    0x22, 0x08, // 202: CALL 208
+    0x12, 0x02, // 204: JP 202
+    0x00, 0x00, // 206: unused
+    0xf2, 0x55, // 208: LD [I], V2
+    0xf2, 0x65, // 20A: LD V2, [I]
+    0xf0, 0x33, // 20C: LD B, V0
+    0x00, 0xee, // 20E: RET
+];
+
+fn run(machine: &mut Chip8) {
+    for _ in 0..STEPS {
+        machine.teak().expect("benchmark program failed");
+    }
+}
+
+fn bench_rom(c: &mut Criterion, name: &str, rom: &[u8], undo_depth: usize) {
+    let mut group = c.benchmark_group("teak");
+    group.throughput(Throughput::Elements(STEPS));
+    group.bench_function(name, |b| {
+        b.iter_batched_ref(
+            || {
+                Chip8::builder(rom.to_vec())
+                    .seed(0)
+                    .undo_depth(undo_depth)
+                    .build()
+                    .expect("benchmark program doesn't fit")
+            },
+            run,
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Machines shared by a pool of worker threads
+fn bench_concurrent(c: &mut Criterion) {
+    let workers = thread::available_parallelism().map_or(4, |count| count.get());
+    let mut group = c.benchmark_group("concurrent");
+    group.throughput(Throughput::Elements(STEPS * MACHINES_COUNT as u64));
+    group.bench_function("draw", |b| {
+        b.iter_batched(
+            || {
+                (0..MACHINES_COUNT)
+                    .map(|_| {
+                        let machine = Chip8::builder(DRAW_ROM.to_vec()).seed(0).build();
+                        Mutex::new(machine.unwrap())
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |machines| {
+                let next = AtomicUsize::new(0);
+                thread::scope(|scope| {
+                    for _ in 0..workers {
+                        scope.spawn(|| {
+                            while let Some(machine) =
+                                machines.get(next.fetch_add(1, Ordering::Relaxed))
+                            {
+                                run(&mut machine.lock().unwrap());
+                            }
+                        });
+                    }
+                });
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn interpreter(c: &mut Criterion) {
+    bench_rom(c, "alu", &ALU_ROM, 0);
+    bench_rom(c, "draw", &DRAW_ROM, 0);
+    bench_rom(c, "call", &CALL_ROM, 0);
+    bench_rom(c, "alu_journal", &ALU_ROM, 1000);
+    bench_concurrent(c);
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
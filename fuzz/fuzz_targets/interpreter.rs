@@ -0,0 +1,90 @@
+//! Random programs executed by the interpreter core, run with `cargo fuzz run interpreter`.
+//! The core may fail only with `Error`, any panic is a bug.
+//!
+//! Input layout: quirk flags byte, keypad byte, program bytes
+
+// the crate is a binary, so the core modules are compiled into the target directly
+#![allow(dead_code)]
+#![no_main]
+extern crate alloc;
+
+#[path = "../../src/chip8.rs"]
+mod chip8;
+#[path = "../../src/common.rs"]
+mod common;
+#[path = "../../src/coverage.rs"]
+mod coverage;
+#[path = "../../src/font.rs"]
+mod font;
+#[path = "../../src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../../src/journal.rs"]
+mod journal;
+#[cfg(feature = "megachip")]
+#[path = "../../src/megachip.rs"]
+mod megachip;
+#[path = "../../src/opcode.rs"]
+mod opcode;
+#[path = "../../src/platform.rs"]
+mod platform;
+#[path = "../../src/quirks.rs"]
+mod quirks;
+
+use libfuzzer_sys::fuzz_target;
+
+use chip8::{Chip8, State};
+use quirks::Quirks;
+
+const STEPS: usize = 1000;
+const TIMER_INTERVAL: usize = 16; // steps between timer ticks
+const UNDO_INTERVAL: usize = 7; // steps between undo of the last instruction
+
+fuzz_target!(|data: &[u8]| {
+    let [flags, keys, rom @ ..] = data else {
+        return;
+    };
+    let quirks = Quirks {
+        vf_reset: flags & 0x01 > 0,
+        memory: flags & 0x02 > 0,
+        shifting: flags & 0x04 > 0,
+        jumping: flags & 0x08 > 0,
+        hires: flags & 0x10 > 0,
+        key_wait_release: flags & 0x80 > 0,
+        // no spare bits: ETI-660 layout is chosen by hires and jumping together
+        eti660: flags & 0x18 == 0x18,
+        half_scroll: flags & 0x06 == 0x06,
+    };
+    let is_journaled = flags & 0x20 > 0;
+    let Ok(mut machine) = Chip8::builder(rom.to_vec()).quirks(quirks).seed(0).build() else {
+        return;
+    };
+    if is_journaled {
+        machine.set_undo_depth(UNDO_INTERVAL);
+    }
+    if flags & 0x40 > 0 {
+        machine.enable_coverage();
+    }
+    for step in 0..STEPS {
+        // the keypad byte is a pressed key and a toggle interval
+        if step % (1 + (keys >> 4) as usize) == 0 {
+            _ = machine.key_down(keys & 0xf);
+        } else {
+            _ = machine.key_up(keys & 0xf);
+        }
+        if step % TIMER_INTERVAL == 0 {
+            machine.on_timer();
+        }
+        if machine.teak().is_err() {
+            break;
+        }
+        _ = machine.instruction_cost();
+        _ = machine.call_stack();
+        if is_journaled && step % UNDO_INTERVAL == 0 {
+            machine.pause();
+            machine.step_back();
+        }
+        if matches!(machine.get_state(), State::Paused) {
+            machine.toggle_execution();
+        }
+    }
+});
@@ -0,0 +1,28 @@
+//! Interpreter core built without std, run `cargo build` here to check it stays portable
+//! to targets like microcontrollers. The modules are compiled in the same way as by the fuzz target
+
+#![no_std]
+#![allow(dead_code)]
+extern crate alloc;
+
+#[path = "../../src/chip8.rs"]
+mod chip8;
+#[path = "../../src/common.rs"]
+mod common;
+#[path = "../../src/coverage.rs"]
+mod coverage;
+#[path = "../../src/font.rs"]
+mod font;
+#[path = "../../src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../../src/journal.rs"]
+mod journal;
+#[cfg(feature = "megachip")]
+#[path = "../../src/megachip.rs"]
+mod megachip;
+#[path = "../../src/opcode.rs"]
+mod opcode;
+#[path = "../../src/platform.rs"]
+mod platform;
+#[path = "../../src/quirks.rs"]
+mod quirks;
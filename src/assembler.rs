@@ -0,0 +1,691 @@
+// A subset of the Octo language enough for hand written programs:
+// labels, constants, aliases, structured control flow and all the
+// CHIP-8, SUPER-CHIP and XO-CHIP statements the interpreter runs.
+// Macros, :calc, :unpack and :next aren't supported
+
+use std::collections::HashMap;
+
+use crate::chip8::PROGRAM_BASE_ADDRESS;
+
+const MAX_ADDRESS: usize = 0xfff;
+
+///
+/// Assembled program with the addresses of its labels
+///
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub labels: Vec<(String, usize)>,
+}
+
+/// Assembles Octo source, errors are prefixed with the line number
+pub fn assemble(source: &str) -> Result<Program, String> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: index + 1,
+            })
+        })
+        .collect();
+    let mut assembler = Assembler::new(tokens);
+    assembler
+        .run()
+        .map_err(|(line, err)| format!("line {line}: {err}"))?;
+    let mut labels = assembler.labels.into_iter().collect::<Vec<_>>();
+    labels.sort_by_key(|(_, address)| *address);
+    Ok(Program {
+        rom: assembler.rom,
+        labels,
+    })
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    Key,
+    NoKey,
+}
+
+impl Comparison {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "==" => Some(Self::Equal),
+            "!=" => Some(Self::NotEqual),
+            "<" => Some(Self::Less),
+            ">" => Some(Self::Greater),
+            "<=" => Some(Self::LessOrEqual),
+            ">=" => Some(Self::GreaterOrEqual),
+            "key" => Some(Self::Key),
+            "-key" => Some(Self::NoKey),
+            _ => None,
+        }
+    }
+
+    fn negated(self) -> Self {
+        match self {
+            Self::Equal => Self::NotEqual,
+            Self::NotEqual => Self::Equal,
+            Self::Less => Self::GreaterOrEqual,
+            Self::GreaterOrEqual => Self::Less,
+            Self::Greater => Self::LessOrEqual,
+            Self::LessOrEqual => Self::Greater,
+            Self::Key => Self::NoKey,
+            Self::NoKey => Self::Key,
+        }
+    }
+}
+
+enum Operand {
+    Register(u16),
+    Value(u16),
+}
+
+enum Block {
+    If { jump: usize },   // offset of the jump to `else` or `end`
+    Else { jump: usize }, // offset of the jump to `end`
+    Loop { start: usize, breaks: Vec<usize> },
+}
+
+type AsmResult<T> = Result<T, (usize, String)>;
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    line: usize,
+    rom: Vec<u8>,
+    offset: usize, // where the next byte goes
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, u16>,
+    aliases: HashMap<String, u16>,
+    fixups: Vec<(usize, String, usize)>, // offset of the opcode, label, line
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            line: 0,
+            rom: Vec::new(),
+            offset: 0,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> AsmResult<()> {
+        while self.position < self.tokens.len() {
+            self.statement()?;
+        }
+        if !self.blocks.is_empty() {
+            return self.error("Unclosed block at the end of the source");
+        }
+        for (offset, name, line) in std::mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(&name) else {
+                return Err((line, format!("Undefined label {name}")));
+            };
+            self.line = line;
+            self.patch(offset, address)?;
+        }
+        Ok(())
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> AsmResult<T> {
+        Err((self.line, message.into()))
+    }
+
+    fn next(&mut self) -> AsmResult<&'a str> {
+        let Some(token) = self.tokens.get(self.position) else {
+            return self.error("Unexpected end of the source");
+        };
+        self.position += 1;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> AsmResult<()> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("Expected {expected} but found {token}"));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> AsmResult<()> {
+        let token = self.next()?;
+        if self.register(token).is_some() {
+            return self.register_statement(token);
+        }
+        match token {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.contains_key(name) {
+                    return self.error(format!("Label {name} is already defined"));
+                }
+                self.labels.insert(name.to_string(), self.address());
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name.to_string(), value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.next()?;
+                let Some(register) = self.register(register) else {
+                    return self.error(format!("Expected a register but found {register}"));
+                };
+                self.aliases.insert(name.to_string(), register);
+            }
+            ":org" => {
+                let address = self.value()? as usize;
+                if address < PROGRAM_BASE_ADDRESS {
+                    return self.error(format!("Address {address:03X} is below the program"));
+                }
+                self.offset = address - PROGRAM_BASE_ADDRESS;
+            }
+            ":byte" => {
+                let value = self.value()?;
+                self.emit(value as u8);
+            }
+            ":pointer" => {
+                let value = self.value()?;
+                self.emit_word(value);
+            }
+            ":call" => self.address_operand(0x2000)?,
+            ":breakpoint" => {
+                self.name()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit_word(0x00e0),
+            "return" | ";" => self.emit_word(0x00ee),
+            "exit" => self.emit_word(0x00fd),
+            "lores" => self.emit_word(0x00fe),
+            "hires" => self.emit_word(0x00ff),
+            "scroll-down" => {
+                let rows = self.nibble()?;
+                self.emit_word(0x00c0 | rows);
+            }
+            "scroll-up" => {
+                let rows = self.nibble()?;
+                self.emit_word(0x00d0 | rows);
+            }
+            "scroll-right" => self.emit_word(0x00fb),
+            "scroll-left" => self.emit_word(0x00fc),
+            "native" => self.address_operand(0x0000)?,
+            "jump" => self.address_operand(0x1000)?,
+            "jump0" => self.address_operand(0xb000)?,
+            "sprite" => {
+                let x = self.register_operand()?;
+                let y = self.register_operand()?;
+                let n = self.nibble()?;
+                self.emit_word(0xd000 | x << 8 | y << 4 | n);
+            }
+            "bcd" => self.register_opcode(0xf033)?,
+            "save" => self.register_opcode(0xf055)?,
+            "load" => self.register_opcode(0xf065)?,
+            "saveflags" => self.register_opcode(0xf075)?,
+            "loadflags" => self.register_opcode(0xf085)?,
+            "plane" => {
+                let mask = self.nibble()?;
+                self.emit_word(0xf001 | mask << 8);
+            }
+            "audio" => self.emit_word(0xf002),
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let opcode = match token {
+                    "delay" => 0xf015,
+                    "buzzer" => 0xf018,
+                    _ => 0xf03a,
+                };
+                self.register_opcode(opcode)?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => {
+                let Some(Block::If { jump }) = self.blocks.pop() else {
+                    return self.error("else without if ... begin");
+                };
+                let end_jump = self.offset;
+                self.emit_word(0x1000);
+                self.patch(jump, self.address())?;
+                self.blocks.push(Block::Else { jump: end_jump });
+            }
+            "end" => {
+                let (Some(Block::If { jump }) | Some(Block::Else { jump })) = self.blocks.pop()
+                else {
+                    return self.error("end without if ... begin");
+                };
+                self.patch(jump, self.address())?;
+            }
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.address(),
+                breaks: Vec::new(),
+            }),
+            "while" => {
+                // skips the jump out of the loop while the condition holds
+                self.condition(true)?;
+                let jump = self.offset;
+                self.emit_word(0x1000);
+                let Some(Block::Loop { breaks, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return self.error("while outside of loop");
+                };
+                breaks.push(jump);
+            }
+            "again" => {
+                let Some(Block::Loop { start, breaks }) = self.blocks.pop() else {
+                    return self.error("again without loop");
+                };
+                self.emit_word(0x1000 | start as u16);
+                for jump in breaks {
+                    self.patch(jump, self.address())?;
+                }
+            }
+            _ if token.starts_with(':') => {
+                return self.error(format!("Unsupported directive {token}"));
+            }
+            _ => {
+                if let Some(value) = self.number(token) {
+                    self.emit(value as u8);
+                } else if is_name(token) {
+                    self.position -= 1;
+                    self.address_operand(0x2000)?;
+                } else {
+                    return self.error(format!("Unexpected {token}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, target: &str) -> AsmResult<()> {
+        let x = self.register(target).unwrap_or_default() << 8;
+        let operator = self.next()?;
+        if operator == ":=" {
+            match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.value()?;
+                    self.emit_word(0xc000 | x | mask & 0xff);
+                    return Ok(());
+                }
+                Some("key") => {
+                    self.next()?;
+                    self.emit_word(0xf00a | x);
+                    return Ok(());
+                }
+                Some("delay") => {
+                    self.next()?;
+                    self.emit_word(0xf007 | x);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        let opcode = match (operator, self.operand()?) {
+            (":=", Operand::Value(nn)) => 0x6000 | x | nn & 0xff,
+            ("+=", Operand::Value(nn)) => 0x7000 | x | nn & 0xff,
+            ("-=", Operand::Value(nn)) => 0x7000 | x | nn.wrapping_neg() & 0xff,
+            (":=", Operand::Register(y)) => 0x8000 | x | y << 4,
+            ("|=", Operand::Register(y)) => 0x8001 | x | y << 4,
+            ("&=", Operand::Register(y)) => 0x8002 | x | y << 4,
+            ("^=", Operand::Register(y)) => 0x8003 | x | y << 4,
+            ("+=", Operand::Register(y)) => 0x8004 | x | y << 4,
+            ("-=", Operand::Register(y)) => 0x8005 | x | y << 4,
+            (">>=", Operand::Register(y)) => 0x8006 | x | y << 4,
+            ("=-", Operand::Register(y)) => 0x8007 | x | y << 4,
+            ("<<=", Operand::Register(y)) => 0x800e | x | y << 4,
+            _ => return self.error(format!("Unsupported operation {target} {operator}")),
+        };
+        self.emit_word(opcode);
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> AsmResult<()> {
+        match self.next()? {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    self.register_opcode(0xf029)
+                }
+                Some("bighex") => {
+                    self.next()?;
+                    self.register_opcode(0xf030)
+                }
+                Some("long") => self.error("i := long isn't supported"),
+                _ => self.address_operand(0xa000),
+            },
+            "+=" => self.register_opcode(0xf01e),
+            operator => self.error(format!("Unsupported operation i {operator}")),
+        }
+    }
+
+    fn if_statement(&mut self) -> AsmResult<()> {
+        let position = self.position;
+        // the body follows `then` or `begin` after the condition
+        let mut end = position;
+        while let Some(token) = self.tokens.get(end) {
+            if token.text == "then" || token.text == "begin" {
+                break;
+            }
+            end += 1;
+        }
+        match self.tokens.get(end).map(|token| token.text) {
+            Some("then") => {
+                self.condition(false)?;
+                self.expect("then")
+            }
+            Some(_) => {
+                self.condition(true)?;
+                self.expect("begin")?;
+                self.blocks.push(Block::If { jump: self.offset });
+                self.emit_word(0x1000);
+                Ok(())
+            }
+            None => self.error("if without then or begin"),
+        }
+    }
+
+    /// Emits instructions skipping the next one if the condition is false,
+    /// or true when it's negated
+    fn condition(&mut self, is_negated: bool) -> AsmResult<()> {
+        let x = self.register_operand()?;
+        let name = self.next()?;
+        let Some(mut comparison) = Comparison::from_name(name) else {
+            return self.error(format!("Unknown comparison {name}"));
+        };
+        if is_negated {
+            comparison = comparison.negated();
+        }
+        let operand = match comparison {
+            Comparison::Key | Comparison::NoKey => Operand::Register(x),
+            _ => self.operand()?,
+        };
+        let opcode = match (comparison, &operand) {
+            (Comparison::Key, _) => 0xe0a1 | x << 8,
+            (Comparison::NoKey, _) => 0xe09e | x << 8,
+            (Comparison::Equal, Operand::Value(nn)) => 0x4000 | x << 8 | nn & 0xff,
+            (Comparison::NotEqual, Operand::Value(nn)) => 0x3000 | x << 8 | nn & 0xff,
+            (Comparison::Equal, Operand::Register(y)) => 0x9000 | x << 8 | y << 4,
+            (Comparison::NotEqual, Operand::Register(y)) => 0x5000 | x << 8 | y << 4,
+            _ => {
+                // VF gets the carry of a subtraction with the operand loaded into VF
+                self.emit_word(match operand {
+                    Operand::Value(nn) => 0x6f00 | nn & 0xff,
+                    Operand::Register(y) => 0x8f00 | y << 4,
+                });
+                match comparison {
+                    // VF = 1 when X >= operand
+                    Comparison::Less | Comparison::GreaterOrEqual => {
+                        self.emit_word(0x8f07 | x << 4)
+                    }
+                    // VF = 1 when operand >= X
+                    _ => self.emit_word(0x8f05 | x << 4),
+                }
+                match comparison {
+                    Comparison::Less | Comparison::Greater => 0x4f00,
+                    _ => 0x3f00,
+                }
+            }
+        };
+        self.emit_word(opcode);
+        Ok(())
+    }
+
+    fn operand(&mut self) -> AsmResult<Operand> {
+        let token = self.next()?;
+        if let Some(register) = self.register(token) {
+            return Ok(Operand::Register(register));
+        }
+        match self.number(token) {
+            Some(value) => Ok(Operand::Value(value)),
+            None => self.error(format!("Expected a register or a number but found {token}")),
+        }
+    }
+
+    fn register_operand(&mut self) -> AsmResult<u16> {
+        let token = self.next()?;
+        match self.register(token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("Expected a register but found {token}")),
+        }
+    }
+
+    fn register_opcode(&mut self, opcode: u16) -> AsmResult<()> {
+        let x = self.register_operand()?;
+        self.emit_word(opcode | x << 8);
+        Ok(())
+    }
+
+    /// 12 bit address operand, labels defined later are patched at the end
+    fn address_operand(&mut self, opcode: u16) -> AsmResult<()> {
+        let token = self.next()?;
+        let address = match self.number(token) {
+            Some(value) => value as usize,
+            None if !is_name(token) => {
+                return self.error(format!("Expected an address but found {token}"))
+            }
+            None => match self.labels.get(token) {
+                Some(address) => *address,
+                None => {
+                    self.fixups
+                        .push((self.offset, token.to_string(), self.line));
+                    0
+                }
+            },
+        };
+        let offset = self.offset;
+        self.emit_word(opcode);
+        self.patch(offset, address)
+    }
+
+    fn nibble(&mut self) -> AsmResult<u16> {
+        let value = self.value()?;
+        if value > 0xf {
+            return self.error(format!("{value} doesn't fit into 4 bits"));
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> AsmResult<u16> {
+        let token = self.next()?;
+        match self.number(token) {
+            Some(value) => Ok(value),
+            None => self.error(format!("Expected a number but found {token}")),
+        }
+    }
+
+    fn name(&mut self) -> AsmResult<&'a str> {
+        let token = self.next()?;
+        if !is_name(token) || self.register(token).is_some() {
+            return self.error(format!("Invalid name {token}"));
+        }
+        Ok(token)
+    }
+
+    /// Number literal, negative numbers are kept in two's complement,
+    /// or a constant
+    fn number(&self, token: &str) -> Option<u16> {
+        if let Some(value) = self.constants.get(token) {
+            return Some(*value);
+        }
+        let (is_negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16)
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            u16::from_str_radix(binary, 2)
+        } else {
+            digits.parse()
+        }
+        .ok()?;
+        Some(if is_negative {
+            value.wrapping_neg()
+        } else {
+            value
+        })
+    }
+
+    fn register(&self, token: &str) -> Option<u16> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+        let digit = token.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn address(&self) -> usize {
+        PROGRAM_BASE_ADDRESS + self.offset
+    }
+
+    fn emit(&mut self, byte: u8) {
+        if self.offset >= self.rom.len() {
+            self.rom.resize(self.offset + 1, 0);
+        }
+        self.rom[self.offset] = byte;
+        self.offset += 1;
+    }
+
+    fn emit_word(&mut self, word: u16) {
+        word.to_be_bytes()
+            .into_iter()
+            .for_each(|byte| self.emit(byte));
+    }
+
+    /// Puts the address into the lower 12 bits of the opcode at the offset
+    fn patch(&mut self, offset: usize, address: usize) -> AsmResult<()> {
+        if address > MAX_ADDRESS {
+            return self.error(format!("Address {address:X} doesn't fit into 12 bits"));
+        }
+        self.rom[offset] = self.rom[offset] & 0xf0 | (address >> 8) as u8;
+        self.rom[offset + 1] = address as u8;
+        Ok(())
+    }
+}
+
+fn is_name(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_alphabetic() || ch == '_')
+        && token
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opcodes(source: &str) -> Vec<u16> {
+        let program = assemble(source).unwrap();
+        program
+            .rom
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn then_skips_statement_if_condition_is_false() {
+        assert_eq!(opcodes("if v3 == 5 then v1 := 2"), [0x4305, 0x6102]);
+        assert_eq!(opcodes("if v3 != 5 then v1 := 2"), [0x3305, 0x6102]);
+        assert_eq!(opcodes("if v3 == v4 then v1 := 2"), [0x9340, 0x6102]);
+        assert_eq!(opcodes("if v3 != v4 then v1 := 2"), [0x5340, 0x6102]);
+        assert_eq!(opcodes("if v3 key then v1 := 2"), [0xe3a1, 0x6102]);
+        assert_eq!(opcodes("if v3 -key then v1 := 2"), [0xe39e, 0x6102]);
+    }
+
+    #[test]
+    fn then_lowers_ordering_to_subtraction_carry() {
+        // VF = 1 if V3 >= 5 for < and >=, VF = 1 if 5 >= V3 for > and <=
+        assert_eq!(
+            opcodes("if v3 < 5 then v1 := 2"),
+            [0x6f05, 0x8f37, 0x4f00, 0x6102]
+        );
+        assert_eq!(
+            opcodes("if v3 >= 5 then v1 := 2"),
+            [0x6f05, 0x8f37, 0x3f00, 0x6102]
+        );
+        assert_eq!(
+            opcodes("if v3 > 5 then v1 := 2"),
+            [0x6f05, 0x8f35, 0x4f00, 0x6102]
+        );
+        assert_eq!(
+            opcodes("if v3 <= 5 then v1 := 2"),
+            [0x6f05, 0x8f35, 0x3f00, 0x6102]
+        );
+        assert_eq!(
+            opcodes("if v3 < v4 then v1 := 2"),
+            [0x8f40, 0x8f37, 0x4f00, 0x6102]
+        );
+    }
+
+    #[test]
+    fn begin_jumps_over_block_if_condition_is_false() {
+        // the skip is negated, so the jump to else is skipped when the condition holds
+        assert_eq!(
+            opcodes("if v3 < 5 begin v1 := 1 else v1 := 2 end"),
+            [0x6f05, 0x8f37, 0x3f00, 0x120c, 0x6101, 0x120e, 0x6102]
+        );
+        assert_eq!(
+            opcodes("if v3 > v4 begin v1 := 1 end"),
+            [0x8f40, 0x8f35, 0x3f00, 0x120a, 0x6101]
+        );
+        assert_eq!(
+            opcodes("if v3 == 5 begin v1 := 1 end"),
+            [0x3305, 0x1206, 0x6101]
+        );
+        assert_eq!(
+            opcodes("if v3 key begin v1 := 1 end"),
+            [0xe39e, 0x1206, 0x6101]
+        );
+    }
+
+    #[test]
+    fn while_leaves_loop_if_condition_is_false() {
+        assert_eq!(
+            opcodes("loop v0 += 1 while v0 != 10 again"),
+            [0x7001, 0x400a, 0x1208, 0x1200]
+        );
+        assert_eq!(
+            opcodes("loop v0 += 1 while v0 <= 10 again"),
+            [0x7001, 0x6f0a, 0x8f05, 0x4f00, 0x120c, 0x1200]
+        );
+    }
+}
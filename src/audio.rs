@@ -0,0 +1,243 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use sdl2::audio::{AudioCVT, AudioCallback, AudioFormat, AudioSpecWAV};
+use tracing::{debug, warn};
+
+use crate::config::{AudioConfig, Waveform};
+#[cfg(feature = "megachip")]
+use crate::megachip::SoundCommand;
+
+const SCOPE_SIZE: usize = 1024; // recent output values kept for the oscilloscope
+
+enum Source {
+    Wave(Waveform),
+    Sample(Vec<f32>),
+}
+
+///
+/// Buzzer audio callback, plays a waveform or a sample while the gate is open
+/// and applies attack/release envelope to avoid clicks
+///
+pub struct Buzzer {
+    pub is_gate_open: bool,
+    pub is_paused: bool, // digitized sound is suspended while the machine is paused
+    source: Source,
+    phase: f32,
+    phase_inc: f32,
+    position: usize, // current position in the sample
+    volume: f32,
+    envelope: f32,
+    attack_step: f32,
+    release_step: f32,
+    noise_state: u32,
+    pattern: Option<([u8; 16], f32)>, // XO-CHIP audio pattern and its rate in bits per second
+    pattern_position: f32,
+    freq: f32,
+    scope: Vec<f32>, // ring buffer of output values without the volume applied
+    scope_position: usize,
+    #[cfg(feature = "megachip")]
+    digitized: Option<Digitized>,
+}
+
+/// Mega-CHIP digitized sound, played regardless of the gate
+#[cfg(feature = "megachip")]
+struct Digitized {
+    samples: Vec<f32>,
+    step: f32, // sample position increment per output value
+    position: f32,
+    is_looped: bool,
+}
+
+impl Buzzer {
+    pub fn new(config: &AudioConfig, volume: f32, freq: i32) -> Self {
+        let source = match &config.sample_path {
+            Some(path) => match load_sample(path, freq) {
+                Ok(sample) if !sample.is_empty() => Source::Sample(sample),
+                Ok(_) => Source::Wave(config.waveform),
+                Err(err) => {
+                    warn!("Failed to load sound sample {path}: {err}");
+                    Source::Wave(config.waveform)
+                }
+            },
+            None => Source::Wave(config.waveform),
+        };
+        debug!("Audio output at {freq} Hz");
+        let envelope_step = |millis: u32| {
+            let samples = millis as f32 * freq as f32 / 1000.0;
+            if samples < 1.0 {
+                1.0
+            } else {
+                1.0 / samples
+            }
+        };
+        Self {
+            is_gate_open: false,
+            is_paused: false,
+            source,
+            phase: 0.0,
+            phase_inc: config.frequency / freq as f32,
+            position: 0,
+            volume,
+            envelope: 0.0,
+            attack_step: envelope_step(config.attack),
+            release_step: envelope_step(config.release),
+            noise_state: 0x2545f491,
+            pattern: None,
+            pattern_position: 0.0,
+            freq: freq as f32,
+            scope: vec![0.0; SCOPE_SIZE],
+            scope_position: 0,
+            #[cfg(feature = "megachip")]
+            digitized: None,
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Sampled audio pattern overrides the configured sound source
+    pub fn set_pattern(&mut self, pattern: Option<(&[u8; 16], f32)>) {
+        self.pattern = pattern.map(|(data, rate)| (*data, rate));
+    }
+
+    /// Recent output values from the oldest one, in -1..1 range
+    pub fn scope(&self) -> Vec<f32> {
+        let (newer, older) = self.scope.split_at(self.scope_position);
+        older.iter().chain(newer).copied().collect()
+    }
+
+    fn record(&mut self, value: f32) {
+        self.scope[self.scope_position] = value;
+        self.scope_position = (self.scope_position + 1) % SCOPE_SIZE;
+    }
+
+    #[cfg(feature = "megachip")]
+    pub fn set_digitized(&mut self, command: SoundCommand) {
+        self.digitized = match command {
+            SoundCommand::Play {
+                samples,
+                rate,
+                is_looped,
+            } if !samples.is_empty() => Some(Digitized {
+                samples: samples
+                    .iter()
+                    .map(|value| *value as f32 / 128.0 - 1.0)
+                    .collect(),
+                step: rate as f32 / self.freq,
+                position: 0.0,
+                is_looped,
+            }),
+            _ => None,
+        };
+    }
+
+    /// Next value of the digitized sound, None when it's finished
+    #[cfg(feature = "megachip")]
+    fn next_digitized(&mut self) -> Option<f32> {
+        let digitized = self.digitized.as_mut()?;
+        let len = digitized.samples.len() as f32;
+        if digitized.position >= len {
+            if !digitized.is_looped {
+                self.digitized = None;
+                return None;
+            }
+            digitized.position %= len;
+        }
+        let value = digitized.samples[digitized.position as usize];
+        digitized.position += digitized.step;
+        Some(value)
+    }
+
+    fn next_value(&mut self) -> f32 {
+        if let Some((data, rate)) = &self.pattern {
+            let bits_count = (data.len() * 8) as f32;
+            let bit = self.pattern_position as usize;
+            self.pattern_position = (self.pattern_position + rate / self.freq) % bits_count;
+            let value = (data[bit / 8] >> (7 - bit % 8)) & 1;
+            return if value > 0 { 1.0 } else { -1.0 };
+        }
+        match &self.source {
+            Source::Sample(sample) => {
+                let value = sample[self.position];
+                self.position = (self.position + 1) % sample.len();
+                value
+            }
+            Source::Wave(waveform) => {
+                let phase = self.phase;
+                self.phase = (self.phase + self.phase_inc) % 1.0;
+                match waveform {
+                    Waveform::Square => {
+                        if phase <= 0.5 {
+                            1.0
+                        } else {
+                            -1.0
+                        }
+                    }
+                    Waveform::Sine => (2.0 * PI * phase).sin(),
+                    Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                    Waveform::Noise => {
+                        // xorshift32
+                        self.noise_state ^= self.noise_state << 13;
+                        self.noise_state ^= self.noise_state >> 17;
+                        self.noise_state ^= self.noise_state << 5;
+                        self.noise_state as f32 / u32::MAX as f32 * 2.0 - 1.0
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            #[cfg(feature = "megachip")]
+            if !self.is_paused {
+                if let Some(value) = self.next_digitized() {
+                    self.record(value);
+                    *x = value * self.volume;
+                    continue;
+                }
+            }
+            self.envelope = if self.is_gate_open {
+                (self.envelope + self.attack_step).min(1.0)
+            } else {
+                (self.envelope - self.release_step).max(0.0)
+            };
+            if self.envelope == 0.0 {
+                // restart from the beginning next time
+                self.position = 0;
+                self.pattern_position = 0.0;
+                self.record(0.0);
+                *x = 0.0;
+                continue;
+            }
+            let value = self.next_value() * self.envelope;
+            self.record(value);
+            *x = value * self.volume;
+        }
+    }
+}
+
+/// Loads WAV file converted to mono f32 samples with given frequency
+fn load_sample<P: AsRef<Path>>(path: P, freq: i32) -> Result<Vec<f32>, String> {
+    let wav = AudioSpecWAV::load_wav(path)?;
+    let converter = AudioCVT::new(
+        wav.format,
+        wav.channels,
+        wav.freq,
+        AudioFormat::f32_sys(),
+        1,
+        freq,
+    )?;
+    let data = converter.convert(wav.buffer().to_vec());
+    let sample = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    Ok(sample)
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_derive::Deserialize;
+
+use crate::chip8::Chip8;
+use crate::config;
+use crate::rom_db;
+
+const CHEATS_DIRECTORY: &str = "cheats";
+
+#[derive(Deserialize)]
+struct CheatsFile {
+    #[serde(default, rename = "cheat")]
+    cheats: Vec<CheatEntry>,
+}
+
+/// Cheat as it appears in the file, codes are `address:bytes` in hex, e.g. `3F0:03`
+#[derive(Deserialize)]
+#[serde(default)]
+struct CheatEntry {
+    name: String,
+    enabled: bool,
+    patch: Vec<String>,  // written once on load and after reset
+    freeze: Vec<String>, // written on every frame, e.g. infinite lives
+}
+
+impl Default for CheatEntry {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            patch: Vec::new(),
+            freeze: Vec::new(),
+        }
+    }
+}
+
+type Code = (usize, Vec<u8>);
+
+pub struct Cheat {
+    pub name: String,
+    pub is_enabled: bool,
+    patches: Vec<Code>,
+    freezes: Vec<Code>,
+}
+
+///
+/// Memory patches and freezes of a ROM, loaded from a TOML file with `[[cheat]]` entries.
+/// By default the file is `$XDG_DATA_HOME/chip8/cheats/<ROM SHA-1>.toml` on Linux
+///
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn with_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let file: CheatsFile =
+            toml::from_str(&content).map_err(|err| format!("{}: {err}", path.display()))?;
+        let cheats = file
+            .cheats
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let parse = |codes: &[String]| {
+                    codes
+                        .iter()
+                        .map(|code| parse_code(code))
+                        .collect::<Result<Vec<_>, _>>()
+                };
+                let name = if entry.name.is_empty() {
+                    format!("Cheat {}", index + 1)
+                } else {
+                    entry.name
+                };
+                Ok(Cheat {
+                    name,
+                    is_enabled: entry.enabled,
+                    patches: parse(&entry.patch)?,
+                    freezes: parse(&entry.freeze)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(Self { cheats })
+    }
+
+    /// Default location of the ROM cheats file
+    pub fn path(rom: &[u8]) -> Option<PathBuf> {
+        let file_name = format!("{}.toml", rom_db::sha1_hex(rom));
+        config::data_path(CHEATS_DIRECTORY).map(|dir| dir.join(file_name))
+    }
+
+    /// Loads the ROM cheats file if it exists
+    pub fn for_rom(rom: &[u8]) -> Result<Option<Self>, String> {
+        match Self::path(rom).filter(|path| path.exists()) {
+            Some(path) => Self::with_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Should be called after the program is loaded or reset
+    pub fn apply_patches(&self, machine: &mut Chip8) {
+        let enabled = self.cheats.iter().filter(|cheat| cheat.is_enabled);
+        enabled
+            .flat_map(|cheat| &cheat.patches)
+            .for_each(|(address, data)| _ = machine.poke(*address, data));
+    }
+
+    /// Should be called on every frame
+    pub fn apply_freezes(&self, machine: &mut Chip8) {
+        let enabled = self.cheats.iter().filter(|cheat| cheat.is_enabled);
+        enabled
+            .flat_map(|cheat| &cheat.freezes)
+            .for_each(|(address, data)| _ = machine.poke(*address, data));
+    }
+
+    /// Disabled patches are reverted to the program bytes
+    pub fn toggle(&mut self, index: usize, machine: &mut Chip8) {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return;
+        };
+        cheat.is_enabled = !cheat.is_enabled;
+        for (address, data) in &cheat.patches {
+            if cheat.is_enabled {
+                _ = machine.poke(*address, data);
+                continue;
+            }
+            let original = (*address..address + data.len())
+                .map(|address| {
+                    address
+                        .checked_sub(machine.program_address())
+                        .and_then(|offset| machine.rom().get(offset))
+                        .copied()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            _ = machine.poke(*address, &original);
+        }
+    }
+}
+
+fn parse_code(code: &str) -> Result<Code, String> {
+    let invalid = || format!("Invalid cheat code: {code}");
+    let (address, bytes) = code.split_once(':').ok_or_else(invalid)?;
+    let address = address.trim().trim_start_matches("0x");
+    let address = usize::from_str_radix(address, 16).map_err(|_| invalid())?;
+    let bytes = bytes.trim();
+    if bytes.is_empty() || bytes.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let data = (0..bytes.len())
+        .step_by(2)
+        .map(|i| {
+            bytes
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((address, data))
+}
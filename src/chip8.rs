@@ -1,163 +1,412 @@
-use rand::{rngs::ThreadRng, Rng};
 ///
-/// Chip8 interpreter
+/// Chip8 interpreter, uses only `core` and `alloc` to be portable to `no_std` targets,
+/// `nostd` crate builds it without std
 ///
-use std::fmt::Display;
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec,
+    vec::Vec,
+};
+use core::fmt::{self, Display};
+use core::time::Duration;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tracing::{debug, trace, warn};
+
+use crate::{
+    common::USize,
+    coverage::Coverage,
+    font::{SmallFont, BIG_FONT, FONTS, SMALL_FONT_SIZE},
+    framebuffer::Framebuffer,
+    journal::{Delta, Journal},
+    opcode::Opcode,
+    platform::{KeyEvent, Platform},
+    quirks::Quirks,
+};
 
-use crate::{common::USize, config::Quirks};
+#[cfg(feature = "megachip")]
+use crate::megachip::{self, MegaChip, SoundCommand};
 
-const MEMORY_SIZE: usize = 4 * 1024;
+const MEMORY_SIZE: usize = 4 * 1024; // Mega-CHIP mode extends it when entered
+#[cfg(not(feature = "megachip"))]
+const MAX_PROGRAM_END: usize = MEMORY_SIZE;
+#[cfg(feature = "megachip")]
+const MAX_PROGRAM_END: usize = megachip::MEMORY_SIZE;
+// the largest classic display, 64x64 of the hi-res interpreter
+const VIDEO_MEMORY_SIZE: usize = 64 * 64;
 const REGISTERS_COUNT: usize = 16;
 const STACK_SIZE: usize = 16;
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
+const PITCH_STEP: f32 = 1.014_545_3; // 2 ^ (1 / 48)
+pub const RPL_FLAGS_COUNT: usize = 8;
+// COSMAC VIP runs at 1.76 MHz, a machine cycle takes 8 clocks
+pub const COSMAC_CYCLES_PER_FRAME: u32 = 3668;
+// fetch and decode overhead of the original interpreter
+const COSMAC_FETCH_CYCLES: u32 = 40;
 
 pub const DISPLAY_SIZE: USize = USize {
     height: 32,
     width: 64,
 };
+const HIRES_DISPLAY_SIZE: USize = USize {
+    height: 64,
+    width: 64,
+};
+const ETI660_DISPLAY_SIZE: USize = USize {
+    height: 48,
+    width: 64,
+};
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("Rom of size {0} bytes is too big")]
     RomTooBig(usize),
-    UnknownInstruction(Instruction),
+    #[error("Unknown instruction: {0:04X}")]
+    UnknownInstruction(u16),
+    #[error("Stack overflow")]
     StackOverflow,
+    #[error("Pop on empty stack")]
     EmptyStack,
+    #[error("Write to interpreter area at {address:03X} by instruction at {pc:03X}")]
+    ProtectedMemoryWrite { address: usize, pc: usize },
+    #[error("Access to {address:04X} outside of memory by instruction at {pc:03X}")]
+    MemoryOutOfBounds { address: usize, pc: usize },
+    #[error("Instruction fetch at {0:04X} outside of memory")]
+    ProgramCounterOutOfBounds(usize),
+    #[error("Invalid key code {0:02X}")]
+    InvalidKeyCode(u8),
+    #[cfg(not(feature = "std"))]
+    #[error("Random generator needs a seed or entropy source")]
+    NoEntropySource, // machine without seed and entropy source
+}
+
+/// Tells the frontend whether the machine may keep running after an error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Warning,
+    Fatal,
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::InvalidKeyCode(_) => Severity::Warning,
+            _ => Severity::Fatal,
+        }
+    }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::RomTooBig(size) => write!(f, "Rom of size {size} bytes is too big"),
-            Self::UnknownInstruction(instr) => write!(f, "Unknown instruction: {instr}"),
-            Self::StackOverflow => write!(f, "Stack overflow"),
-            Self::EmptyStack => write!(f, "Pop on empty stack"),
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
         }
     }
 }
 
+/// Pauses the machine when program reads or writes the memory range
+#[derive(Clone, Copy)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16, // inclusive
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: usize, access: Access) -> bool {
+        let is_access = match access {
+            Access::Read => self.on_read,
+            Access::Write => self.on_write,
+        };
+        is_access && (self.start as usize..=self.end as usize).contains(&address)
+    }
+}
+
+/// Decision of the pre-instruction hook about the instruction at PC
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookAction {
+    Continue,
+    Skip,  // PC moves to the next instruction without executing it
+    Pause, // the instruction is executed when the machine is resumed
+}
+
+/// Called before every instruction with the decoded one, the machine isn't changed yet.
+/// Hooks are `Send` like the rest of the machine, so it can be run by any thread
+pub type InstructionHook = Box<dyn FnMut(&Chip8, Opcode) -> HookAction + Send>;
+
+/// Seed of the random generator provided by the embedder, e.g. read from a hardware generator
+pub type EntropySource = fn() -> u64;
+
+/// Active subroutine call
+#[derive(Clone, Copy)]
+pub struct StackFrame {
+    pub subroutine: Option<usize>, // called address, unknown if the call instruction was overwritten
+    pub return_address: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct WatchpointHit {
+    pub address: usize,
+    pub access: Access,
+    pub pc: usize, // address of the instruction which accessed memory
+}
+
+/// Work for `run_for`, the machine never blocks, so it can be driven by an async task
+#[derive(Clone, Copy)]
+pub enum RunBudget {
+    Instructions(u64), // timers aren't ticked
+    Time {
+        duration: Duration, // emulated time, the timers tick at 60 Hz
+        operations_per_second: u64,
+    },
+}
+
+/// Outcome of `run_for`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RunStatus {
+    Completed,
+    WaitingForKey, // FX0A waits, the caller may await a key event before running again
+    Stopped,       // paused or terminated, see `get_state`
+}
+
 #[derive(Clone, Copy)]
 pub enum State {
     Running,
+    WaitingForKey(usize), // FX0A register, resolved by a key event or `provide_key`
     Paused,
     Terminated,
 }
 
-const FONT_SPRITES: [u8; 5 * 16] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
 const FONT_BASE_ADDRESS: usize = 0x050;
-const STACK_BASE_ADDRESS: usize = 0x010;
-const PROGRAM_BASE_ADDRESS: usize = 0x200;
-const KB_WAIT_KEYCODE_ADDRESS: usize = 0x000;
-
-#[derive(Debug)]
-pub struct Instruction {
-    header: u8,
-    nnn: u16,
-    nn: u8,
-    n: u8,
-    x: usize,
-    y: usize,
-}
+const BIG_FONT_BASE_ADDRESS: usize = FONT_BASE_ADDRESS + SMALL_FONT_SIZE;
+pub const PROGRAM_BASE_ADDRESS: usize = 0x200;
+// hi-res programs start with a jump over the interpreter extension
+const HIRES_ENTRY_OPCODE: [u8; 2] = [0x12, 0x60];
+const HIRES_PROGRAM_ADDRESS: usize = 0x2c0;
+const HIRES_CLEAR_SCREEN: u16 = 0x230;
+// ETI-660 interpreter and its variables take the memory below
+const ETI660_PROGRAM_ADDRESS: usize = 0x600;
 
-impl Instruction {
-    fn with_opcode(opcode: u16) -> Self {
-        Self {
-            header: (opcode >> 12 & 0xf) as u8,
-            nnn: opcode & 0xfff,
-            nn: (opcode & 0xff) as u8,
-            n: (opcode & 0x0f) as u8,
-            x: (opcode >> 8 & 0xf) as usize,
-            y: (opcode >> 4 & 0xf) as usize,
-        }
-    }
+const IDLE_LOOP_MAX_SIZE: usize = 16; // longer loops aren't checked for being idle
 
-    fn with_bytes(high: u8, low: u8) -> Self {
-        Self::with_opcode((high as u16) << 8 | low as u16)
-    }
-}
+const TIMER_FREQUENCY: u64 = 60;
+const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / TIMER_FREQUENCY);
 
-impl Display for Instruction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Header: {:x}, NNN: {:x}, NN: {:x}, N: {:x}, X: {:x}, Y:{:x}",
-            self.header, self.nnn, self.nn, self.n, self.x, self.y
-        )
-    }
-}
+// machines hold no shared or global state, a server may run many of them on a thread pool
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Chip8>();
+};
 
 pub struct Chip8 {
     reg: [u8; REGISTERS_COUNT],
-    ri: u16,   // indexing register
-    dt: u8,    // delay timer
-    st: u8,    // sound time
-    sp: usize, // stack pointer
-    pc: usize, // program counter
-    memory: [u8; MEMORY_SIZE],
-    video_memory: Vec<u8>,
-    keypad: [bool; 0x10], // true if key pressed
+    ri: u32,                                 // indexing register
+    dt: u8,                                  // delay timer
+    st: u8,                                  // sound time
+    is_sound_reported: bool,                 // sound state the platform was notified about
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE], // XO-CHIP 1-bit audio samples
+    is_audio_pattern_loaded: bool,
+    pitch: u8,                        // XO-CHIP audio playback rate
+    scroll_remainder: (isize, isize), // half pixels of lores scrolls not applied yet
+    rpl_flags: [u8; RPL_FLAGS_COUNT], // SCHIP user flags, survive reset
+    is_rpl_flags_changed: bool,
+    stack: [u16; STACK_SIZE],
+    sp: usize,      // stack pointer
+    peak_sp: usize, // the deepest nesting of calls since the start, survives reset
+    pc: usize,      // program counter
+    memory: Vec<u8>,
+    video_memory: [u8; VIDEO_MEMORY_SIZE], // pixels of the current resolution come first
+    display_size: USize,
+    is_display_changed: bool, // video memory was modified since last check
+    keypad: [bool; 0x10],     // true if key pressed
+    key_events: VecDeque<KeyEvent>, // applied one per instruction, so short taps aren't lost
+    key_hold: [u8; 0x10],     // timer ticks left before the pressed key can be released
+    key_release_delay: u8,    // timer ticks the key stays pressed at least
+    wait_key: Option<u8>,     // key pressed while FX0A waits for its release
+    pending_time: Duration,   // `run_for` time shorter than a timer tick
+    pending_operations: u64,  // `run_for` instructions budget remainder, in 1/60 units
     state: State,
-    rng: ThreadRng,
+    rng: StdRng,
+    seed: Option<u64>,
+    entropy: Option<EntropySource>,
+    cycles: u64, // executed instructions count
     rom: Vec<u8>,
     quirks: Quirks,
+    watchpoints: Vec<Watchpoint>,
+    watchpoint_hit: Option<WatchpointHit>,
+    step_depth: Option<usize>, // machine pauses when the stack unwinds to this depth
+    is_step_completed: bool,
+    instruction_address: usize, // address of the instruction being executed
+    program_address: usize,     // the first byte of the loaded program
+    base_address: Option<usize>, // custom program address instead of the layout one
+    is_strict_memory: bool,     // writes to the interpreter area are errors
+    last_opcode: Option<u16>,   // the last executed instruction
+    loop_probe: Option<(usize, [u8; REGISTERS_COUNT], u32)>, // backward jump with registers and I
+    is_idle: bool,              // the last instruction spins until a timer or a key changes
+    font: SmallFont,
+    unknown_opcodes: Option<BTreeMap<u16, u64>>, // skipped instructions count in permissive mode
+    coverage: Option<Coverage>,
+    memory_writes: Option<Vec<(usize, u8)>>, // program writes since the last take
+    journal: Option<Journal>,                // executed instructions which can be undone
+    pre_instruction_hook: Option<InstructionHook>,
+    #[cfg(feature = "megachip")]
+    megachip: Option<MegaChip>, // present while Mega-CHIP mode is on
 }
 
 impl Chip8 {
-    pub fn with_rom(rom: Vec<u8>, quirks: Quirks) -> Result<Self, Error> {
-        if rom.len() > MEMORY_SIZE - PROGRAM_BASE_ADDRESS {
-            return Err(Error::RomTooBig(rom.len()));
-        }
-        let mut machine = Self {
+    pub fn builder(rom: Vec<u8>) -> Chip8Builder {
+        Chip8Builder::new(rom)
+    }
+
+    /// Machine without program, it is started by `load_rom`
+    fn new(quirks: Quirks, seed: Option<u64>) -> Self {
+        Self {
             reg: [0u8; REGISTERS_COUNT],
             ri: 0,
             dt: 0,
             st: 0,
+            is_sound_reported: false,
+            audio_pattern: [0u8; AUDIO_PATTERN_SIZE],
+            is_audio_pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            scroll_remainder: (0, 0),
+            rpl_flags: [0u8; RPL_FLAGS_COUNT],
+            is_rpl_flags_changed: false,
+            stack: [0u16; STACK_SIZE],
             sp: 0,
+            peak_sp: 0,
             pc: PROGRAM_BASE_ADDRESS,
-            memory: [0u8; MEMORY_SIZE],
-            video_memory: vec![0u8; DISPLAY_SIZE.square()],
+            memory: vec![0u8; MEMORY_SIZE],
+            video_memory: [0u8; VIDEO_MEMORY_SIZE],
+            display_size: DISPLAY_SIZE,
+            is_display_changed: true,
             keypad: [false; 0x10],
+            key_events: VecDeque::new(),
+            key_hold: [0; 0x10],
+            key_release_delay: 0,
+            wait_key: None,
+            pending_time: Duration::ZERO,
+            pending_operations: 0,
             state: State::Paused,
-            rng: rand::thread_rng(),
-            rom,
+            rng: StdRng::seed_from_u64(0), // seeded on the program load
+            seed,
+            entropy: None,
+            cycles: 0,
+            rom: Vec::new(),
             quirks,
-        };
-        machine.reset();
-        Ok(machine)
+            watchpoints: Vec::new(),
+            watchpoint_hit: None,
+            step_depth: None,
+            is_step_completed: false,
+            instruction_address: PROGRAM_BASE_ADDRESS,
+            program_address: PROGRAM_BASE_ADDRESS,
+            base_address: None,
+            is_strict_memory: false,
+            last_opcode: None,
+            loop_probe: None,
+            is_idle: false,
+            font: FONTS[0].sprites,
+            unknown_opcodes: None,
+            coverage: None,
+            memory_writes: None,
+            journal: None,
+            pre_instruction_hook: None,
+            #[cfg(feature = "megachip")]
+            megachip: None,
+        }
+    }
+
+    /// Replaces the program and restarts the machine
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), Error> {
+        if rom.len() > MAX_PROGRAM_END.saturating_sub(self.layout_address()) {
+            return Err(Error::RomTooBig(rom.len()));
+        }
+        self.rom = rom;
+        // coverage of the previous program is irrelevant
+        if self.coverage.is_some() {
+            self.enable_coverage();
+        }
+        self.reset();
+        Ok(())
     }
 
     pub fn reset(&mut self) {
+        // memory of Mega-CHIP mode is released
+        self.memory.truncate(MEMORY_SIZE);
+        self.memory.shrink_to_fit();
         self.memory.iter_mut().for_each(|x| *x = 0);
-        for (i, val) in self.rom.iter().enumerate() {
-            self.memory[PROGRAM_BASE_ADDRESS + i] = *val
-        }
-        for (i, val) in FONT_SPRITES.iter().enumerate() {
-            self.memory[FONT_BASE_ADDRESS + i] = *val;
+        // layout quirk could be changed after the program was checked
+        self.program_address = self.layout_address().min(MEMORY_SIZE);
+        let program = &mut self.memory[self.program_address..];
+        for (cell, val) in program.iter_mut().zip(&self.rom) {
+            *cell = *val;
         }
+        self.load_fonts();
         self.reg.iter_mut().for_each(|x| *x = 0);
         self.ri = 0;
         self.dt = 0;
         self.st = 0;
+        self.audio_pattern.iter_mut().for_each(|x| *x = 0);
+        self.is_audio_pattern_loaded = false;
+        self.pitch = DEFAULT_PITCH;
+        self.scroll_remainder = (0, 0);
+        self.stack.iter_mut().for_each(|x| *x = 0);
         self.sp = 0;
-        self.pc = PROGRAM_BASE_ADDRESS;
-        self.video_memory.iter_mut().for_each(|x| *x = 0);
+        self.pc = self.program_address;
+        let is_hires = self.quirks.hires && self.rom.starts_with(&HIRES_ENTRY_OPCODE);
+        if self.quirks.eti660 {
+            self.display_size = ETI660_DISPLAY_SIZE;
+        } else if is_hires {
+            self.display_size = HIRES_DISPLAY_SIZE;
+            self.pc = HIRES_PROGRAM_ADDRESS;
+        } else {
+            self.display_size = DISPLAY_SIZE;
+        }
+        self.video_memory = [0u8; VIDEO_MEMORY_SIZE];
+        self.is_display_changed = true;
         self.keypad.iter_mut().for_each(|x| *x = false);
+        self.key_events.clear();
+        self.key_hold.iter_mut().for_each(|x| *x = 0);
+        self.wait_key = None;
+        self.pending_time = Duration::ZERO;
+        self.pending_operations = 0;
+        self.loop_probe = None;
+        self.is_idle = false;
+        self.rng = self.make_rng();
+        self.watchpoint_hit = None;
+        self.step_depth = None;
+        self.is_step_completed = false;
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+        #[cfg(feature = "megachip")]
+        {
+            self.megachip = None;
+        }
         self.state = State::Running;
+        debug!(is_hires, "Machine reset");
+    }
+
+    /// Seeded by the fixed seed, otherwise by the embedder entropy source or, with `std`, by OS
+    fn make_rng(&self) -> StdRng {
+        match (self.seed, self.entropy) {
+            (Some(seed), _) => StdRng::seed_from_u64(seed),
+            (None, Some(entropy)) => StdRng::seed_from_u64(entropy()),
+            #[cfg(feature = "std")]
+            (None, None) => StdRng::from_entropy(),
+            // rejected by the builder
+            #[cfg(not(feature = "std"))]
+            (None, None) => unreachable!("no seed and entropy source"),
+        }
     }
 
     pub fn get_state(&self) -> State {
@@ -169,93 +418,469 @@ impl Chip8 {
     }
 
     pub fn toggle_execution(&mut self) {
-        self.state = match self.state {
-            State::Paused => State::Running,
-            State::Running => State::Paused,
-            State::Terminated => State::Terminated,
+        self.step_depth = None;
+        match self.state {
+            State::Paused => self.state = State::Running,
+            State::Running | State::WaitingForKey(_) => self.enter_pause(),
+            State::Terminated => {}
         }
     }
 
+    /// True if the machine proceeds by itself or after a key press
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, State::Running | State::WaitingForKey(_))
+    }
+
+    /// Paused machine can't keep waiting for a key, FX0A is executed again on resume
+    fn enter_pause(&mut self) {
+        if let State::WaitingForKey(_) = self.state {
+            self.pc = self.instruction_address;
+        }
+        self.state = State::Paused;
+    }
+
     pub fn on_timer(&mut self) {
         self.dt = self.dt.saturating_sub(1);
         self.st = self.st.saturating_sub(1);
+        self.key_hold
+            .iter_mut()
+            .for_each(|x| *x = x.saturating_sub(1));
     }
 
-    pub fn teak(&mut self) -> Result<(), Error> {
-        let instr = Instruction::with_bytes(self.memory[self.pc], self.memory[self.pc + 1]);
-        self.pc += 2;
-        let (nnn, nn, n, x, y) = (instr.nnn, instr.nn, instr.n, instr.x, instr.y);
-        match instr.header {
-            0x0 => match nnn {
-                0xe0 => self.op_clear_screen(),
-                0xee => self.op_return()?,
-                _ => {
-                    // ignore machine code routine calls
+    /// Unknown instruction terminates the machine unless it runs in permissive mode
+    fn unknown_instruction(&mut self, opcode: u16) -> Result<(), Error> {
+        let Some(histogram) = &mut self.unknown_opcodes else {
+            return Err(Error::UnknownInstruction(opcode));
+        };
+        let count = histogram.entry(opcode).or_default();
+        if *count == 0 {
+            warn!(
+                "Skipped unknown instruction {opcode:04X} at {:03X}",
+                self.instruction_address
+            );
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Ticks the timers and notifies the platform if the sound started or stopped,
+    /// so the beep lasts exactly as many ticks as the sound timer value
+    pub fn on_timer_with<P: Platform>(&mut self, platform: &mut P) {
+        self.on_timer();
+        self.report_sound(platform);
+    }
+
+    /// Runs the machine until the budget is spent or it can't proceed, it never waits itself.
+    /// The time budget ticks the timers, idle frames and frames spent waiting for a key
+    /// skip the rest of their instructions, the time shorter than a tick is carried over
+    pub fn run_for(&mut self, budget: RunBudget) -> Result<RunStatus, Error> {
+        match budget {
+            RunBudget::Instructions(count) => {
+                for _ in 0..count {
+                    if let Some(status) = self.teak_status()? {
+                        return Ok(status);
+                    }
                 }
-            },
-            0x1 => self.op_jmp(nnn),
-            0x2 => self.op_call(nnn)?,
-            0x3 => self.op_skip_eq(x, nn),
-            0x4 => self.op_skip_ne(x, nn),
-            0x5 => self.op_skip_reg_eq(x, y),
-            0x6 => self.op_mov(x, nn),
-            0x7 => self.op_add(x, nn),
-            0x8 => match n {
-                0x0 => self.op_reg_mov(x, y),
-                0x1 => self.op_or(x, y),
-                0x2 => self.op_and(x, y),
-                0x3 => self.op_xor(x, y),
-                0x4 => self.op_reg_add(x, y),
-                0x5 => self.op_reg_sub(x, y),
-                0x6 => self.op_shr(x, y),
-                0x7 => self.op_reg_sub_rev(x, y),
-                0xe => self.op_shl(x, y),
-                _ => {
-                    return Err(Error::UnknownInstruction(instr));
+            }
+            RunBudget::Time {
+                duration,
+                operations_per_second,
+            } => {
+                self.pending_time += duration;
+                while self.pending_time >= TIMER_PERIOD {
+                    self.pending_time -= TIMER_PERIOD;
+                    self.pending_operations += operations_per_second;
+                    // a key event or the last tick may let the program proceed
+                    self.is_idle = false;
+                    while self.pending_operations >= TIMER_FREQUENCY {
+                        self.pending_operations -= TIMER_FREQUENCY;
+                        if self.is_idle {
+                            continue;
+                        }
+                        if let Some(RunStatus::Stopped) = self.teak_status()? {
+                            return Ok(RunStatus::Stopped);
+                        }
+                    }
+                    self.on_timer();
                 }
-            },
-            0x9 => self.op_skip_reg_ne(x, y),
-            0xa => self.op_mov_ptr(nnn),
-            0xb => self.op_reg_jmp(nnn),
-            0xc => self.op_rand(x, nn),
-            0xd => self.op_display(x, y, n),
-            0xe => match nn {
-                0x9e => self.op_skip_key_eq(x),
-                0xa1 => self.op_skip_key_ne(x),
-                _ => {
-                    return Err(Error::UnknownInstruction(instr));
+                if self.is_waiting_for_key() {
+                    return Ok(RunStatus::WaitingForKey);
                 }
-            },
-            0xf => match nn {
-                0x07 => self.op_dump_delay(x),
-                0x0a => self.op_wait_key(x),
-                0x15 => self.op_set_delay(x),
-                0x18 => self.op_set_sound(x),
-                0x1e => self.op_ptr_add(x),
-                0x29 => self.op_mov_font_addr(x),
-                0x33 => self.op_bdc(x),
-                0x55 => self.op_reg_dump(x),
-                0x65 => self.op_reg_load(x),
+            }
+        }
+        Ok(RunStatus::Completed)
+    }
+
+    /// Executes an instruction, returns the status if the machine can't proceed
+    fn teak_status(&mut self) -> Result<Option<RunStatus>, Error> {
+        if !self.is_running() {
+            return Ok(Some(RunStatus::Stopped));
+        }
+        self.teak()?;
+        if self.is_waiting_for_key() {
+            return Ok(Some(RunStatus::WaitingForKey));
+        }
+        Ok(None)
+    }
+
+    /// Executes an instruction, the machine waiting for a key only applies the next key event
+    pub fn teak(&mut self) -> Result<(), Error> {
+        self.is_idle = false;
+        self.apply_key_event();
+        if let State::WaitingForKey(x) = self.state {
+            self.poll_wait_key(x);
+            return Ok(());
+        }
+        let is_paused = matches!(self.state, State::Paused);
+        self.instruction_address = self.pc;
+        let opcode = self.fetch_opcode(self.pc)?;
+        let instruction = Opcode::decode(opcode);
+        match self.run_pre_instruction_hook(instruction) {
+            HookAction::Continue => {}
+            HookAction::Skip => {
+                self.pc += 2;
+                return Ok(());
+            }
+            HookAction::Pause => {
+                self.state = State::Paused;
+                return Ok(());
+            }
+        }
+        self.begin_delta();
+        #[cfg(feature = "megachip")]
+        let was_megachip = self.megachip.is_some();
+        trace!(
+            pc = format_args!("{:03X}", self.pc),
+            opcode = format_args!("{opcode:04X}"),
+            "execute"
+        );
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark_executed(self.pc);
+            coverage.mark_executed(self.pc + 1);
+        }
+        self.pc += 2;
+        self.cycles += 1;
+        self.last_opcode = Some(opcode);
+        match instruction {
+            Opcode::ClearScreen => self.op_clear_screen(),
+            Opcode::Return => self.op_return()?,
+            Opcode::System(nnn) => match nnn {
+                HIRES_CLEAR_SCREEN if self.is_hires() => self.op_clear_screen(),
+                #[cfg(feature = "megachip")]
+                0x010 | 0x011 | 0x0b0..=0x0bf | 0x100..=0x9ff => self.op_megachip(nnn)?,
                 _ => {
-                    return Err(Error::UnknownInstruction(instr));
+                    // ignore machine code routine calls
                 }
             },
-            _ => {
-                return Err(Error::UnknownInstruction(instr));
+            Opcode::ScrollDown(n) => self.op_scroll(0, n as isize),
+            Opcode::ScrollUp(n) => self.op_scroll(0, -(n as isize)),
+            Opcode::ScrollRight => self.op_scroll(4, 0),
+            Opcode::ScrollLeft => self.op_scroll(-4, 0),
+            Opcode::Jump(nnn) => self.op_jmp(nnn),
+            Opcode::Call(nnn) => self.op_call(nnn)?,
+            Opcode::SkipEq { x, nn } => self.op_skip_eq(x, nn),
+            Opcode::SkipNe { x, nn } => self.op_skip_ne(x, nn),
+            Opcode::SkipRegEq { x, y } => self.op_skip_reg_eq(x, y),
+            Opcode::Load { x, nn } => self.op_mov(x, nn),
+            Opcode::Add { x, nn } => self.op_add(x, nn),
+            Opcode::Move { x, y } => self.op_reg_mov(x, y),
+            Opcode::Or { x, y } => self.op_or(x, y),
+            Opcode::And { x, y } => self.op_and(x, y),
+            Opcode::Xor { x, y } => self.op_xor(x, y),
+            Opcode::AddReg { x, y } => self.op_reg_add(x, y),
+            Opcode::Sub { x, y } => self.op_reg_sub(x, y),
+            Opcode::ShiftRight { x, y } => self.op_shr(x, y),
+            Opcode::SubReversed { x, y } => self.op_reg_sub_rev(x, y),
+            Opcode::ShiftLeft { x, y } => self.op_shl(x, y),
+            Opcode::SkipRegNe { x, y } => self.op_skip_reg_ne(x, y),
+            Opcode::LoadIndex(nnn) => self.op_mov_ptr(nnn),
+            Opcode::JumpOffset(nnn) => self.op_reg_jmp(nnn),
+            Opcode::Random { x, nn } => self.op_rand(x, nn),
+            Opcode::Draw { x, y, n } => self.op_display(x, y, n)?,
+            Opcode::SkipKeyPressed { x } => self.op_skip_key_eq(x),
+            Opcode::SkipKeyReleased { x } => self.op_skip_key_ne(x),
+            Opcode::LoadAudio => self.op_load_audio()?,
+            Opcode::LoadDelay { x } => self.op_dump_delay(x),
+            Opcode::WaitKey { x } => self.op_wait_key(x),
+            Opcode::SetDelay { x } => self.op_set_delay(x),
+            Opcode::SetSound { x } => self.op_set_sound(x),
+            Opcode::AddIndex { x } => self.op_ptr_add(x),
+            Opcode::LoadFont { x } => self.op_mov_font_addr(x),
+            Opcode::LoadBigFont { x } => self.op_mov_big_font_addr(x),
+            Opcode::Bcd { x } => self.op_bdc(x)?,
+            Opcode::SetPitch { x } => self.op_set_pitch(x),
+            Opcode::StoreRegisters { x } => self.op_reg_dump(x)?,
+            Opcode::LoadRegisters { x } => self.op_reg_load(x)?,
+            Opcode::StoreFlags { x } => self.op_rpl_store(x),
+            Opcode::LoadFlags { x } => self.op_rpl_load(x),
+            Opcode::Unknown(opcode) => self.unknown_instruction(opcode)?,
+        }
+        // Mega-CHIP display and sound state isn't journaled
+        #[cfg(feature = "megachip")]
+        if was_megachip || self.megachip.is_some() {
+            if let Some(journal) = &mut self.journal {
+                journal.clear();
             }
         }
+        self.detect_idle(instruction);
+        if self.step_depth.is_some_and(|depth| self.sp <= depth) {
+            debug!("Step completed at {:03X}", self.pc);
+            self.step_depth = None;
+            self.is_step_completed = true;
+            self.enter_pause();
+        } else if is_paused {
+            // single step of the debugger doesn't resume the machine
+            self.enter_pause();
+        }
         Ok(())
     }
 
+    /// The hook is taken out for the call, so it sees the machine immutable
+    fn run_pre_instruction_hook(&mut self, instruction: Opcode) -> HookAction {
+        let Some(mut hook) = self.pre_instruction_hook.take() else {
+            return HookAction::Continue;
+        };
+        let action = hook(self, instruction);
+        self.pre_instruction_hook = Some(hook);
+        action
+    }
+
+    /// The program spins if it jumps to itself, waits for a key or repeats a short
+    /// register only loop with the same registers, only a timer tick or a key changes that
+    fn detect_idle(&mut self, instruction: Opcode) {
+        if !self.key_events.is_empty() {
+            return;
+        }
+        if self.pc == self.instruction_address {
+            self.is_idle = true;
+            return;
+        }
+        let loop_start = self.pc;
+        if !matches!(instruction, Opcode::Jump(_)) || loop_start > self.instruction_address {
+            return;
+        }
+        let probe = (self.instruction_address, self.reg, self.ri);
+        if self.loop_probe == Some(probe) {
+            self.is_idle = self.is_register_only_loop(loop_start);
+        }
+        self.loop_probe = Some(probe);
+    }
+
+    fn is_register_only_loop(&self, loop_start: usize) -> bool {
+        let loop_end = self.instruction_address;
+        if loop_end - loop_start > IDLE_LOOP_MAX_SIZE * 2 {
+            return false;
+        }
+        (loop_start..=loop_end).step_by(2).all(|address| {
+            let Ok(opcode) = self.fetch_opcode(address) else {
+                return false;
+            };
+            matches!(
+                Opcode::decode(opcode),
+                Opcode::Jump(_)
+                    | Opcode::SkipEq { .. }
+                    | Opcode::SkipNe { .. }
+                    | Opcode::SkipRegEq { .. }
+                    | Opcode::Load { .. }
+                    | Opcode::Add { .. }
+                    | Opcode::Move { .. }
+                    | Opcode::Or { .. }
+                    | Opcode::And { .. }
+                    | Opcode::Xor { .. }
+                    | Opcode::AddReg { .. }
+                    | Opcode::Sub { .. }
+                    | Opcode::ShiftRight { .. }
+                    | Opcode::SubReversed { .. }
+                    | Opcode::ShiftLeft { .. }
+                    | Opcode::SkipRegNe { .. }
+                    | Opcode::LoadIndex(_)
+                    | Opcode::SkipKeyPressed { .. }
+                    | Opcode::SkipKeyReleased { .. }
+                    | Opcode::LoadDelay { .. }
+                    | Opcode::AddIndex { .. }
+                    | Opcode::LoadFont { .. }
+                    | Opcode::LoadBigFont { .. }
+            )
+        })
+    }
+
+    /// Applies the oldest keypad event before the instruction,
+    /// release waits until the key was held for the release delay
+    fn apply_key_event(&mut self) {
+        let Some(&event) = self.key_events.front() else {
+            return;
+        };
+        match event {
+            KeyEvent::Down(key_code) => {
+                self.keypad[key_code as usize] = true;
+                self.key_hold[key_code as usize] = self.key_release_delay;
+            }
+            KeyEvent::Up(key_code) if self.key_hold[key_code as usize] > 0 => return,
+            KeyEvent::Up(key_code) => self.keypad[key_code as usize] = false,
+        }
+        self.key_events.pop_front();
+    }
+
+    fn begin_delta(&mut self) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        journal.begin(Delta {
+            reg: self.reg,
+            ri: self.ri,
+            pc: self.pc,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+            pitch: self.pitch,
+            scroll_remainder: self.scroll_remainder,
+            wait_key: self.wait_key,
+            cycles: self.cycles,
+            stack: None,
+            memory: Vec::new(),
+            pixels: Vec::new(),
+            video_memory: None,
+            audio_pattern: None,
+            rpl_flags: None,
+            rng: None,
+        });
+    }
+
+    /// Delta of the executed instruction, None if the journal is disabled
+    fn delta(&mut self) -> Option<&mut Delta> {
+        self.journal.as_mut().and_then(|journal| journal.current())
+    }
+
+    fn fetch_opcode(&self, address: usize) -> Result<u16, Error> {
+        match self.memory.get(address..address.saturating_add(2)) {
+            Some(&[high, low]) => Ok((high as u16) << 8 | low as u16),
+            _ => Err(Error::ProgramCounterOutOfBounds(address)),
+        }
+    }
+
+    fn fetch_memory(&self, address: usize) -> Result<u8, Error> {
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or(Error::MemoryOutOfBounds {
+                address,
+                pc: self.instruction_address,
+            })
+    }
+
+    /// Approximate execution time of the instruction by the original COSMAC VIP interpreter
+    /// in machine cycles, conditional branches and page crossings aren't taken into account
+    fn cosmac_cycles(&self, opcode: Opcode) -> u32 {
+        match opcode {
+            Opcode::ClearScreen => 24,
+            Opcode::Return => 10,
+            Opcode::Jump(_) | Opcode::Move { .. } | Opcode::LoadIndex(_) => 12,
+            Opcode::Call(_) => 26,
+            Opcode::SkipEq { .. } | Opcode::SkipNe { .. } | Opcode::Add { .. } => 10,
+            Opcode::SkipRegEq { .. } | Opcode::SkipRegNe { .. } => 14,
+            Opcode::Load { .. } => 6,
+            Opcode::Or { .. }
+            | Opcode::And { .. }
+            | Opcode::Xor { .. }
+            | Opcode::AddReg { .. }
+            | Opcode::Sub { .. }
+            | Opcode::ShiftRight { .. }
+            | Opcode::SubReversed { .. }
+            | Opcode::ShiftLeft { .. } => 44,
+            Opcode::JumpOffset(_) => 22,
+            Opcode::Random { .. } => 36,
+            Opcode::Draw { n, .. } => 68 + n as u32 * 46,
+            Opcode::SkipKeyPressed { .. } | Opcode::SkipKeyReleased { .. } => 14,
+            Opcode::AddIndex { .. } | Opcode::LoadFont { .. } => 16,
+            Opcode::Bcd { x } => {
+                let val = self.reg[x];
+                let digits = (val / 100 % 10 + val / 10 % 10 + val % 10) as u32;
+                84 + digits * 16
+            }
+            Opcode::StoreRegisters { x }
+            | Opcode::LoadRegisters { x }
+            | Opcode::StoreFlags { x }
+            | Opcode::LoadFlags { x } => 14 + x as u32 * 14,
+            Opcode::LoadAudio
+            | Opcode::LoadDelay { .. }
+            | Opcode::WaitKey { .. }
+            | Opcode::SetDelay { .. }
+            | Opcode::SetSound { .. }
+            | Opcode::LoadBigFont { .. }
+            | Opcode::SetPitch { .. } => 10,
+            Opcode::System(_)
+            | Opcode::ScrollDown(_)
+            | Opcode::ScrollUp(_)
+            | Opcode::ScrollRight
+            | Opcode::ScrollLeft
+            | Opcode::Unknown(_) => 0,
+        }
+    }
+
+    /// Program's memory read, checked against watchpoints
+    fn read_memory(&mut self, address: usize) -> Result<u8, Error> {
+        let value = self.fetch_memory(address)?;
+        self.check_watchpoints(address, Access::Read);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark_read(address);
+        }
+        Ok(value)
+    }
+
+    /// Program's memory write, checked against watchpoints
+    /// and protected interpreter area in strict mode
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Error> {
+        if self.is_strict_memory && address < self.program_address {
+            return Err(Error::ProtectedMemoryWrite {
+                address,
+                pc: self.instruction_address,
+            });
+        }
+        if address >= self.memory.len() {
+            return Err(Error::MemoryOutOfBounds {
+                address,
+                pc: self.instruction_address,
+            });
+        }
+        self.check_watchpoints(address, Access::Write);
+        let prev = self.memory[address];
+        if let Some(delta) = self.delta() {
+            delta.memory.push((address, prev));
+        }
+        self.memory[address] = value;
+        if let Some(writes) = &mut self.memory_writes {
+            writes.push((address, value));
+        }
+        Ok(())
+    }
+
+    fn check_watchpoints(&mut self, address: usize, access: Access) {
+        if self.watchpoint_hit.is_some() {
+            return;
+        }
+        if self.watchpoints.iter().any(|w| w.matches(address, access)) {
+            debug!("Watchpoint {access} {address:03X}");
+            self.watchpoint_hit = Some(WatchpointHit {
+                address,
+                access,
+                pc: self.instruction_address,
+            });
+            // the instruction is completed, the machine stops before the next one
+            self.state = State::Paused;
+        }
+    }
+
     fn push(&mut self, value: u16) -> Result<(), Error> {
         if self.sp == STACK_SIZE {
             return Err(Error::StackOverflow);
         }
-        let high = (value >> 8) as u8;
-        let low = (value & 0xff) as u8;
-        self.memory[STACK_BASE_ADDRESS + self.sp * 2] = high;
-        self.memory[STACK_BASE_ADDRESS + self.sp * 2 + 1] = low;
+        let slot = (self.sp, self.stack[self.sp]);
+        if let Some(delta) = self.delta() {
+            delta.stack = Some(slot);
+        }
+        self.stack[self.sp] = value;
         self.sp += 1;
+        self.peak_sp = self.peak_sp.max(self.sp);
         Ok(())
     }
 
@@ -264,13 +889,17 @@ impl Chip8 {
             return Err(Error::EmptyStack);
         }
         self.sp -= 1;
-        let high = self.memory[STACK_BASE_ADDRESS + self.sp * 2] as u16;
-        let low = self.memory[STACK_BASE_ADDRESS + self.sp * 2 + 1] as u16;
-        Ok(high << 8 | low)
+        Ok(self.stack[self.sp])
     }
 
     fn op_clear_screen(&mut self) {
+        #[cfg(feature = "megachip")]
+        if let Some(megachip) = &mut self.megachip {
+            megachip.clear();
+        }
+        self.save_screen();
         self.video_memory.iter_mut().for_each(|val| *val = 0);
+        self.is_display_changed = true;
     }
 
     fn op_return(&mut self) -> Result<(), Error> {
@@ -391,7 +1020,7 @@ impl Chip8 {
     }
 
     fn op_mov_ptr(&mut self, address: u16) {
-        self.ri = address;
+        self.ri = address as u32;
     }
 
     fn op_reg_jmp(&mut self, address: u16) {
@@ -404,72 +1033,234 @@ impl Chip8 {
     }
 
     fn op_rand(&mut self, x: usize, value: u8) {
+        if self.journal.is_some() {
+            let rng = Box::new(self.rng.clone());
+            if let Some(delta) = self.delta() {
+                delta.rng = Some(rng);
+            }
+        }
         self.reg[x] = value & self.rng.gen::<u8>();
     }
 
-    fn op_display(&mut self, x: usize, y: usize, height: u8) {
+    fn op_display(&mut self, x: usize, y: usize, height: u8) -> Result<(), Error> {
+        #[cfg(feature = "megachip")]
+        if self.megachip.is_some() {
+            return self.op_display_megachip(x, y);
+        }
         let height = height as usize;
-        let row = self.reg[y] as usize % DISPLAY_SIZE.height;
-        let col = self.reg[x] as usize % DISPLAY_SIZE.width;
+        let size = self.display_size;
+        let row = self.reg[y] as usize % size.height;
+        let col = self.reg[x] as usize % size.width;
         let ptr = self.ri as usize;
         self.reg[0xf] = 0;
-        for (i, val) in self.memory[ptr..ptr + height].iter().enumerate() {
+        self.is_display_changed = true;
+        for i in 0..height {
             let r = row + i;
-            if r >= DISPLAY_SIZE.height {
+            if r >= size.height {
                 break;
             }
-            for j in 0..8 {
+            let val = self.read_memory(ptr + i)?;
+            // only set bits of the sprite row flip pixels
+            for j in (0..8).filter(|j| val >> (7 - j) & 1 > 0) {
                 let c = col + j;
-                if c >= DISPLAY_SIZE.width {
+                if c >= size.width {
                     break;
                 }
-                let idx = r * DISPLAY_SIZE.width + c;
-                let prev = self.video_memory[idx];
-                let pixel = (val >> (7 - j)) & 1;
-                if prev & pixel > 0 {
+                let idx = r * size.width + c;
+                if self.video_memory[idx] > 0 {
                     self.reg[0xf] = 1;
                 }
-                self.video_memory[idx] ^= pixel;
+                if let Some(delta) = self.delta() {
+                    delta.pixels.push(idx);
+                }
+                self.video_memory[idx] ^= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shifts the display by SCHIP amounts, pixels moved out are lost and the freed area is dark.
+    /// With the half scroll quirk the lores amounts are in SCHIP 128x64 pixels as on HP48:
+    /// odd amounts leave a half pixel which is added to the next scroll along the axis
+    fn op_scroll(&mut self, dx: isize, dy: isize) {
+        let (dx, dy) = if self.quirks.half_scroll && !self.is_hires() {
+            let (rest_x, rest_y) = self.scroll_remainder;
+            let (half_x, half_y) = (rest_x + dx, rest_y + dy);
+            self.scroll_remainder = (half_x % 2, half_y % 2);
+            (half_x / 2, half_y / 2)
+        } else {
+            (dx, dy)
+        };
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        self.save_screen();
+        let screen = self.video_memory;
+        let size = self.display_size;
+        for row in 0..size.height {
+            for col in 0..size.width {
+                let (src_row, src_col) = (row as isize - dy, col as isize - dx);
+                let is_inside = (0..size.height as isize).contains(&src_row)
+                    && (0..size.width as isize).contains(&src_col);
+                self.video_memory[row * size.width + col] = if is_inside {
+                    screen[src_row as usize * size.width + src_col as usize]
+                } else {
+                    0
+                };
+            }
+        }
+        self.is_display_changed = true;
+    }
+
+    /// Keeps the screen before it's cleared or scrolled for undo
+    fn save_screen(&mut self) {
+        if self.journal.is_none() {
+            return;
+        }
+        let screen = self.get_video_ram().to_vec();
+        if let Some(delta) = self.delta() {
+            delta.video_memory.get_or_insert(screen);
+        }
+    }
+
+    /// Mega-CHIP sprites are drawn from palette indices of configured size
+    #[cfg(feature = "megachip")]
+    fn op_display_megachip(&mut self, x: usize, y: usize) -> Result<(), Error> {
+        let size = self.megachip.as_ref().map(|m| m.sprite_size()).unwrap_or(0);
+        let ptr = self.ri as usize;
+        let sprite = (0..size)
+            .map(|i| self.read_memory(ptr + i))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (col, row) = (self.reg[x] as usize, self.reg[y] as usize);
+        if let Some(megachip) = &mut self.megachip {
+            self.reg[0xf] = megachip.draw(&sprite, col, row) as u8;
+        }
+        Ok(())
+    }
+
+    /// Dispatches Mega-CHIP specific 0NNN instructions
+    #[cfg(feature = "megachip")]
+    fn op_megachip(&mut self, nnn: u16) -> Result<(), Error> {
+        let nn = (nnn & 0xff) as u8;
+        if nnn == 0x011 {
+            self.enter_megachip();
+            return Ok(());
+        }
+        let Some(megachip) = &mut self.megachip else {
+            // Mega-CHIP instructions are ignored in the regular mode
+            return Ok(());
+        };
+        let ptr = self.ri as usize;
+        match nnn >> 8 {
+            0x0 if nnn == 0x010 => {
+                self.megachip = None;
+                self.scroll_remainder = (0, 0);
+                self.is_display_changed = true;
+            }
+            0x0 => megachip.scroll_up((nnn & 0xf) as usize),
+            0x1 => {
+                // the lowest 16 bits of I are stored in the next word
+                let high = self.fetch_memory(self.pc)? as u32;
+                let low = self.fetch_memory(self.pc + 1)? as u32;
+                self.ri = (nn as u32) << 16 | high << 8 | low;
+                self.pc += 2;
+            }
+            0x2 => {
+                let end = (ptr + nn as usize * 4).min(self.memory.len());
+                megachip.load_palette(self.memory.get(ptr..end).unwrap_or_default());
+            }
+            0x3 => megachip.set_sprite_width(nn),
+            0x4 => megachip.set_sprite_height(nn),
+            0x5 => {
+                // screen alpha isn't supported
+            }
+            0x6 => {
+                let data = self.memory.get(ptr..).unwrap_or_default();
+                megachip.play_sound(data, nn & 0xf == 0);
+            }
+            0x7 => megachip.stop_sound(),
+            0x8 => {
+                if !megachip.set_blend_mode(nn & 0xf) {
+                    return Err(Error::UnknownInstruction(nnn));
+                }
             }
+            0x9 => megachip.set_collision_index(nn),
+            _ => return Err(Error::UnknownInstruction(nnn)),
         }
+        Ok(())
     }
 
-    fn op_bdc(&mut self, x: usize) {
+    /// Extends memory to 24-bit address space and loads the part of the program past 4 KB
+    #[cfg(feature = "megachip")]
+    fn enter_megachip(&mut self) {
+        if self.memory.len() < megachip::MEMORY_SIZE {
+            self.memory.resize(megachip::MEMORY_SIZE, 0);
+            let loaded = MEMORY_SIZE - self.program_address;
+            let rest = self.rom.get(loaded..).unwrap_or_default();
+            self.memory[MEMORY_SIZE..MEMORY_SIZE + rest.len()].copy_from_slice(rest);
+        }
+        self.megachip = Some(MegaChip::new());
+        // half pixels of the previous resolution aren't carried over
+        self.scroll_remainder = (0, 0);
+        self.is_display_changed = true;
+    }
+
+    fn op_bdc(&mut self, x: usize) -> Result<(), Error> {
         let val = self.reg[x];
         let ptr = self.ri as usize;
-        self.memory[ptr] = val / 100 % 10;
-        self.memory[ptr + 1] = val / 10 % 10;
-        self.memory[ptr + 2] = val % 10;
+        self.write_memory(ptr, val / 100 % 10)?;
+        self.write_memory(ptr + 1, val / 10 % 10)?;
+        self.write_memory(ptr + 2, val % 10)
     }
 
-    fn op_reg_dump(&mut self, x: usize) {
+    fn op_reg_dump(&mut self, x: usize) -> Result<(), Error> {
         let ptr = self.ri as usize;
         for offset in 0..=x {
-            self.memory[ptr + offset] = self.reg[offset];
+            self.write_memory(ptr + offset, self.reg[offset])?;
         }
         if self.quirks.memory {
-            self.ri += x as u16 + 1;
+            self.ri = self.ri.wrapping_add(x as u32 + 1);
         }
+        Ok(())
     }
 
-    fn op_reg_load(&mut self, x: usize) {
+    fn op_reg_load(&mut self, x: usize) -> Result<(), Error> {
         let ptr = self.ri as usize;
         for offset in 0..=x {
-            self.reg[offset] = self.memory[ptr + offset];
+            self.reg[offset] = self.read_memory(ptr + offset)?;
         }
         if self.quirks.memory {
-            self.ri += x as u16 + 1;
+            self.ri = self.ri.wrapping_add(x as u32 + 1);
+        }
+        Ok(())
+    }
+
+    fn op_rpl_store(&mut self, x: usize) {
+        let flags = self.rpl_flags;
+        if let Some(delta) = self.delta() {
+            delta.rpl_flags = Some(flags);
         }
+        self.rpl_flags[..=x].copy_from_slice(&self.reg[..=x]);
+        self.is_rpl_flags_changed = true;
+    }
+
+    fn op_rpl_load(&mut self, x: usize) {
+        self.reg[..=x].copy_from_slice(&self.rpl_flags[..=x]);
     }
 
     fn op_ptr_add(&mut self, x: usize) {
         let val = self.reg[x];
-        self.ri += val as u16;
+        self.ri = self.ri.wrapping_add(val as u32);
     }
 
     fn op_mov_font_addr(&mut self, x: usize) {
-        let val = self.reg[x] as u16;
-        self.ri = FONT_BASE_ADDRESS as u16 + val * 5;
+        let val = self.reg[x] as u32;
+        self.ri = FONT_BASE_ADDRESS as u32 + val * 5;
+    }
+
+    fn op_mov_big_font_addr(&mut self, x: usize) {
+        let val = self.reg[x] as u32;
+        self.ri = BIG_FONT_BASE_ADDRESS as u32 + val * 10;
     }
 
     fn op_set_delay(&mut self, x: usize) {
@@ -480,32 +1271,52 @@ impl Chip8 {
         self.st = self.reg[x];
     }
 
+    fn op_load_audio(&mut self) -> Result<(), Error> {
+        let ptr = self.ri as usize;
+        let pattern = (self.audio_pattern, self.is_audio_pattern_loaded);
+        if let Some(delta) = self.delta() {
+            delta.audio_pattern = Some(pattern);
+        }
+        for i in 0..AUDIO_PATTERN_SIZE {
+            self.audio_pattern[i] = self.read_memory(ptr + i)?;
+        }
+        self.is_audio_pattern_loaded = true;
+        Ok(())
+    }
+
+    fn op_set_pitch(&mut self, x: usize) {
+        self.pitch = self.reg[x];
+    }
+
     fn op_dump_delay(&mut self, x: usize) {
         self.reg[x] = self.dt;
     }
 
     fn op_skip_key_eq(&mut self, x: usize) {
-        if self.keypad[self.reg[x] as usize] {
+        if self.keypad[(self.reg[x] & 0xf) as usize] {
             self.pc += 2;
         }
     }
 
     fn op_skip_key_ne(&mut self, x: usize) {
-        if !self.keypad[self.reg[x] as usize] {
+        if !self.keypad[(self.reg[x] & 0xf) as usize] {
             self.pc += 2;
         }
     }
 
+    /// The machine waits for a key instead of repeating the instruction,
+    /// a key pressed already is accepted at once without the release quirk
     fn op_wait_key(&mut self, x: usize) {
-        // Highest bit is a flag that displays if key already pressed
-        // Lowest bits are representing keycode
-        let val = self.memory[KB_WAIT_KEYCODE_ADDRESS];
-        let is_pressed = val >> 7 == 1;
-        if is_pressed {
-            let key_code = val & 0xf;
+        self.state = State::WaitingForKey(x);
+        self.poll_wait_key(x);
+    }
+
+    /// The pressed key is kept in `wait_key` until its release,
+    /// without the release quirk the key is accepted on press
+    fn poll_wait_key(&mut self, x: usize) {
+        if let Some(key_code) = self.wait_key {
             if !self.keypad[key_code as usize] {
-                self.reg[x] = key_code;
-                self.memory[KB_WAIT_KEYCODE_ADDRESS] = 0;
+                self.resolve_wait_key(x, key_code);
                 return;
             }
         } else if let Some((key_code, _)) = self
@@ -514,24 +1325,706 @@ impl Chip8 {
             .enumerate()
             .find(|&(_, is_pressed)| *is_pressed)
         {
-            self.memory[KB_WAIT_KEYCODE_ADDRESS] = 1 << 7 | key_code as u8;
+            if !self.quirks.key_wait_release {
+                self.resolve_wait_key(x, key_code as u8);
+                return;
+            }
+            self.wait_key = Some(key_code as u8);
+        }
+        // nothing changes until the next key event
+        self.is_idle = self.key_events.is_empty();
+    }
+
+    fn resolve_wait_key(&mut self, x: usize, key_code: u8) {
+        self.reg[x] = key_code;
+        self.wait_key = None;
+        self.state = State::Running;
+    }
+
+    /// Current resolution of the display
+    pub fn display_size(&self) -> USize {
+        #[cfg(feature = "megachip")]
+        if self.megachip.is_some() {
+            return megachip::DISPLAY_SIZE;
         }
-        self.pc -= 2;
+        self.display_size
+    }
+
+    fn is_hires(&self) -> bool {
+        self.display_size == HIRES_DISPLAY_SIZE
+    }
+
+    /// ARGB frame of the Mega-CHIP display, None in the regular mode
+    #[cfg(feature = "megachip")]
+    pub fn color_frame(&self) -> Option<&[u32]> {
+        self.megachip.as_ref().map(|megachip| megachip.frame())
+    }
+
+    /// Mega-CHIP digitized sound start or stop request
+    #[cfg(feature = "megachip")]
+    pub fn take_sound_command(&mut self) -> Option<SoundCommand> {
+        self.megachip
+            .as_mut()
+            .and_then(|megachip| megachip.take_sound_command())
     }
 
     pub fn get_video_ram(&self) -> &[u8] {
-        &self.video_memory
+        &self.video_memory[..self.display_size.square()]
+    }
+
+    /// Monochrome display with its resolution, see `Framebuffer` for the export formats
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer::new(self.get_video_ram(), self.display_size)
+    }
+
+    /// True if the program waits for a timer tick or a key,
+    /// the rest of the frame can be skipped
+    pub fn is_idle(&self) -> bool {
+        self.is_idle
+    }
+
+    /// True if FX0A waits for a key press or release, the program doesn't proceed
+    /// until a key event, so async callers may stop running the machine meanwhile
+    pub fn is_waiting_for_key(&self) -> bool {
+        matches!(self.state, State::WaitingForKey(_))
+    }
+
+    /// Resolves FX0A wait with the key, ignored if the machine doesn't wait.
+    /// Frontends without key events use it instead of `key_down` and `key_up`
+    pub fn provide_key(&mut self, key_code: u8) -> Result<(), Error> {
+        if key_code >= 0x10 {
+            return Err(Error::InvalidKeyCode(key_code));
+        }
+        if let State::WaitingForKey(x) = self.state {
+            self.resolve_wait_key(x, key_code);
+        }
+        Ok(())
+    }
+
+    /// Returns true if video memory was changed since previous call
+    pub fn poll_display_changed(&mut self) -> bool {
+        let is_changed = self.is_display_changed;
+        self.is_display_changed = false;
+        is_changed
+    }
+
+    /// Queues the key press, it takes effect before the next instruction.
+    /// Codes outside of the hexadecimal keypad are rejected
+    pub fn key_down(&mut self, key_code: u8) -> Result<(), Error> {
+        self.push_key_event(KeyEvent::Down(key_code), key_code)
+    }
+
+    pub fn key_up(&mut self, key_code: u8) -> Result<(), Error> {
+        self.push_key_event(KeyEvent::Up(key_code), key_code)
+    }
+
+    fn push_key_event(&mut self, event: KeyEvent, key_code: u8) -> Result<(), Error> {
+        if key_code >= 0x10 {
+            return Err(Error::InvalidKeyCode(key_code));
+        }
+        self.key_events.push_back(event);
+        Ok(())
+    }
+
+    /// Lets embedders trace, patch or stop the program without changing the dispatch loop,
+    /// it survives reset
+    pub fn set_pre_instruction_hook(
+        &mut self,
+        hook: impl FnMut(&Chip8, Opcode) -> HookAction + Send + 'static,
+    ) {
+        self.pre_instruction_hook = Some(Box::new(hook));
     }
 
-    pub fn key_down(&mut self, key_code: u8) {
-        self.keypad[key_code as usize] = true;
+    /// Timer ticks a pressed key is held at least, so taps shorter than a frame aren't missed
+    pub fn set_key_release_delay(&mut self, ticks: u8) {
+        self.key_release_delay = ticks;
+    }
+
+    /// Exchanges the state with the frontend, expected to be called once per frame
+    pub fn sync_platform<P: Platform>(&mut self, platform: &mut P) {
+        while let Some(event) = platform.next_key_event() {
+            let result = match event {
+                KeyEvent::Down(key_code) => self.key_down(key_code),
+                KeyEvent::Up(key_code) => self.key_up(key_code),
+            };
+            if let Err(err) = result {
+                warn!("{err}");
+            }
+        }
+        if self.poll_display_changed() {
+            platform.draw(self.get_video_ram(), self.display_size);
+        }
+        platform.set_sound(self.is_audio_playing());
+        self.report_sound(platform);
     }
 
-    pub fn key_up(&mut self, key_code: u8) {
-        self.keypad[key_code as usize] = false;
+    fn report_sound<P: Platform>(&mut self, platform: &mut P) {
+        let is_playing = self.is_audio_playing();
+        if is_playing == self.is_sound_reported {
+            return;
+        }
+        self.is_sound_reported = is_playing;
+        if is_playing {
+            platform.on_sound_start();
+        } else {
+            platform.on_sound_stop();
+        }
     }
 
     pub fn is_audio_playing(&self) -> bool {
         self.st > 0
     }
+
+    /// XO-CHIP audio pattern and its playback rate in bits per second,
+    /// None if the program doesn't use sampled audio
+    pub fn audio_pattern(&self) -> Option<(&[u8; AUDIO_PATTERN_SIZE], f32)> {
+        if !self.is_audio_pattern_loaded {
+            return None;
+        }
+        // 4000 * 2 ^ ((pitch - 64) / 48) without `powf` which needs std
+        let steps = self.pitch as i32 - 64;
+        let octave = match steps.div_euclid(48) {
+            exp @ 0.. => (1 << exp) as f32,
+            exp => 1.0 / (1 << -exp) as f32,
+        };
+        let rest = (0..steps.rem_euclid(48)).fold(1.0, |rate, _| rate * PITCH_STEP);
+        Some((&self.audio_pattern, 4000.0 * octave * rest))
+    }
+
+    /// Total number of executed instructions, isn't affected by reset
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The deepest subroutine nesting since the machine start
+    pub fn peak_stack_depth(&self) -> usize {
+        self.peak_sp
+    }
+
+    pub fn registers(&self) -> &[u8; REGISTERS_COUNT] {
+        &self.reg
+    }
+
+    /// Return addresses from the bottom to the top of the stack
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    /// Active calls from the innermost one, subroutines are found by the call instructions
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        self.stack()
+            .iter()
+            .rev()
+            .map(|&address| {
+                let return_address = address as usize;
+                let call = return_address
+                    .checked_sub(2)
+                    .and_then(|call| self.memory.get(call..call + 2));
+                let subroutine = match call {
+                    Some(&[high, low]) if high >> 4 == 0x2 => {
+                        Some(((high as usize & 0xf) << 8) | low as usize)
+                    }
+                    _ => None,
+                };
+                StackFrame {
+                    subroutine,
+                    return_address,
+                }
+            })
+            .collect()
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Address of the last executed instruction, points to the failed one after an error
+    pub fn instruction_address(&self) -> usize {
+        self.instruction_address
+    }
+
+    pub fn index(&self) -> u32 {
+        self.ri
+    }
+
+    /// Delay and sound timers
+    pub fn timers(&self) -> (u8, u8) {
+        (self.dt, self.st)
+    }
+
+    /// Opcode which will be executed next, None if program counter is out of memory
+    pub fn current_opcode(&self) -> Option<u16> {
+        self.fetch_opcode(self.pc).ok()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// COSMAC VIP machine cycles taken by the last executed instruction
+    pub fn instruction_cost(&self) -> u32 {
+        self.last_opcode.map_or(0, |opcode| {
+            COSMAC_FETCH_CYCLES + self.cosmac_cycles(Opcode::decode(opcode))
+        })
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// New quirks are applied to the next instruction, hi-res mode is selected on reset
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Address the program is loaded at, depends on the machine layout
+    pub fn program_address(&self) -> usize {
+        self.program_address
+    }
+
+    /// Program address of the next reset
+    fn layout_address(&self) -> usize {
+        self.base_address.unwrap_or(if self.quirks.eti660 {
+            ETI660_PROGRAM_ADDRESS
+        } else {
+            PROGRAM_BASE_ADDRESS
+        })
+    }
+
+    /// In strict mode writes below the program area terminate the program with error
+    pub fn set_strict_memory(&mut self, is_strict: bool) {
+        self.is_strict_memory = is_strict;
+    }
+
+    fn load_fonts(&mut self) {
+        self.memory[FONT_BASE_ADDRESS..BIG_FONT_BASE_ADDRESS].copy_from_slice(&self.font);
+        self.memory[BIG_FONT_BASE_ADDRESS..BIG_FONT_BASE_ADDRESS + BIG_FONT.len()]
+            .copy_from_slice(&BIG_FONT);
+    }
+
+    /// In permissive mode unknown instructions are skipped and counted
+    pub fn set_permissive(&mut self, is_permissive: bool) {
+        self.unknown_opcodes = is_permissive.then(BTreeMap::new);
+    }
+
+    /// Skipped unknown opcodes and their execution counts, empty if the mode is off
+    pub fn unknown_opcodes(&self) -> Vec<(u16, u64)> {
+        self.unknown_opcodes
+            .iter()
+            .flatten()
+            .map(|(opcode, count)| (*opcode, *count))
+            .collect()
+    }
+
+    /// Executes the instruction, a call of paused machine is executed entirely:
+    /// the machine runs and pauses after the matching return
+    pub fn step_over(&mut self) -> Result<(), Error> {
+        let is_call = self
+            .current_opcode()
+            .is_some_and(|opcode| opcode >> 12 == 0x2);
+        if !is_call || !matches!(self.state, State::Paused) {
+            return self.teak();
+        }
+        self.step_depth = Some(self.sp);
+        self.state = State::Running;
+        Ok(())
+    }
+
+    /// Runs paused machine until the current subroutine returns,
+    /// false if the machine isn't paused or there is no subroutine
+    pub fn step_out(&mut self) -> bool {
+        if self.sp == 0 || !matches!(self.state, State::Paused) {
+            return false;
+        }
+        self.step_depth = Some(self.sp - 1);
+        self.state = State::Running;
+        true
+    }
+
+    /// Returns true once if the machine was paused by finished step over or step out
+    pub fn take_step_completed(&mut self) -> bool {
+        core::mem::take(&mut self.is_step_completed)
+    }
+
+    /// Returns the watchpoint which paused the machine, reported once
+    pub fn take_watchpoint_hit(&mut self) -> Option<WatchpointHit> {
+        self.watchpoint_hit.take()
+    }
+
+    /// Starts tracking of executed and read addresses, kept across resets
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(MEMORY_SIZE));
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Starts logging of the program memory writes
+    #[cfg(feature = "scripting")]
+    pub fn enable_memory_writes(&mut self) {
+        self.memory_writes = Some(Vec::new());
+    }
+
+    /// Address and value of the program writes since the previous call
+    #[cfg(feature = "scripting")]
+    pub fn take_memory_writes(&mut self) -> Vec<(usize, u8)> {
+        self.memory_writes
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn pause(&mut self) {
+        self.step_depth = None;
+        if self.is_running() {
+            self.enter_pause();
+        }
+    }
+
+    /// Number of executed instructions kept for stepping backwards, 0 disables the journal
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        match (&mut self.journal, depth) {
+            (_, 0) => self.journal = None,
+            (Some(journal), _) => journal.set_capacity(depth),
+            (None, _) => self.journal = Some(Journal::new(depth)),
+        }
+    }
+
+    /// Number of instructions which can be undone
+    pub fn undo_count(&self) -> usize {
+        self.journal.as_ref().map_or(0, |journal| journal.len())
+    }
+
+    /// Reverts the last executed instruction of paused machine,
+    /// false if the machine isn't paused or the journal is empty
+    pub fn step_back(&mut self) -> bool {
+        if !matches!(self.state, State::Paused) {
+            return false;
+        }
+        let Some(delta) = self.journal.as_mut().and_then(|journal| journal.pop()) else {
+            return false;
+        };
+        self.reg = delta.reg;
+        self.ri = delta.ri;
+        self.pc = delta.pc;
+        self.sp = delta.sp;
+        self.dt = delta.dt;
+        self.st = delta.st;
+        self.pitch = delta.pitch;
+        self.scroll_remainder = delta.scroll_remainder;
+        self.wait_key = delta.wait_key;
+        self.cycles = delta.cycles;
+        if let Some((slot, value)) = delta.stack {
+            self.stack[slot] = value;
+        }
+        for (address, value) in delta.memory.into_iter().rev() {
+            self.memory[address] = value;
+        }
+        if let Some(screen) = delta.video_memory {
+            self.video_memory[..screen.len()].copy_from_slice(&screen);
+        }
+        for idx in delta.pixels {
+            self.video_memory[idx] ^= 1;
+        }
+        if let Some((pattern, is_loaded)) = delta.audio_pattern {
+            self.audio_pattern = pattern;
+            self.is_audio_pattern_loaded = is_loaded;
+        }
+        if let Some(flags) = delta.rpl_flags {
+            self.rpl_flags = flags;
+            self.is_rpl_flags_changed = true;
+        }
+        if let Some(rng) = delta.rng {
+            self.rng = *rng;
+        }
+        self.instruction_address = self.pc;
+        self.is_display_changed = true;
+        true
+    }
+
+    /// SCHIP user flags stored by FX75, kept by the frontend between runs
+    pub fn rpl_flags(&self) -> &[u8; RPL_FLAGS_COUNT] {
+        &self.rpl_flags
+    }
+
+    /// Restores the flags saved by the previous run of the program
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAGS_COUNT]) {
+        self.rpl_flags = flags;
+    }
+
+    /// Returns true if the program stored the flags since previous call
+    pub fn poll_rpl_flags_changed(&mut self) -> bool {
+        let is_changed = self.is_rpl_flags_changed;
+        self.is_rpl_flags_changed = false;
+        is_changed
+    }
+
+    pub fn set_register(&mut self, index: usize, value: u8) {
+        self.reg[index & 0xf] = value;
+    }
+
+    pub fn set_pc(&mut self, address: usize) {
+        self.pc = address;
+    }
+
+    pub fn set_index(&mut self, value: u32) {
+        self.ri = value;
+    }
+
+    pub fn set_timers(&mut self, dt: u8, st: u8) {
+        self.dt = dt;
+        self.st = st;
+    }
+
+    /// Replaces return addresses of the active calls
+    pub fn set_stack(&mut self, stack: &[u16]) -> Result<(), Error> {
+        if stack.len() > STACK_SIZE {
+            return Err(Error::StackOverflow);
+        }
+        self.stack[..stack.len()].copy_from_slice(stack);
+        self.sp = stack.len();
+        Ok(())
+    }
+
+    /// Replaces the display content, the pixels must match the current resolution
+    pub fn set_video_ram(&mut self, pixels: &[u8]) {
+        let size = self.display_size.square();
+        self.video_memory[..size].copy_from_slice(pixels);
+        self.is_display_changed = true;
+    }
+
+    /// Debugger memory write, isn't checked against watchpoints and strict mode
+    pub fn poke(&mut self, address: usize, data: &[u8]) -> Result<(), Error> {
+        let end = address + data.len();
+        if end > self.memory.len() {
+            return Err(Error::MemoryOutOfBounds {
+                address: end - 1,
+                pc: self.pc,
+            });
+        }
+        self.memory[address..end].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+///
+/// Options of the machine construction, the program is loaded by `build`:
+/// `Chip8::builder(rom).quirks(Model::Schip.quirks()).seed(7).build()`
+///
+pub struct Chip8Builder {
+    rom: Vec<u8>,
+    quirks: Quirks,
+    seed: Option<u64>, // random generator is seeded from entropy if not set
+    entropy: Option<EntropySource>,
+    program_address: Option<usize>,
+    font: SmallFont,
+    key_release_delay: u8,
+    undo_depth: usize,
+    watchpoints: Vec<Watchpoint>,
+    is_coverage: bool,
+    is_strict_memory: bool,
+    is_permissive: bool,
+}
+
+impl Chip8Builder {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            quirks: Quirks::default(),
+            seed: None,
+            entropy: None,
+            program_address: None,
+            font: FONTS[0].sprites,
+            key_release_delay: 0,
+            undo_depth: 0,
+            watchpoints: Vec::new(),
+            is_coverage: false,
+            is_strict_memory: false,
+            is_permissive: false,
+        }
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// The same seed produces the same sequence of CXNN results
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Seed source called on every reset if the seed isn't fixed, e.g. a hardware generator.
+    /// Without `std` there is no OS entropy, so either the seed or the source is required
+    #[cfg(not(feature = "std"))]
+    pub fn entropy(mut self, entropy: EntropySource) -> Self {
+        self.entropy = Some(entropy);
+        self
+    }
+
+    /// Custom load address instead of the layout one, e.g. 0x600 of ETI-660
+    pub fn program_address(mut self, address: usize) -> Self {
+        self.program_address = Some(address);
+        self
+    }
+
+    pub fn font(mut self, font: &SmallFont) -> Self {
+        self.font = *font;
+        self
+    }
+
+    pub fn key_release_delay(mut self, ticks: u8) -> Self {
+        self.key_release_delay = ticks;
+        self
+    }
+
+    /// Instructions which can be undone, 0 disables the journal
+    pub fn undo_depth(mut self, depth: usize) -> Self {
+        self.undo_depth = depth;
+        self
+    }
+
+    pub fn watchpoint(mut self, watchpoint: Watchpoint) -> Self {
+        self.watchpoints.push(watchpoint);
+        self
+    }
+
+    /// Executed and read addresses are tracked
+    pub fn coverage(mut self, is_enabled: bool) -> Self {
+        self.is_coverage = is_enabled;
+        self
+    }
+
+    pub fn strict_memory(mut self, is_strict: bool) -> Self {
+        self.is_strict_memory = is_strict;
+        self
+    }
+
+    pub fn permissive(mut self, is_permissive: bool) -> Self {
+        self.is_permissive = is_permissive;
+        self
+    }
+
+    pub fn build(self) -> Result<Chip8, Error> {
+        #[cfg(not(feature = "std"))]
+        if self.seed.is_none() && self.entropy.is_none() {
+            return Err(Error::NoEntropySource);
+        }
+        let mut machine = Chip8::new(self.quirks, self.seed);
+        machine.entropy = self.entropy;
+        machine.base_address = self.program_address;
+        machine.font = self.font;
+        machine.set_key_release_delay(self.key_release_delay);
+        machine.set_undo_depth(self.undo_depth);
+        machine.set_strict_memory(self.is_strict_memory);
+        machine.set_permissive(self.is_permissive);
+        for watchpoint in self.watchpoints {
+            machine.add_watchpoint(watchpoint);
+        }
+        if self.is_coverage {
+            machine.enable_coverage();
+        }
+        machine.load_rom(self.rom)?;
+        Ok(machine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // font sprite row of 4 pixels at the top left corner scrolled right and down by 2
+    const SCROLL_PROGRAM: [u8; 10] = [
+        0xf0, 0x29, // LD F, V0
+        0xd0, 0x11, // DRW V0, V1, 1
+        0x00, 0xfb, // SCR
+        0x00, 0xc2, // SCD 2
+        0x12, 0x08, // JP to itself
+    ];
+
+    fn lit_pixels(machine: &Chip8) -> Vec<(usize, usize)> {
+        let width = machine.display_size().width;
+        machine
+            .get_video_ram()
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| **pixel > 0)
+            .map(|(idx, _)| (idx % width, idx / width))
+            .collect()
+    }
+
+    fn scrolled_pixels(rom: Vec<u8>, quirks: Quirks) -> Vec<(usize, usize)> {
+        let mut machine = Chip8::builder(rom).quirks(quirks).seed(0).build().unwrap();
+        for _ in 0..4 {
+            machine.teak().unwrap();
+        }
+        lit_pixels(&machine)
+    }
+
+    fn hires_rom() -> Vec<u8> {
+        let mut rom = vec![0; HIRES_PROGRAM_ADDRESS - 0x200];
+        rom[..2].copy_from_slice(&HIRES_ENTRY_OPCODE);
+        rom.extend(SCROLL_PROGRAM);
+        rom
+    }
+
+    #[test]
+    fn scroll_moves_lores_display_by_schip_amounts() {
+        let quirks = Quirks {
+            half_scroll: false,
+            ..Quirks::default()
+        };
+        let pixels = scrolled_pixels(SCROLL_PROGRAM.to_vec(), quirks);
+        assert_eq!(pixels, [(4, 2), (5, 2), (6, 2), (7, 2)]);
+    }
+
+    #[test]
+    fn half_scroll_moves_lores_display_by_half() {
+        let quirks = Quirks {
+            half_scroll: true,
+            ..Quirks::default()
+        };
+        let pixels = scrolled_pixels(SCROLL_PROGRAM.to_vec(), quirks);
+        assert_eq!(pixels, [(2, 1), (3, 1), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn half_scroll_carries_odd_amounts() {
+        let quirks = Quirks {
+            half_scroll: true,
+            ..Quirks::default()
+        };
+        // SCD 1 twice moves the display by a single row
+        let rom = vec![0xf0, 0x29, 0xd0, 0x11, 0x00, 0xc1, 0x00, 0xc1, 0x12, 0x08];
+        let mut machine = Chip8::builder(rom).quirks(quirks).seed(0).build().unwrap();
+        for _ in 0..3 {
+            machine.teak().unwrap();
+        }
+        assert_eq!(lit_pixels(&machine), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        machine.teak().unwrap();
+        assert_eq!(lit_pixels(&machine), [(0, 1), (1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn hires_scroll_ignores_half_scroll() {
+        for half_scroll in [false, true] {
+            let quirks = Quirks {
+                hires: true,
+                half_scroll,
+                ..Quirks::default()
+            };
+            let pixels = scrolled_pixels(hires_rom(), quirks);
+            assert_eq!(pixels, [(4, 2), (5, 2), (6, 2), (7, 2)]);
+        }
+    }
 }
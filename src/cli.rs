@@ -0,0 +1,343 @@
+use std::str::FromStr;
+
+use crate::chip8::Watchpoint;
+use crate::config::{self, Config, Model, Palette, QuirksConfig, Renderer, TimerClock, Timing};
+use crate::font::Font;
+use crate::netplay::NetplayRole;
+
+///
+/// Command line arguments
+///
+#[derive(Clone, Default)]
+pub struct Arguments {
+    pub rom_paths: Vec<String>,    // ROM files or directories played in turn
+    pub auto_advance: Option<u64>, // seconds
+    pub is_watch_rom: bool,        // reload the ROM when its file changes
+    pub seed: Option<u64>,
+    pub screenshot_on_exit: bool,
+    pub record_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub config_path: Option<String>,
+    pub write_default_config: bool,
+    pub is_strict_config: bool,
+    pub is_selftest: bool,
+    pub is_sprite_editor: bool,
+    pub test_specs_path: Option<String>, // golden tests run by `test` command
+    pub is_lint: bool, // report static analysis warnings instead of running the ROM
+    pub frame_hashes_path: Option<String>,
+    pub hash_interval: Option<u64>, // instructions between frame hashes, every frame if not set
+    pub compared_hashes: Option<(String, String)>, // frame hash files diffed instead of running
+    pub side_by_side: Option<QuirksConfig>, // quirks of the machine shown next to the main one
+    pub watchpoints: Vec<Watchpoint>,
+    pub pause_at: Vec<usize>, // addresses the machine pauses at before the instruction
+    pub skip_at: Vec<usize>,  // addresses of instructions which are never executed
+    pub is_tui: bool,         // terminal frontend instead of SDL window
+    pub debug_address: Option<String>, // address of the remote debugger server
+    pub is_monitor: bool,     // debugger commands read from the console
+    pub netplay: Option<NetplayRole>,
+    pub input_delay: Option<u8>, // netplay frames before the local input is applied
+    pub profile_path: Option<String>,
+    pub coverage_path: Option<String>,
+    pub report_path: Option<String>, // run statistics written on exit
+    pub symbols_path: Option<String>,
+    pub state_path: Option<String>, // JSON machine state applied on start
+    pub patch_path: Option<String>, // IPS or BPS patch applied to the first ROM on load
+    pub script_path: Option<String>, // Rhai script hooked on frames and instructions
+    pub cheats_path: Option<String>, // instead of the cheats file of the ROM in data directory
+    pub verbosity: u8,              // 0 - info, 1 - debug, 2 - trace
+    pub is_log_json: bool,
+    // config overrides
+    pub operations_per_second: Option<u64>,
+    pub timing: Option<Timing>,
+    pub timer_clock: Option<TimerClock>,
+    pub renderer: Option<Renderer>,
+    pub is_no_vsync: bool,
+    pub frame_rate: Option<u32>, // 0 - uncapped
+    pub scale: Option<usize>,
+    pub is_integer_scaling: bool,
+    pub palette: Option<&'static Palette>,
+    pub foreground: Option<[u8; 3]>,
+    pub background: Option<[u8; 3]>,
+    pub font: Option<String>, // built-in font name or font file path
+    pub audio_device: Option<String>,
+    pub quirks: QuirksConfig,
+    pub is_strict_memory: bool,
+    pub is_crash_dump: bool,
+    pub is_permissive: bool,
+    pub load_address: Option<usize>, // program address instead of the machine layout one
+}
+
+impl Arguments {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--auto-advance" => result.auto_advance = Some(parse_number(&value()?)?),
+                "--seed" => result.seed = Some(parse_number(&value()?)?),
+                "--screenshot-on-exit" => result.screenshot_on_exit = true,
+                "--watch-rom" => result.is_watch_rom = true,
+                "--record" => result.record_path = Some(value()?),
+                "--replay" => result.replay_path = Some(value()?),
+                "--config" => result.config_path = Some(value()?),
+                "--write-default-config" => result.write_default_config = true,
+                "--strict-config" => result.is_strict_config = true,
+                "--selftest" => result.is_selftest = true,
+                "--sprite-editor" => result.is_sprite_editor = true,
+                "--lint" => result.is_lint = true,
+                "--frame-hashes" => result.frame_hashes_path = Some(value()?),
+                "--hash-interval" => {
+                    let value = value()?;
+                    let interval = parse_number(&value)?;
+                    if interval == 0 {
+                        return Err(format!("Invalid hash interval: {value}"));
+                    }
+                    result.hash_interval = Some(interval);
+                }
+                "--compare-hashes" => result.compared_hashes = Some((value()?, value()?)),
+                "--ips" => result.operations_per_second = Some(parse_number(&value()?)?),
+                "--timing" => {
+                    let value = value()?;
+                    let timing = Timing::from_name(&value)
+                        .ok_or_else(|| format!("Unknown timing: {value}"))?;
+                    result.timing = Some(timing);
+                }
+                "--timer-clock" => {
+                    let value = value()?;
+                    let clock = TimerClock::from_name(&value)
+                        .ok_or_else(|| format!("Unknown timer clock: {value}"))?;
+                    result.timer_clock = Some(clock);
+                }
+                "--renderer" => {
+                    let value = value()?;
+                    let renderer = Renderer::from_name(&value)
+                        .ok_or_else(|| format!("Unknown renderer: {value}"))?;
+                    result.renderer = Some(renderer);
+                }
+                "--no-vsync" => result.is_no_vsync = true,
+                "--fps" => result.frame_rate = Some(parse_number(&value()?)?),
+                "--scale" => result.scale = Some(parse_number(&value()?)?),
+                "--integer-scaling" => result.is_integer_scaling = true,
+                "--palette" => {
+                    let value = value()?;
+                    let palette =
+                        Palette::find(&value).ok_or_else(|| format!("Unknown palette: {value}"))?;
+                    result.palette = Some(palette);
+                }
+                "--font" => result.font = Some(value()?),
+                "--foreground" => result.foreground = Some(parse_color(&value()?)?),
+                "--background" => result.background = Some(parse_color(&value()?)?),
+                "--audio-device" => result.audio_device = Some(value()?),
+                "--model" => {
+                    let value = value()?;
+                    let model = Model::from_name(&value)
+                        .ok_or_else(|| format!("Unknown machine model: {value}"))?;
+                    result.quirks.model = Some(model);
+                }
+                "--quirk" => parse_quirk(&value()?, &mut result.quirks)?,
+                "--compare-model" => {
+                    let value = value()?;
+                    let model = Model::from_name(&value)
+                        .ok_or_else(|| format!("Unknown machine model: {value}"))?;
+                    result
+                        .side_by_side
+                        .get_or_insert_with(Default::default)
+                        .model = Some(model);
+                }
+                "--compare-quirk" => {
+                    let quirks = result.side_by_side.get_or_insert_with(Default::default);
+                    parse_quirk(&value()?, quirks)?;
+                }
+                "--strict-memory" => result.is_strict_memory = true,
+                "--crash-dump" => result.is_crash_dump = true,
+                "--permissive" => result.is_permissive = true,
+                "--load-address" => result.load_address = Some(parse_address(&value()?)?),
+                "--watch" => result.watchpoints.push(parse_watchpoint(&value()?)?),
+                "--pause-at" => result.pause_at.push(parse_address(&value()?)?),
+                "--skip-at" => result.skip_at.push(parse_address(&value()?)?),
+                "--tui" => result.is_tui = true,
+                "-v" => result.verbosity += 1,
+                "-vv" => result.verbosity += 2,
+                "--log-json" => result.is_log_json = true,
+                "--profile" => result.profile_path = Some(value()?),
+                "--coverage" => result.coverage_path = Some(value()?),
+                "--report" => result.report_path = Some(value()?),
+                "--symbols" => result.symbols_path = Some(value()?),
+                "--load-state" => result.state_path = Some(value()?),
+                "--patch" => result.patch_path = Some(value()?),
+                "--script" => result.script_path = Some(value()?),
+                "--cheats" => result.cheats_path = Some(value()?),
+                "--debug-server" => {
+                    let value = value()?;
+                    // port only means local connections
+                    let address = if value.contains(':') {
+                        value
+                    } else {
+                        format!("127.0.0.1:{value}")
+                    };
+                    result.debug_address = Some(address);
+                }
+                "--monitor" => result.is_monitor = true,
+                "--netplay-host" => {
+                    let value = value()?;
+                    // port only means connections from any address
+                    let address = if value.contains(':') {
+                        value
+                    } else {
+                        format!("0.0.0.0:{value}")
+                    };
+                    result.netplay = Some(NetplayRole::Host(address));
+                }
+                "--netplay-connect" => result.netplay = Some(NetplayRole::Client(value()?)),
+                "--input-delay" => result.input_delay = Some(parse_number(&value()?)?),
+                "test" if result.rom_paths.is_empty() && result.test_specs_path.is_none() => {
+                    result.test_specs_path = Some(value()?)
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+                _ => result.rom_paths.push(arg),
+            }
+        }
+        // commands don't require ROM
+        let is_command = result.write_default_config
+            || result.is_selftest
+            || result.is_sprite_editor
+            || result.test_specs_path.is_some()
+            || result.compared_hashes.is_some();
+        if !is_command && result.rom_paths.is_empty() {
+            return Err("ROM path isn't specified".to_string());
+        }
+        Ok(result)
+    }
+
+    /// Command line values have priority over config file
+    pub fn apply(&self, config: &mut Config) {
+        config.quirks = self.quirks.apply(config.quirks);
+        if self.is_strict_memory {
+            config.debug.strict_memory = true;
+        }
+        if self.is_crash_dump {
+            config.debug.crash_dump = true;
+        }
+        if self.is_permissive {
+            config.debug.permissive = true;
+        }
+        if let Some(device) = &self.audio_device {
+            config.audio.device = Some(device.clone());
+        }
+        let appearance = &mut config.appearance;
+        if let Some(ips) = self.operations_per_second {
+            appearance.operations_per_second = ips;
+        }
+        if let Some(timing) = self.timing {
+            appearance.timing = timing;
+        }
+        if let Some(clock) = self.timer_clock {
+            appearance.timer_clock = clock;
+        }
+        if let Some(renderer) = self.renderer {
+            appearance.renderer = renderer;
+        }
+        if self.is_no_vsync {
+            appearance.is_vsync = false;
+        }
+        if let Some(frame_rate) = self.frame_rate {
+            appearance.frame_rate = frame_rate;
+        }
+        if let Some(scale) = self.scale {
+            appearance.scale = scale;
+        }
+        if self.is_integer_scaling {
+            appearance.is_integer_scaling = true;
+        }
+        if let Some(palette) = self.palette {
+            appearance.set_palette(palette);
+        }
+        if let Some(rgb) = self.foreground {
+            appearance.set_foreground(rgb);
+        }
+        if let Some(rgb) = self.background {
+            appearance.set_background(rgb);
+        }
+        if let Some(font) = &self.font {
+            if Font::find(font).is_some() {
+                appearance.font = font.clone();
+                appearance.font_path = None;
+            } else {
+                appearance.font_path = Some(font.clone());
+            }
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid number: {value}"))
+}
+
+/// Color is expected in `R,G,B` or `#RRGGBB` format
+fn parse_color(value: &str) -> Result<[u8; 3], String> {
+    if value.starts_with('#') {
+        return config::parse_hex_color(value);
+    }
+    let components = value
+        .split(',')
+        .map(|val| parse_number::<u8>(val.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    components
+        .try_into()
+        .map_err(|_| format!("Invalid color: {value}"))
+}
+
+/// Quirk is expected in `name=true|false` format
+fn parse_quirk(value: &str, quirks: &mut QuirksConfig) -> Result<(), String> {
+    let invalid = || format!("Invalid quirk: {value}");
+    let (name, flag) = value.split_once('=').ok_or_else(invalid)?;
+    let flag = flag.trim().parse::<bool>().map_err(|_| invalid())?;
+    let field = match name.trim() {
+        "vf_reset" => &mut quirks.vf_reset,
+        "memory" => &mut quirks.memory,
+        "shifting" => &mut quirks.shifting,
+        "jumping" => &mut quirks.jumping,
+        "hires" => &mut quirks.hires,
+        "key_wait_release" => &mut quirks.key_wait_release,
+        "eti660" => &mut quirks.eti660,
+        "half_scroll" => &mut quirks.half_scroll,
+        _ => return Err(invalid()),
+    };
+    *field = Some(flag);
+    Ok(())
+}
+
+/// Hexadecimal address, `0x` prefix is optional
+fn parse_address(value: &str) -> Result<usize, String> {
+    usize::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("Invalid address: {value}"))
+}
+
+/// Watchpoint is expected in `start[-end][:r|w|rw]` format, addresses are hexadecimal
+fn parse_watchpoint(value: &str) -> Result<Watchpoint, String> {
+    let invalid = || format!("Invalid watchpoint: {value}");
+    let (range, access) = value.split_once(':').unwrap_or((value, "rw"));
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let parse_address = |address: &str| {
+        let address = address.trim().trim_start_matches("0x");
+        u16::from_str_radix(address, 16).map_err(|_| invalid())
+    };
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if start > end {
+        return Err(invalid());
+    }
+    let (on_read, on_write) = match access {
+        "r" => (true, false),
+        "w" => (false, true),
+        "rw" => (true, true),
+        _ => return Err(invalid()),
+    };
+    Ok(Watchpoint {
+        start,
+        end,
+        on_read,
+        on_write,
+    })
+}
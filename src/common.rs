@@ -1,5 +1,6 @@
-use std::ops::Mul;
+use core::ops::Mul;
 
+#[derive(Clone, Copy, PartialEq)]
 pub struct Size<T> {
     pub height: T,
     pub width: T,
@@ -0,0 +1,254 @@
+use std::fmt::{self, Display};
+
+use crate::chip8::Chip8;
+
+///
+/// Expression over the machine state used by conditional breakpoints and watch list,
+/// e.g. `V3 == 0x1F && [I] > 2`. Operands: numbers (decimal or `0x` hexadecimal),
+/// registers `V0`-`VF` or `V[expr]`, `I`, `PC`, `DT`, `ST`, `SP` and memory bytes `[expr]`
+/// or `mem[expr]`. Operators from the highest priority: `* /`, `+ -`, `& |`,
+/// comparisons `== != < <= > >=`, `&&`, `||`, parentheses group subexpressions
+///
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expr = parser.parse_binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("unexpected '{token}'"));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    pub fn is_true(&self, machine: &Chip8) -> bool {
+        self.evaluate(machine) != 0
+    }
+
+    pub fn evaluate(&self, machine: &Chip8) -> u32 {
+        self.expr.evaluate(machine)
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+    Sp,
+}
+
+enum Expr {
+    Number(u32),
+    Register(Register),
+    IndexedRegister(Box<Expr>), // V[expr], the index wraps around
+    Memory(Box<Expr>),
+    Binary(Box<Expr>, String, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, machine: &Chip8) -> u32 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Register(register) => {
+                let (dt, st) = machine.timers();
+                match register {
+                    Register::V(index) => machine.registers()[*index] as u32,
+                    Register::I => machine.index(),
+                    Register::Pc => machine.pc() as u32,
+                    Register::Dt => dt as u32,
+                    Register::St => st as u32,
+                    Register::Sp => machine.stack().len() as u32,
+                }
+            }
+            Self::IndexedRegister(index) => {
+                machine.registers()[index.evaluate(machine) as usize % 0x10] as u32
+            }
+            // bytes outside of memory read as zero
+            Self::Memory(address) => machine
+                .memory()
+                .get(address.evaluate(machine) as usize)
+                .copied()
+                .unwrap_or_default() as u32,
+            Self::Binary(left, operator, right) => {
+                let (a, b) = (left.evaluate(machine), right.evaluate(machine));
+                match operator.as_str() {
+                    "*" => a.wrapping_mul(b),
+                    "/" => a.checked_div(b).unwrap_or_default(),
+                    "+" => a.wrapping_add(b),
+                    "-" => a.wrapping_sub(b),
+                    "&" => a & b,
+                    "|" => a | b,
+                    "==" => (a == b) as u32,
+                    "!=" => (a != b) as u32,
+                    "<" => (a < b) as u32,
+                    "<=" => (a <= b) as u32,
+                    ">" => (a > b) as u32,
+                    ">=" => (a >= b) as u32,
+                    "&&" => (a != 0 && b != 0) as u32,
+                    _ => (a != 0 || b != 0) as u32,
+                }
+            }
+        }
+    }
+}
+
+// binary operators from the lowest priority
+const OPERATORS: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<=", ">=", "<", ">"],
+    &["|", "&"],
+    &["+", "-"],
+    &["*", "/"],
+];
+
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            continue;
+        }
+        if ch.is_ascii_alphanumeric() {
+            let mut token = ch.to_string();
+            while let Some(next) = chars.next_if(|next| next.is_ascii_alphanumeric()) {
+                token.push(next);
+            }
+            tokens.push(token);
+            continue;
+        }
+        let pair = chars.peek().map(|next| format!("{ch}{next}"));
+        match pair.as_deref() {
+            Some("==" | "!=" | "<=" | ">=" | "&&" | "||") => {
+                tokens.extend(pair);
+                chars.next();
+            }
+            _ if "()[]*/+-&|<>".contains(ch) => tokens.push(ch.to_string()),
+            _ => return Err(format!("unexpected '{ch}'")),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("unexpected end of condition".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(format!("expected '{expected}' instead of '{token}'"));
+        }
+        Ok(())
+    }
+
+    fn is_subscripted(&self) -> bool {
+        self.tokens
+            .get(self.position)
+            .is_some_and(|token| token == "[")
+    }
+
+    /// Expression closed by `]`
+    fn parse_subscript(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_binary(0)?;
+        self.expect("]")?;
+        Ok(expr)
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(operators) = OPERATORS.get(level) else {
+            return self.parse_operand();
+        };
+        let mut expr = self.parse_binary(level + 1)?;
+        // operators of the same priority are left associative
+        while let Some(operator) = self
+            .tokens
+            .get(self.position)
+            .filter(|token| operators.contains(&token.as_str()))
+            .cloned()
+        {
+            self.position += 1;
+            let right = self.parse_binary(level + 1)?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr, String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "(" => {
+                let expr = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            "[" => Ok(Expr::Memory(Box::new(self.parse_subscript()?))),
+            _ if self.is_subscripted() && token.eq_ignore_ascii_case("mem") => {
+                self.expect("[")?;
+                Ok(Expr::Memory(Box::new(self.parse_subscript()?)))
+            }
+            _ if self.is_subscripted() && token.eq_ignore_ascii_case("v") => {
+                self.expect("[")?;
+                Ok(Expr::IndexedRegister(Box::new(self.parse_subscript()?)))
+            }
+            _ => parse_register(&token)
+                .map(Expr::Register)
+                .or_else(|| parse_number(&token).map(Expr::Number))
+                .ok_or(format!("unknown operand '{token}'")),
+        }
+    }
+}
+
+fn parse_register(token: &str) -> Option<Register> {
+    let token = token.to_ascii_lowercase();
+    let register = match token.as_str() {
+        "i" => Register::I,
+        "pc" => Register::Pc,
+        "dt" => Register::Dt,
+        "st" => Register::St,
+        "sp" => Register::Sp,
+        _ => {
+            let index = token.strip_prefix('v').filter(|index| index.len() == 1)?;
+            Register::V(usize::from_str_radix(index, 16).ok()?)
+        }
+    };
+    Some(register)
+}
+
+fn parse_number(token: &str) -> Option<u32> {
+    let token = token.to_ascii_lowercase();
+    match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
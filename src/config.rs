@@ -1,24 +1,176 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use serde_derive::Deserialize;
+use tracing::{debug, warn};
 
-#[derive(Default, Deserialize)]
+use crate::font::{Font, SmallFont, FONTS, SMALL_FONT_SIZE};
+use crate::quirks::Quirks;
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub appearance: AppearanceConfig,
+    pub audio: AudioConfig,
     pub quirks: Quirks,
+    pub debug: DebugConfig,
+    pub input: InputConfig,
+    pub storage: StorageConfig,
+    pub keymap: HashMap<String, u8>, // extra bindings: SDL key name to keypad code
 }
 
+const APP_DIRECTORY: &str = "chip8";
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+// sample config shipped with sources serves as a commented template
+const CONFIG_TEMPLATE: &str = include_str!("../chip8.toml");
+
 impl Config {
-    pub fn with_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let config = toml::from_str(&content)?;
-        Ok(config)
+    /// Loads config file, unknown keys are ignored and returned as warnings
+    pub fn with_file<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<String>), String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let mut warnings = Vec::new();
+        let deserializer = &mut toml::Deserializer::new(&content);
+        let mut config: Self = serde_ignored::deserialize(deserializer, |key| {
+            warnings.push(format!("{}: unknown key '{key}'", path.display()))
+        })
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+        if let Err(err) = config.appearance.resolve_colors() {
+            warnings.push(format!("{}: {err}", path.display()));
+        }
+        Ok((config, warnings))
+    }
+
+    /// Writes commented config template, existing file isn't overwritten
+    pub fn write_template<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, CONFIG_TEMPLATE)
     }
 }
 
-#[derive(Deserialize)]
+///
+/// Detects changes of a file by polling its modification time
+///
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl FileWatcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let modified = modification_time(&path);
+        Self {
+            path,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file was changed since the last call, checked once per interval
+    pub fn is_changed(&mut self) -> bool {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let modified = modification_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+///
+/// Detects changes of the config file
+///
+pub struct ConfigWatcher {
+    file: FileWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            file: FileWatcher::new(path),
+        }
+    }
+
+    /// Returns reloaded config if the file was changed since the last call
+    pub fn poll(&mut self) -> Option<Result<Config, String>> {
+        if !self.file.is_changed() {
+            return None;
+        }
+        let path = self.file.path();
+        debug!("Config file changed: {}", path.display());
+        let result = Config::with_file(path).map(|(config, warnings)| {
+            warnings.iter().for_each(|warning| warn!("{warning}"));
+            config
+        });
+        Some(result)
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Platform specific location of the settings file,
+/// e.g. `$XDG_CONFIG_HOME/chip8/<file_name>` on Linux
+pub fn platform_path(file_name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIRECTORY).join(file_name))
+}
+
+/// Platform specific location of the saved program data,
+/// e.g. `$XDG_DATA_HOME/chip8/<directory>` on Linux
+pub fn data_path(directory: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIRECTORY).join(directory))
+}
+
+/// Looks for the settings file in the platform config directory and then in the current one
+pub fn find_file(file_name: &str) -> Option<PathBuf> {
+    platform_path(file_name)
+        .into_iter()
+        .chain([PathBuf::from(file_name)])
+        .find(|path| path.is_file())
+}
+
+/// Generates unique file name in the current directory, e.g. for screenshots
+pub fn timestamped_file_name(extension: &str) -> String {
+    prefixed_file_name("chip8", extension)
+}
+
+/// Unique file name with the prefix, e.g. `crash-<timestamp>.json`
+pub fn prefixed_file_name(prefix: &str, extension: &str) -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{prefix}-{millis}.{extension}")
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
 pub struct AppearanceConfig {
     pub scale: usize,
     pub foreground_red: u8,
@@ -28,8 +180,32 @@ pub struct AppearanceConfig {
     pub background_green: u8,
     pub background_blue: u8,
     pub is_pixel_style: bool,
-    pub operations_per_second: u64,
+    pub is_integer_scaling: bool, // display pixels are whole multiples of the screen pixels
+    pub is_fullscreen: bool,
+    pub is_virtual_keypad: bool, // on-screen keypad for mouse and touch input
+    pub is_audio_scope: bool,    // strip with the sound timer and the audio waveform
+    pub is_scanline_style: bool,
+    pub phosphor_decay: f32, // 0 disables ghosting, closer to 1 - longer afterglow
+    pub frame_blending: usize, // number of recent frames blended to reduce flicker, up to 3
+    pub operations_per_second: u64, // executed as a budget of operations_per_second / 60 instructions every frame
+    pub timing: Timing,
+    pub timer_clock: TimerClock, // netplay always uses the emulated one
+    pub is_idle_skip: bool, // frame ends early while the program spins waiting for a timer or a key
+    pub renderer: Renderer,
+    pub is_vsync: bool,  // present frames in sync with the display refresh
+    pub frame_rate: u32, // rendering cap when vsync is off or unavailable, 0 - uncapped
+    pub background_frame_rate: u32, // loop rate while the window is minimized, 0 - not throttled
+    pub is_pause_on_focus_loss: bool, // except netplay and debugger sessions
     pub sound_volume: f32,
+    pub palette: Option<String>,   // preset name, replaces the RGB keys
+    pub foreground: Option<Color>, // overrides the RGB keys and the palette
+    pub background: Option<Color>,
+    pub second_plane: Option<Color>, // XO-CHIP pixels of the second plane only
+    pub both_planes: Option<Color>,  // XO-CHIP pixels of the both planes
+    pub font: String,                // built-in digits font: schip, vip, dream6800 or eti660
+    pub font_path: Option<String>,   // file of 80 bytes with 16 digits replacing the built-in font
+    #[serde(skip)]
+    pub plane_colors: [[u8; 3]; 2], // second plane and both planes colors of the palette
 }
 
 impl Default for AppearanceConfig {
@@ -43,27 +219,495 @@ impl Default for AppearanceConfig {
             background_green: 0,
             background_blue: 0,
             is_pixel_style: true,
+            is_integer_scaling: false,
+            is_fullscreen: false,
+            is_virtual_keypad: false,
+            is_audio_scope: false,
+            is_scanline_style: false,
+            phosphor_decay: 0.0,
+            frame_blending: 1,
             operations_per_second: 800,
+            timing: Timing::Ips,
+            timer_clock: TimerClock::Emulated,
+            is_idle_skip: true,
+            renderer: Renderer::Canvas,
+            is_vsync: true,
+            frame_rate: 60,
+            background_frame_rate: 10,
+            is_pause_on_focus_loss: true,
             sound_volume: 0.1,
+            palette: None,
+            foreground: None,
+            background: None,
+            second_plane: None,
+            both_planes: None,
+            font: FONTS[0].name.to_string(),
+            font_path: None,
+            plane_colors: [PALETTES[0].colors[2], PALETTES[0].colors[3]],
+        }
+    }
+}
+
+impl AppearanceConfig {
+    pub fn set_foreground(&mut self, [r, g, b]: [u8; 3]) {
+        self.foreground_red = r;
+        self.foreground_green = g;
+        self.foreground_blue = b;
+    }
+
+    pub fn set_background(&mut self, [r, g, b]: [u8; 3]) {
+        self.background_red = r;
+        self.background_green = g;
+        self.background_blue = b;
+    }
+
+    pub fn set_palette(&mut self, palette: &Palette) {
+        let [background, foreground, second_plane, both_planes] = palette.colors;
+        self.set_background(background);
+        self.set_foreground(foreground);
+        self.plane_colors = [second_plane, both_planes];
+    }
+
+    pub fn set_plane_colors(&mut self, second_plane: [u8; 3], both_planes: [u8; 3]) {
+        self.plane_colors = [second_plane, both_planes];
+    }
+
+    /// Digits sprites loaded into the interpreter memory
+    pub fn load_font(&self) -> Result<SmallFont, String> {
+        if let Some(path) = &self.font_path {
+            let data = fs::read(path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+            return SmallFont::try_from(data.as_slice()).map_err(|_| {
+                format!(
+                    "Font {path} has {} bytes, {SMALL_FONT_SIZE} expected",
+                    data.len()
+                )
+            });
+        }
+        Font::find(&self.font)
+            .map(|font| font.sprites)
+            .ok_or_else(|| format!("Unknown font: {}", self.font))
+    }
+
+    /// Colors of the planes combinations: none, first, second and both
+    pub fn plane_colors(&self) -> [[u8; 3]; 4] {
+        let [second_plane, both_planes] = self.plane_colors;
+        [
+            [
+                self.background_red,
+                self.background_green,
+                self.background_blue,
+            ],
+            [
+                self.foreground_red,
+                self.foreground_green,
+                self.foreground_blue,
+            ],
+            second_plane,
+            both_planes,
+        ]
+    }
+
+    /// Applies the palette preset and hex colors to the RGB keys,
+    /// unknown palette is reported after the colors are applied
+    fn resolve_colors(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        if let Some(name) = &self.palette {
+            match Palette::find(name) {
+                Some(palette) => self.set_palette(palette),
+                None => result = Err(format!("unknown palette '{name}'")),
+            }
+        }
+        if let Some(Color(rgb)) = self.foreground {
+            self.set_foreground(rgb);
         }
+        if let Some(Color(rgb)) = self.background {
+            self.set_background(rgb);
+        }
+        let [second_plane, both_planes] = self.plane_colors;
+        self.plane_colors = [
+            self.second_plane.map_or(second_plane, |Color(rgb)| rgb),
+            self.both_planes.map_or(both_planes, |Color(rgb)| rgb),
+        ];
+        result
     }
 }
 
+/// Color as `#RRGGBB` hex string or `[r, g, b]` array
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "ColorValue")]
+pub struct Color(pub [u8; 3]);
+
 #[derive(Deserialize)]
-pub struct Quirks {
-    pub vf_reset: bool, // reset vf register after AND, OR, XOR operations
-    pub memory: bool,   // increase RI after register dumb/load operations
-    pub shifting: bool, // TRUE to SHR/SHL with Vx only, otherwise perform Vx = Vy before
-    pub jumping: bool,
+#[serde(untagged)]
+enum ColorValue {
+    Rgb([u8; 3]),
+    Hex(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgb(rgb) => Ok(Self(rgb)),
+            ColorValue::Hex(hex) => parse_hex_color(&hex).map(Self),
+        }
+    }
+}
+
+/// Color is expected in `#RRGGBB` format, the hash sign is optional
+pub fn parse_hex_color(value: &str) -> Result<[u8; 3], String> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color: {value}"));
+    }
+    let [_, r, g, b] = u32::from_str_radix(hex, 16)
+        .map_err(|_| format!("Invalid color: {value}"))?
+        .to_be_bytes();
+    Ok([r, g, b])
+}
+
+///
+/// Display colors preset: background, foreground,
+/// then XO-CHIP second plane and overlapped planes colors
+///
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [[u8; 3]; 4],
+}
+
+pub const PALETTES: [Palette; 5] = [
+    Palette {
+        name: "classic",
+        colors: [[0x00; 3], [0xff; 3], [0xaa; 3], [0x55; 3]],
+    },
+    Palette {
+        name: "green", // phosphor monitor
+        colors: [
+            [0x08, 0x18, 0x08],
+            [0x33, 0xff, 0x66],
+            [0x1a, 0x99, 0x40],
+            [0x99, 0xff, 0xb3],
+        ],
+    },
+    Palette {
+        name: "amber", // phosphor monitor
+        colors: [
+            [0x18, 0x0e, 0x00],
+            [0xff, 0xb0, 0x00],
+            [0x99, 0x66, 0x00],
+            [0xff, 0xd8, 0x80],
+        ],
+    },
+    Palette {
+        name: "lcd", // handheld console screen
+        colors: [
+            [0x9b, 0xbc, 0x0f],
+            [0x0f, 0x38, 0x0f],
+            [0x30, 0x62, 0x30],
+            [0x8b, 0xac, 0x0f],
+        ],
+    },
+    Palette {
+        name: "octo", // Octo IDE defaults
+        colors: [
+            [0x99, 0x66, 0x00],
+            [0xff, 0xcc, 0x00],
+            [0xff, 0x66, 0x00],
+            [0x66, 0x22, 0x00],
+        ],
+    },
+];
+
+impl Palette {
+    pub fn find(name: &str) -> Option<&'static Palette> {
+        PALETTES
+            .iter()
+            .find(|palette| palette.name.eq_ignore_ascii_case(name))
+    }
 }
 
-impl Default for Quirks {
+/// Defines how many instructions are executed per frame
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Timing {
+    Ips,    // flat instructions per second budget
+    Cosmac, // each instruction takes its original COSMAC VIP time
+}
+
+impl Timing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ips" => Some(Self::Ips),
+            "cosmac" => Some(Self::Cosmac),
+            _ => None,
+        }
+    }
+}
+
+/// Clock of the delay and sound timers while the emulation runs faster or slower
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerClock {
+    Emulated, // 60 Hz of emulated time, timers speed up with fast forward and turbo
+    Realtime, // 60 Hz of wall clock time regardless of the emulation speed
+}
+
+impl TimerClock {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "emulated" => Some(Self::Emulated),
+            "realtime" => Some(Self::Realtime),
+            _ => None,
+        }
+    }
+}
+
+/// Graphics backend used to draw the window content
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    Canvas, // SDL2 renderer, supports all overlays
+    Wgpu,   // GPU shader pipeline, draws only the display
+}
+
+impl Renderer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "canvas" => Some(Self::Canvas),
+            "wgpu" => Some(Self::Wgpu),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Noise,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub sample_path: Option<String>, // WAV file played instead of the waveform
+    pub attack: u32,                 // milliseconds
+    pub release: u32,                // milliseconds
+    pub device: Option<String>,      // output device name, system default if not set
+    pub sample_rate: i32,
+    pub buffer_size: Option<u16>, // samples, smaller buffer reduces latency
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 220.0,
+            sample_path: None,
+            attack: 5,
+            release: 5,
+            device: None,
+            sample_rate: 44100,
+            buffer_size: None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct DebugConfig {
+    pub strict_memory: bool, // writes below 0x200 terminate the program
+    pub undo_depth: usize,   // instructions kept for stepping backwards, 0 disables
+    pub crash_dump: bool,    // save machine state to crash-<timestamp>.json on errors
+    pub permissive: bool,    // skip unknown instructions instead of terminating
+}
+
+impl Default for DebugConfig {
     fn default() -> Self {
         Self {
-            vf_reset: true,
-            memory: false,
-            shifting: true,
-            jumping: false,
+            strict_memory: false,
+            undo_depth: 1000,
+            crash_dump: false,
+            permissive: false,
         }
     }
 }
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub key_release_delay: u8, // timer ticks a pressed key is held at least
+    pub key_mapping: KeyMapping,
+    pub macros: Vec<MacroConfig>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            key_release_delay: 2,
+            key_mapping: KeyMapping::Scancode,
+            macros: Vec::new(),
+        }
+    }
+}
+
+/// Keypad sequence played by a hotkey, e.g. a level select code
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct MacroConfig {
+    pub hotkey: String, // SDL key name, e.g. F2
+    pub keys: String,   // hex keypad codes pressed in turn, `+N` waits N more frames
+    pub hold: u32,      // frames each key is held
+    pub delay: u32,     // frames after the release before the next key
+}
+
+impl Default for MacroConfig {
+    fn default() -> Self {
+        Self {
+            hotkey: String::new(),
+            keys: String::new(),
+            hold: 3,
+            delay: 3,
+        }
+    }
+}
+
+/// Defines how the keyboard keys of the keypad grid are found
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyMapping {
+    Scancode, // physical positions, the grid stays in place on AZERTY or QWERTZ
+    Keycode,  // characters of the active layout
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub rpl_flags: bool, // keep SCHIP RPL flags of every program between runs
+    pub persistent_ram: Option<String>, // hex `start-end` memory range kept between runs
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            rpl_flags: true,
+            persistent_ram: None,
+        }
+    }
+}
+
+/// Machine model defines complete set of quirks of the original interpreter
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    Cosmac,    // original COSMAC VIP interpreter
+    Chip48,    // CHIP-48 for HP-48 calculators
+    Schip,     // SUPER-CHIP 1.1
+    Hires,     // COSMAC VIP with 64x64 hi-res interpreter
+    Dream6800, // Dream 6800 with CHIPOS
+    Eti660,    // ETI-660 with 64x48 display, programs start at 0x600
+}
+
+impl Model {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "cosmac" => Some(Self::Cosmac),
+            "chip48" => Some(Self::Chip48),
+            "schip" => Some(Self::Schip),
+            "hires" => Some(Self::Hires),
+            "dream6800" => Some(Self::Dream6800),
+            "eti660" => Some(Self::Eti660),
+            _ => None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Self::Cosmac => Quirks {
+                vf_reset: true,
+                memory: true,
+                shifting: false,
+                jumping: false,
+                hires: false,
+                key_wait_release: true,
+                eti660: false,
+                half_scroll: false,
+            },
+            Self::Chip48 => Quirks {
+                vf_reset: false,
+                memory: false,
+                shifting: true,
+                jumping: true,
+                hires: false,
+                key_wait_release: true,
+                eti660: false,
+                half_scroll: false,
+            },
+            // SCHIP 1.1 scrolls the low resolution display by half
+            Self::Schip => Quirks {
+                half_scroll: true,
+                ..Self::Chip48.quirks()
+            },
+            Self::Hires => Quirks {
+                hires: true,
+                ..Self::Cosmac.quirks()
+            },
+            // CHIPOS follows the VIP interpreter semantics
+            Self::Dream6800 => Self::Cosmac.quirks(),
+            Self::Eti660 => Quirks {
+                eti660: true,
+                ..Self::Cosmac.quirks()
+            },
+        }
+    }
+}
+
+/// Quirks as they appear in config file: model defines base values,
+/// explicitly specified flags override them
+#[derive(Clone, Default, Deserialize)]
+pub struct QuirksConfig {
+    pub model: Option<Model>,
+    pub vf_reset: Option<bool>,
+    pub memory: Option<bool>,
+    pub shifting: Option<bool>,
+    pub jumping: Option<bool>,
+    pub hires: Option<bool>,
+    pub key_wait_release: Option<bool>,
+    pub eti660: Option<bool>,
+    pub half_scroll: Option<bool>,
+}
+
+impl QuirksConfig {
+    /// Model replaces the base quirks completely, then explicit flags are applied
+    pub fn apply(&self, base: Quirks) -> Quirks {
+        let base = self.model.map(|m| m.quirks()).unwrap_or(base);
+        Quirks {
+            vf_reset: self.vf_reset.unwrap_or(base.vf_reset),
+            memory: self.memory.unwrap_or(base.memory),
+            shifting: self.shifting.unwrap_or(base.shifting),
+            jumping: self.jumping.unwrap_or(base.jumping),
+            hires: self.hires.unwrap_or(base.hires),
+            key_wait_release: self.key_wait_release.unwrap_or(base.key_wait_release),
+            eti660: self.eti660.unwrap_or(base.eti660),
+            half_scroll: self.half_scroll.unwrap_or(base.half_scroll),
+        }
+    }
+}
+
+/// Quirks of the config file are read as `QuirksConfig` applied to the defaults
+impl<'de> serde::Deserialize<'de> for Quirks {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        QuirksConfig::deserialize(deserializer).map(Quirks::from)
+    }
+}
+
+impl From<QuirksConfig> for Quirks {
+    fn from(value: QuirksConfig) -> Self {
+        value.apply(Quirks::default())
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Write as _;
+use core::ops::Range;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const BYTES_PER_LINE: usize = 32;
+
+///
+/// Memory addresses which were ever executed as code or read as data
+///
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            flags: vec![0u8; memory_size],
+        }
+    }
+
+    pub fn mark_executed(&mut self, address: usize) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= EXECUTED;
+        }
+    }
+
+    pub fn mark_read(&mut self, address: usize) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= READ;
+        }
+    }
+
+    /// Map of the program loaded at the start address,
+    /// HTML page with program bytes or plain text
+    pub fn report(&self, start: usize, program: &[u8], is_html: bool) -> String {
+        let range = start..(start + program.len()).min(self.flags.len());
+        if is_html {
+            self.html_report(range, program)
+        } else {
+            self.text_report(range)
+        }
+    }
+
+    fn summary(&self, range: Range<usize>) -> String {
+        let flags = &self.flags[range];
+        let count = |mask: u8| flags.iter().filter(|x| **x & mask != 0).count();
+        let unused = flags.iter().filter(|x| **x == 0).count();
+        let total = flags.len().max(1) as f64;
+        format!(
+            "Program size: {} bytes, code: {} ({:.1}%), data: {} ({:.1}%), unused: {} ({:.1}%)",
+            flags.len(),
+            count(EXECUTED),
+            count(EXECUTED) as f64 * 100.0 / total,
+            count(READ),
+            count(READ) as f64 * 100.0 / total,
+            unused,
+            unused as f64 * 100.0 / total
+        )
+    }
+
+    fn text_report(&self, range: Range<usize>) -> String {
+        let mut report = String::new();
+        _ = writeln!(report, "{}", self.summary(range.clone()));
+        _ = writeln!(
+            report,
+            "Legend: C - executed, D - read as data, * - both, . - never accessed\n"
+        );
+        for start in range.clone().step_by(BYTES_PER_LINE) {
+            let end = (start + BYTES_PER_LINE).min(range.end);
+            let line = self.flags[start..end]
+                .iter()
+                .map(|flags| match *flags {
+                    EXECUTED => 'C',
+                    READ => 'D',
+                    0 => '.',
+                    _ => '*',
+                })
+                .collect::<String>();
+            _ = writeln!(report, "{start:03X}: {line}");
+        }
+        report
+    }
+
+    fn html_report(&self, range: Range<usize>, program: &[u8]) -> String {
+        let mut report = String::new();
+        report.push_str(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Coverage</title>\n",
+        );
+        report.push_str("<style>body{font-family:monospace}span{padding:0 2px}.c{background:#8f8}.d{background:#8cf}.b{background:#fd8}</style></head><body>\n");
+        _ = writeln!(report, "<p>{}</p>", self.summary(range.clone()));
+        report.push_str("<p><span class=\"c\">executed</span> <span class=\"d\">read as data</span> <span class=\"b\">both</span></p>\n<pre>\n");
+        for start in range.clone().step_by(BYTES_PER_LINE) {
+            let end = (start + BYTES_PER_LINE).min(range.end);
+            _ = write!(report, "{start:03X}:");
+            for (address, flags) in (start..end).zip(&self.flags[start..end]) {
+                let class = match *flags {
+                    EXECUTED => "c",
+                    READ => "d",
+                    0 => "",
+                    _ => "b",
+                };
+                let value = program[address - range.start];
+                _ = write!(report, "<span class=\"{class}\">{value:02X}</span>");
+            }
+            report.push('\n');
+        }
+        report.push_str("</pre>\n</body></html>\n");
+        report
+    }
+}
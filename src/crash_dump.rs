@@ -0,0 +1,156 @@
+use std::fmt::{self, Display};
+use std::fs;
+
+use serde_derive::Serialize;
+
+use crate::chip8::{Chip8, Error};
+use crate::config;
+use crate::disasm;
+use crate::symbols::Symbols;
+
+// instructions listed before and after the failed one
+const DISASSEMBLY_CONTEXT: usize = 5;
+
+#[derive(Serialize)]
+pub struct DisassemblyLine {
+    pub address: usize,
+    pub opcode: u16,
+    pub text: String,
+}
+
+///
+/// Machine state at the moment of the program error, logged as an error
+/// and optionally saved as JSON to attach to the bug report
+///
+#[derive(Serialize)]
+pub struct CrashDump {
+    pub error: String,
+    pub address: usize,           // instruction which caused the error
+    pub location: Option<String>, // address relative to the nearest label
+    pub pc: usize,
+    pub index: u32,
+    pub registers: Vec<u8>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub cycles: u64,
+    pub call_stack: Vec<String>, // active calls from the innermost one
+    pub disassembly: Vec<DisassemblyLine>,
+    pub screen: Vec<String>, // rows of `#` for lit and `.` for dark pixels
+}
+
+impl CrashDump {
+    pub fn new(machine: &Chip8, error: &Error, symbols: &Symbols) -> Self {
+        let address = machine.instruction_address();
+        let (dt, st) = machine.timers();
+        let call_stack = machine
+            .call_stack()
+            .iter()
+            .enumerate()
+            .map(|(depth, frame)| {
+                let subroutine = frame
+                    .subroutine
+                    .map(|address| symbols.label_or_hex(address))
+                    .unwrap_or("???".to_string());
+                format!(
+                    "#{depth} {subroutine} returns to {}",
+                    symbols.describe(frame.return_address)
+                )
+            })
+            .collect();
+        let screen = machine
+            .framebuffer()
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| if *pixel > 0 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        Self {
+            error: error.to_string(),
+            address,
+            location: (!symbols.is_empty()).then(|| symbols.describe(address)),
+            pc: machine.pc(),
+            index: machine.index(),
+            registers: machine.registers().to_vec(),
+            delay_timer: dt,
+            sound_timer: st,
+            cycles: machine.cycles(),
+            call_stack,
+            disassembly: disassembly(machine.memory(), address, symbols),
+            screen,
+        }
+    }
+
+    /// Writes the dump to `crash-<timestamp>.json` in the current directory
+    pub fn save(&self) -> Result<String, String> {
+        let file_name = config::prefixed_file_name("crash", "json");
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&file_name, json).map_err(|err| err.to_string())?;
+        Ok(file_name)
+    }
+}
+
+impl Display for CrashDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Machine error: {}", self.error)?;
+        let location = match &self.location {
+            Some(location) => format!(" ({location})"),
+            None => String::new(),
+        };
+        writeln!(
+            f,
+            "Instruction: {:03X}{location}, PC: {:03X}, I: {:03X}, DT: {}, ST: {}, cycles: {}",
+            self.address, self.pc, self.index, self.delay_timer, self.sound_timer, self.cycles
+        )?;
+        let registers = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{i:X}={value:02X}"))
+            .collect::<Vec<_>>();
+        writeln!(f, "{}", registers.join(" "))?;
+        writeln!(f, "Call stack:")?;
+        if self.call_stack.is_empty() {
+            writeln!(f, "  no active calls")?;
+        }
+        for frame in &self.call_stack {
+            writeln!(f, "  {frame}")?;
+        }
+        writeln!(f, "Disassembly:")?;
+        for line in &self.disassembly {
+            let marker = if line.address == self.address {
+                '>'
+            } else {
+                ' '
+            };
+            writeln!(
+                f,
+                "{marker} {:03X}: {:04X}  {}",
+                line.address, line.opcode, line.text
+            )?;
+        }
+        writeln!(f, "Screen:")?;
+        for row in &self.screen {
+            writeln!(f, "  {row}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Instructions around the address, the listing is aligned to the address
+fn disassembly(memory: &[u8], address: usize, symbols: &Symbols) -> Vec<DisassemblyLine> {
+    let start = address.saturating_sub(2 * DISASSEMBLY_CONTEXT) | address & 1;
+    (start..=address + 2 * DISASSEMBLY_CONTEXT)
+        .step_by(2)
+        .filter_map(|address| {
+            let bytes = memory.get(address..address + 2)?;
+            let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+            Some(DisassemblyLine {
+                address,
+                opcode,
+                text: disasm::disassemble(opcode, symbols),
+            })
+        })
+        .collect()
+}
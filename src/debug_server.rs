@@ -0,0 +1,520 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use tracing::{debug, info};
+
+use crate::chip8::{Chip8, State};
+use crate::condition::Condition;
+use crate::machine_state::MachineState;
+use crate::symbols::Symbols;
+use crate::watch_list::WatchList;
+
+///
+/// Remote debugger, accepts a single client with line based text protocol:
+///
+/// `regs`, `setreg <v0..vf|pc|i|dt|st> <hex>`, `read <addr> <len>`, `write <addr> <hex bytes>`,
+/// `stack`, `break <addr|label> [condition]`, `delete <addr|label>`, `breakwhen <condition>`,
+/// `deletewhen`, `breaks`, `pause`, `continue`, `step`, `stepback`, `stepover`, `stepout`, `reset`, `state`,
+/// `screen`, `watch <expression>`, `unwatch <index>`, `watchlist`. Numbers are hexadecimal, replies start with `OK` or `ERR`.
+/// Breakpoint with condition stops only if it's true, `breakwhen` stops on any address
+/// when the condition becomes true, see `Condition` for the syntax.
+/// When the machine stops by itself the server sends `STOP <reason> <pc>`,
+/// step over a call and step out reply `OK running` and finish with `STOP step <pc>`.
+/// `screen` replies the resolution and the display packed 1 bit per pixel,
+/// `export` replies and `import <json>` sets the machine state, see `MachineState`.
+/// Machine monitor aliases: `peek <addr> <len>`, `poke <register> <hex>`,
+/// `poke <addr> <hex bytes>...`, `bp add <addr|label> [condition]`, `bp del <addr|label>`,
+/// `bp list` and `resume`. The console monitor reads the same commands from stdin
+///
+pub struct DebugServer {
+    transport: Transport,
+    input: Vec<u8>, // received bytes of incomplete command
+    breakpoints: BTreeMap<usize, Option<Condition>>,
+    watches: Vec<(Condition, bool)>, // global conditions and their last values
+    resume_address: Option<usize>,   // breakpoint which is skipped once after continue
+}
+
+enum Transport {
+    Tcp {
+        listener: TcpListener,
+        client: Option<TcpStream>,
+    },
+    Console(Option<Receiver<String>>), // lines read by a separate thread, None after the end of input
+}
+
+impl DebugServer {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|err| err.to_string())?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        let transport = Transport::Tcp {
+            listener,
+            client: None,
+        };
+        Ok(Self::with_transport(transport))
+    }
+
+    /// Machine monitor reading commands from stdin and printing replies to stdout
+    pub fn console() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::with_transport(Transport::Console(Some(receiver)))
+    }
+
+    fn with_transport(transport: Transport) -> Self {
+        Self {
+            transport,
+            input: Vec::new(),
+            breakpoints: BTreeMap::new(),
+            watches: Vec::new(),
+            resume_address: None,
+        }
+    }
+
+    /// Accepts a client and executes received commands, called once per loop iteration
+    pub fn poll(&mut self, machine: &mut Chip8, symbols: &Symbols, watch_list: &mut WatchList) {
+        if !self.receive() {
+            return;
+        }
+        while let Some(position) = self.input.iter().position(|x| *x == b'\n') {
+            let line = self.input.drain(..=position).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            debug!("Debugger command: {line}");
+            let line = monitor_alias(&line).unwrap_or(line);
+            let reply = match self.execute(&line, machine, symbols, watch_list) {
+                Ok(reply) if reply.is_empty() => "OK".to_string(),
+                Ok(reply) => format!("OK {reply}"),
+                Err(err) => format!("ERR {err}"),
+            };
+            self.send(&reply);
+        }
+    }
+
+    /// Returns the reason to stop before the current instruction is executed
+    pub fn stop_reason(&mut self, machine: &Chip8) -> Option<&'static str> {
+        // global conditions are tracked even if the breakpoint is skipped
+        let mut is_condition_met = false;
+        for (condition, was_true) in &mut self.watches {
+            let is_true = condition.is_true(machine);
+            is_condition_met |= is_true && !*was_true;
+            *was_true = is_true;
+        }
+        let address = machine.pc();
+        if self.resume_address.take() == Some(address) {
+            return None;
+        }
+        if is_condition_met {
+            return Some("condition");
+        }
+        match self.breakpoints.get(&address) {
+            Some(None) => Some("breakpoint"),
+            Some(Some(condition)) if condition.is_true(machine) => Some("breakpoint"),
+            _ => None,
+        }
+    }
+
+    /// Informs the client that the machine stopped by itself
+    pub fn notify_stop(&mut self, reason: &str, pc: usize) {
+        self.send(&format!("STOP {reason} {pc:03X}"));
+    }
+
+    /// Appends available input, returns false if there is nobody to reply to
+    fn receive(&mut self) -> bool {
+        match &mut self.transport {
+            Transport::Tcp { listener, client } => {
+                if client.is_none() {
+                    *client = accept(listener);
+                    if client.is_some() {
+                        self.input.clear();
+                    }
+                }
+                let Some(stream) = client else {
+                    return false;
+                };
+                let mut buffer = [0u8; 1024];
+                loop {
+                    match stream.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(count) => self.input.extend_from_slice(&buffer[..count]),
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+                        Err(_) => break,
+                    }
+                }
+                self.disconnect();
+                false
+            }
+            Transport::Console(receiver) => {
+                let Some(lines) = receiver else {
+                    return false;
+                };
+                loop {
+                    match lines.try_recv() {
+                        Ok(line) => {
+                            self.input.extend_from_slice(line.as_bytes());
+                            self.input.push(b'\n');
+                        }
+                        Err(TryRecvError::Empty) => return true,
+                        Err(TryRecvError::Disconnected) => {
+                            // input is closed but buffered commands are still executed
+                            *receiver = None;
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        if let Transport::Tcp { client, .. } = &mut self.transport {
+            info!("Debugger disconnected");
+            *client = None;
+        }
+    }
+
+    fn send(&mut self, message: &str) {
+        let is_sent = match &mut self.transport {
+            Transport::Tcp {
+                client: Some(client),
+                ..
+            } => writeln!(client, "{message}").is_ok(),
+            Transport::Tcp { client: None, .. } => return,
+            Transport::Console(_) => {
+                println!("{message}");
+                true
+            }
+        };
+        if !is_sent {
+            self.disconnect();
+        }
+    }
+
+    /// Running machine reports the stop later, breakpoint at the start address is skipped
+    fn step_reply(&mut self, machine: &Chip8, start: usize) -> String {
+        if machine.is_running() {
+            self.resume_address = Some(start);
+            "running".to_string()
+        } else {
+            format!("{:03X}", machine.pc())
+        }
+    }
+
+    fn execute(
+        &mut self,
+        line: &str,
+        machine: &mut Chip8,
+        symbols: &Symbols,
+        watch_list: &mut WatchList,
+    ) -> Result<String, String> {
+        let mut args = line.split_whitespace();
+        let command = args.next().unwrap_or_default();
+        // conditions take the rest of the line
+        let rest = |args: std::str::SplitWhitespace| args.collect::<Vec<_>>().join(" ");
+        let mut arg = || args.next().ok_or("missing argument".to_string());
+        match command {
+            "regs" => Ok(registers(machine)),
+            "setreg" => {
+                let name = arg()?.to_lowercase();
+                let value = parse_hex(arg()?)?;
+                set_register(machine, &name, value)?;
+                Ok(String::new())
+            }
+            "read" => {
+                let address = parse_hex(arg()?)? as usize;
+                let length = parse_hex(arg()?)? as usize;
+                let bytes = machine
+                    .memory()
+                    .get(address..address + length)
+                    .ok_or("out of memory".to_string())?;
+                Ok(bytes.iter().map(|x| format!("{x:02X}")).collect())
+            }
+            "write" => {
+                let address = parse_hex(arg()?)? as usize;
+                let data = parse_bytes(arg()?)?;
+                machine
+                    .poke(address, &data)
+                    .map_err(|err| err.to_string())?;
+                Ok(String::new())
+            }
+            "stack" => Ok(call_stack(machine, symbols)),
+            "break" => {
+                let address = parse_location(arg()?, symbols)?;
+                let condition = rest(args);
+                let condition = if condition.is_empty() {
+                    None
+                } else {
+                    Some(Condition::parse(&condition)?)
+                };
+                self.breakpoints.insert(address, condition);
+                Ok(String::new())
+            }
+            "breakwhen" => {
+                let condition = Condition::parse(&rest(args))?;
+                let is_true = condition.is_true(machine);
+                self.watches.push((condition, is_true));
+                Ok(String::new())
+            }
+            "deletewhen" => {
+                self.watches.clear();
+                Ok(String::new())
+            }
+            "delete" => {
+                self.breakpoints.remove(&parse_location(arg()?, symbols)?);
+                Ok(String::new())
+            }
+            "breaks" => {
+                let breakpoints =
+                    self.breakpoints
+                        .iter()
+                        .map(|(address, condition)| match condition {
+                            Some(condition) => format!("{address:03X}[{condition}]"),
+                            None => format!("{address:03X}"),
+                        });
+                let watches = self
+                    .watches
+                    .iter()
+                    .map(|(condition, _)| format!("when[{condition}]"));
+                Ok(breakpoints.chain(watches).collect::<Vec<_>>().join(" "))
+            }
+            "watch" => {
+                watch_list.add(&rest(args))?;
+                Ok(String::new())
+            }
+            "unwatch" => {
+                watch_list.remove(parse_hex(arg()?)? as usize)?;
+                Ok(String::new())
+            }
+            "watchlist" => {
+                let values = watch_list
+                    .values(machine)
+                    .into_iter()
+                    .map(|(expression, value)| format!("{expression}={value:X}"));
+                Ok(values.collect::<Vec<_>>().join(";"))
+            }
+            "pause" => {
+                machine.pause();
+                Ok(format!("{:03X}", machine.pc()))
+            }
+            "continue" => {
+                if matches!(machine.get_state(), State::Paused) {
+                    self.resume_address = Some(machine.pc());
+                    machine.toggle_execution();
+                }
+                Ok(String::new())
+            }
+            "step" => {
+                if !matches!(machine.get_state(), State::Paused) {
+                    return Err("machine isn't paused".to_string());
+                }
+                machine.teak().map_err(|err| err.to_string())?;
+                // watchpoint hit is already a stop
+                _ = machine.take_watchpoint_hit();
+                Ok(format!("{:03X}", machine.pc()))
+            }
+            "stepback" => {
+                if !matches!(machine.get_state(), State::Paused) {
+                    return Err("machine isn't paused".to_string());
+                }
+                if !machine.step_back() {
+                    return Err("nothing to undo".to_string());
+                }
+                Ok(format!("{:03X}", machine.pc()))
+            }
+            "stepover" => {
+                if !matches!(machine.get_state(), State::Paused) {
+                    return Err("machine isn't paused".to_string());
+                }
+                let pc = machine.pc();
+                machine.step_over().map_err(|err| err.to_string())?;
+                _ = machine.take_watchpoint_hit();
+                Ok(self.step_reply(machine, pc))
+            }
+            "stepout" => {
+                if !matches!(machine.get_state(), State::Paused) {
+                    return Err("machine isn't paused".to_string());
+                }
+                let pc = machine.pc();
+                if !machine.step_out() {
+                    return Err("not in subroutine".to_string());
+                }
+                Ok(self.step_reply(machine, pc))
+            }
+            "reset" => {
+                machine.reset();
+                machine.pause();
+                Ok(format!("{:03X}", machine.pc()))
+            }
+            "state" => Ok(match machine.get_state() {
+                State::Running => "running",
+                State::WaitingForKey(_) => "waiting",
+                State::Paused => "paused",
+                State::Terminated => "terminated",
+            }
+            .to_string()),
+            "key" => {
+                if !machine.is_waiting_for_key() {
+                    return Err("machine doesn't wait for a key".to_string());
+                }
+                let key_code = parse_hex(arg()?)?;
+                machine
+                    .provide_key(key_code.min(u8::MAX as u32) as u8)
+                    .map_err(|err| err.to_string())?;
+                Ok(String::new())
+            }
+            "screen" => {
+                let framebuffer = machine.framebuffer();
+                let pixels: String = framebuffer
+                    .to_packed()
+                    .iter()
+                    .map(|x| format!("{x:02X}"))
+                    .collect();
+                Ok(format!(
+                    "{}x{} {pixels}",
+                    framebuffer.width(),
+                    framebuffer.height()
+                ))
+            }
+            "export" => MachineState::new(machine).to_json(),
+            "import" => {
+                MachineState::from_json(&rest(args))?.apply(machine)?;
+                Ok(String::new())
+            }
+            _ => Err(format!("unknown command {command}")),
+        }
+    }
+}
+
+fn registers(machine: &Chip8) -> String {
+    let (dt, st) = machine.timers();
+    let mut values = vec![
+        format!("pc={:03X}", machine.pc()),
+        format!("i={:03X}", machine.index()),
+        format!("dt={dt:02X}"),
+        format!("st={st:02X}"),
+        format!("sp={:X}", machine.stack().len()),
+    ];
+    for (i, value) in machine.registers().iter().enumerate() {
+        values.push(format!("v{i:x}={value:02X}"));
+    }
+    values.join(" ")
+}
+
+/// Frames from the innermost one as `<subroutine>:<return address>`, `???` for unknown subroutine
+fn call_stack(machine: &Chip8, symbols: &Symbols) -> String {
+    machine
+        .call_stack()
+        .iter()
+        .map(|frame| {
+            let subroutine = frame
+                .subroutine
+                .map(|address| symbols.label_or_hex(address))
+                .unwrap_or("???".to_string());
+            format!("{subroutine}:{}", symbols.describe(frame.return_address))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn set_register(machine: &mut Chip8, name: &str, value: u32) -> Result<(), String> {
+    let byte = || u8::try_from(value).map_err(|_| format!("value {value:X} is too big"));
+    match name {
+        "pc" => machine.set_pc(value as usize),
+        "i" => machine.set_index(value),
+        "dt" => machine.set_timers(byte()?, machine.timers().1),
+        "st" => machine.set_timers(machine.timers().0, byte()?),
+        _ => {
+            let index = name
+                .strip_prefix('v')
+                .filter(|index| index.len() == 1)
+                .and_then(|index| usize::from_str_radix(index, 16).ok())
+                .ok_or_else(|| format!("unknown register {name}"))?;
+            machine.set_register(index, byte()?);
+        }
+    }
+    Ok(())
+}
+
+fn accept(listener: &TcpListener) -> Option<TcpStream> {
+    let (stream, address) = listener.accept().ok()?;
+    stream.set_nonblocking(true).ok()?;
+    info!("Debugger connected from {address}");
+    Some(stream)
+}
+
+/// Protocol command of the machine monitor syntax, e.g. `bp add 2A4` is `break 2A4`
+fn monitor_alias(line: &str) -> Option<String> {
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    let command = match tokens[..] {
+        ["peek", ..] => "read",
+        ["resume"] => "continue",
+        ["bp", "add", ..] => "break",
+        ["bp", "del", ..] => "delete",
+        ["bp"] | ["bp", "list"] => "breaks",
+        ["poke", name, value] if is_register_name(name) => {
+            return Some(format!("setreg {name} {value}"));
+        }
+        ["poke", address, ref bytes @ ..] if !bytes.is_empty() => {
+            let bytes = bytes
+                .iter()
+                .map(|byte| byte.trim_start_matches("0x"))
+                .collect::<String>();
+            return Some(format!("write {address} {bytes}"));
+        }
+        _ => return None,
+    };
+    let skipped = if tokens[0] == "bp" { 2 } else { 1 };
+    let args = tokens.get(skipped..).unwrap_or_default().join(" ");
+    Some(format!("{command} {args}"))
+}
+
+fn is_register_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    matches!(name.as_str(), "pc" | "i" | "dt" | "st")
+        || name
+            .strip_prefix('v')
+            .is_some_and(|index| index.len() == 1 && u8::from_str_radix(index, 16).is_ok())
+}
+
+/// Label name or hexadecimal address
+fn parse_location(value: &str, symbols: &Symbols) -> Result<usize, String> {
+    match symbols.address(value) {
+        Some(address) => Ok(address),
+        None => parse_hex(value).map(|address| address as usize),
+    }
+}
+
+fn parse_hex(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid number {value}"))
+}
+
+fn parse_bytes(value: &str) -> Result<Vec<u8>, String> {
+    if value.len() % 2 != 0 {
+        return Err(format!("invalid bytes {value}"));
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            value
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("invalid bytes {value}"))
+        })
+        .collect()
+}
@@ -0,0 +1,17 @@
+use crate::opcode::Opcode;
+use crate::symbols::Symbols;
+
+/// Human readable instruction, unknown opcodes are shown as data
+/// and addresses with labels are shown by their names
+pub fn disassemble(opcode: u16, symbols: &Symbols) -> String {
+    let opcode = Opcode::decode(opcode);
+    let label = |nnn: u16| symbols.label_or_hex(nnn as usize);
+    match opcode {
+        Opcode::System(nnn) => format!("SYS {}", label(nnn)),
+        Opcode::Jump(nnn) => format!("JP {}", label(nnn)),
+        Opcode::Call(nnn) => format!("CALL {}", label(nnn)),
+        Opcode::LoadIndex(nnn) => format!("LD I, {}", label(nnn)),
+        Opcode::JumpOffset(nnn) => format!("JP V0, {}", label(nnn)),
+        _ => opcode.to_string(),
+    }
+}
@@ -1,216 +1,2140 @@
 extern crate sdl2;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioStatus};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::audio::{AudioDevice, AudioSpecDesired};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::mouse::MouseButton;
+use sdl2::video::{FullscreenType, Window};
 
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Texture, WindowCanvas};
+use sdl2::surface::Surface;
 use sdl2::{AudioSubsystem, Sdl, VideoSubsystem};
+use tracing::{error, info, info_span, warn};
 
-use crate::chip8::{self, Chip8, State};
-use crate::config::AppearanceConfig;
+use crate::audio::Buzzer;
+use crate::cheats::Cheats;
+use crate::chip8::{self, Chip8, Error, Severity, State};
+use crate::common::USize;
+use crate::config::{
+    self, AppearanceConfig, AudioConfig, Config, ConfigWatcher, FileWatcher, KeyMapping, Renderer,
+    TimerClock, Timing, PALETTES,
+};
+use crate::crash_dump::CrashDump;
+use crate::debug_server::DebugServer;
+use crate::frame_hash::FrameHashWriter;
+use crate::gui::DebugGui;
+use crate::input_log::{InputEvent, InputPlayer, InputRecorder};
+use crate::input_macro::{InputMacro, MacroPlayer};
+use crate::keypad::VirtualKeypad;
+use crate::menu::{Menu, MenuAction, MenuItem};
+use crate::netplay::Netplay;
+use crate::platform::FlagStorage;
+use crate::playlist::{self, Playlist};
+use crate::profiler::Profiler;
+use crate::recorder::Recorder;
+use crate::renderer::{CanvasRenderer, RendererBackend};
+use crate::rom_db;
+use crate::rom_info::RomInfo;
+use crate::rom_loader::{self, Rom};
+use crate::rom_settings::RomSettings;
+use crate::run_report::RunReport;
+use crate::save_ram::SaveRam;
+use crate::screenshot;
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::side_by_side::SideBySide;
+use crate::symbols::Symbols;
+use crate::text;
+use crate::watch_list::WatchList;
+#[cfg(feature = "wgpu")]
+use crate::wgpu_renderer::WgpuRenderer;
+
+const FRAMES_PER_SECOND: u64 = 60;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+// frames which weren't executed in time are dropped to avoid endless catching up
+const MAX_FRAME_LAG: Duration = Duration::from_millis(250);
+const SCANLINE_ALPHA: u8 = 96;
+const MAX_PHOSPHOR_DECAY: f32 = 0.95;
+const MIN_PHOSPHOR_INTENSITY: f32 = 0.05;
+const MAX_FRAME_BLENDING: usize = 3;
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(2);
+const MIN_OPERATIONS_PER_SECOND: u64 = 60;
+const MAX_OPERATIONS_PER_SECOND: u64 = 100_000;
+const VOLUME_STEP: f32 = 0.05;
+const FAST_FORWARD_SPEED: f32 = 10.0;
+const SLOW_MOTION_SPEED: f32 = 0.25;
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+pub const APP_NAME: &str = "Chip8";
+const APP_CLASS: &str = "chip8"; // X11/Wayland window class, matches the desktop file name
+const ICON_SCALE: u32 = 2;
+// keys of the 4x4 grid under the left hand and the keypad codes they send
+const KEYPAD_KEYS: [(&str, u8); 16] = [
+    ("1", 0x1),
+    ("2", 0x2),
+    ("3", 0x3),
+    ("4", 0xc),
+    ("Q", 0x4),
+    ("W", 0x5),
+    ("E", 0x6),
+    ("R", 0xd),
+    ("A", 0x7),
+    ("S", 0x8),
+    ("D", 0x9),
+    ("F", 0xe),
+    ("Z", 0xa),
+    ("X", 0x0),
+    ("C", 0xb),
+    ("V", 0xf),
+];
+// 16x16 'C8' sprite framed like a screen, drawn with the display colors
+const ICON_SPRITE: [u16; 16] = [
+    0b0111_1111_1111_1110,
+    0b1000_0000_0000_0001,
+    0b1000_0000_0000_0001,
+    0b1000_0000_0000_0001,
+    0b1000_0000_0000_0001,
+    0b1001_1110_0111_1001,
+    0b1001_0000_0100_1001,
+    0b1001_0000_0111_1001,
+    0b1001_0000_0100_1001,
+    0b1001_1110_0111_1001,
+    0b1000_0000_0000_0001,
+    0b1000_0000_0000_0001,
+    0b1000_0000_0000_0001,
+    0b0111_1111_1111_1110,
+    0b0000_0011_1100_0000,
+    0b0001_1111_1111_1000,
+];
 
 pub struct Environment<'a> {
     sdl_context: Sdl,
     video_subsystem: VideoSubsystem,
-    audio_subsystem: AudioSubsystem,
+    audio_subsystem: Option<AudioSubsystem>, // None if audio isn't available
     config: AppearanceConfig,
+    audio_config: AudioConfig,
     machine: &'a mut Chip8,
-    key_mapping: HashMap<Keycode, u8>,
+    key_mapping: HashMap<Keycode, u8>, // keymap bindings have priority over the positions
+    scancode_mapping: HashMap<Scancode, u8>,
+    macros: HashMap<Keycode, InputMacro>,
+    macro_player: Option<MacroPlayer>, // hotkeys are ignored until the macro ends
+    screenshot_on_exit: bool,
+    recorder: Option<Recorder>,
+    input_recorder: Option<InputRecorder>,
+    input_player: Option<InputPlayer>,
+    frame_hashes: Option<FrameHashWriter>,
+    side_by_side: Option<SideBySide>, // machine with other quirks shown next to the main one
+    flag_storage: Option<Box<dyn FlagStorage>>, // keeps SCHIP RPL flags between runs
+    save_ram: Option<SaveRam>,        // memory range of the started ROM kept between runs
+    netplay: Option<Netplay>,         // keypad shared with the remote player
+    cheats: Option<Cheats>,
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+    notification: Option<(String, Instant)>,
+    is_turbo: bool,
+    speed: f32, // emulated time multiplier while fast forward or slow motion key is held
+    operations_budget: u64,
+    cycles_budget: i64, // COSMAC VIP machine cycles left in the frame
+    is_frame_outdated: bool,
+    frame: Vec<u8>, // display pixels in RGB24 format
+    is_fullscreen: bool,
+    display_rect: Rect,  // area of the window occupied by the display
+    intensity: Vec<f32>, // pixels brightness used by phosphor and blending filters
+    frame_history: VecDeque<Vec<u8>>,
+    rom_path: Option<PathBuf>,
+    patch: Option<(PathBuf, PathBuf)>, // patch file and the ROM it's applied to on reloads
+    playlist: Option<Playlist>,
+    auto_advance: Option<Duration>, // switch to the next ROM of the playlist after the delay
+    rom_started_at: Instant,
+    menu_stack: Vec<Menu>,
+    is_running_before_menu: bool,
+    is_paused_by_focus_loss: bool, // resumed when the window gets the focus back
+    is_minimized: bool,
+    virtual_keypad: Option<VirtualKeypad>,
+    gui: DebugGui,
+    watch_list: WatchList, // expressions shown by debug window and debug server
+    is_gui_visible: bool,
+    debug_server: Option<DebugServer>,
+    profiler: Option<(Profiler, PathBuf)>, // report is written to the path on exit
+    config_watcher: Option<ConfigWatcher>,
+    rom_settings: Option<RomSettings>, // config file layered with ROM specific settings
+    rom_watcher: Option<FileWatcher>,  // follows the loaded ROM in watch mode
+    audio_device: Option<AudioDevice<Buzzer>>, // opened while the environment runs
+    is_muted: bool,
+    // speed measured for the window title
+    executed_count: u64,  // instructions executed since the last title update
+    presented_count: u64, // frames presented since the last title update
+    title_time: Instant,  // time of the last title update
+    is_title_running: bool,
+    is_title_outdated: bool, // title is updated on the next frame
+    hovered_pixel: Option<(usize, usize)>, // display pixel under the mouse while paused
+    coverage_path: Option<PathBuf>,
+    report_path: Option<PathBuf>,
+    presented_total: u64, // frames presented since the start for the run report
+    failed_opcode: Option<u16>, // unknown instruction which terminated the machine
+    symbols: Symbols,
+    is_crash_dump_saved: bool, // machine state is written to a file on errors
 }
 
 impl<'a> Environment<'a> {
-    pub fn new(appearance: AppearanceConfig, machine: &'a mut Chip8) -> Result<Self, String> {
-        let key_mapping = HashMap::from([
-            (Keycode::Num1, 0x1),
-            (Keycode::Num2, 0x2),
-            (Keycode::Num3, 0x3),
-            (Keycode::Num4, 0xc),
-            (Keycode::Q, 0x4),
-            (Keycode::W, 0x5),
-            (Keycode::E, 0x6),
-            (Keycode::R, 0xd),
-            (Keycode::A, 0x7),
-            (Keycode::S, 0x8),
-            (Keycode::D, 0x9),
-            (Keycode::F, 0xe),
-            (Keycode::Z, 0xa),
-            (Keycode::X, 0x0),
-            (Keycode::C, 0xb),
-            (Keycode::V, 0xf),
-        ]);
+    pub fn new(config: Config, machine: &'a mut Chip8) -> Result<Self, String> {
+        let (key_mapping, scancode_mapping, macros) = input_bindings(&config);
+        let appearance = config.appearance;
+        // hints take effect only when set before the video initialization
+        sdl2::hint::set("SDL_APP_NAME", APP_NAME);
+        sdl2::hint::set("SDL_VIDEO_X11_WMCLASS", APP_CLASS);
+        sdl2::hint::set("SDL_VIDEO_WAYLAND_WMCLASS", APP_CLASS);
         let sdl_context = sdl2::init()?;
         let video_subsystem = sdl_context.video()?;
-        let audio_subsystem = sdl_context.audio()?;
+        // headless machines may have no audio at all
+        let audio_subsystem = sdl_context
+            .audio()
+            .map_err(|err| warn!("Audio isn't available, continue without sound: {err}"))
+            .ok();
+        let is_fullscreen = appearance.is_fullscreen;
+        let virtual_keypad = appearance.is_virtual_keypad.then(VirtualKeypad::new);
         Ok(Self {
             sdl_context,
             video_subsystem,
             audio_subsystem,
             config: appearance,
+            audio_config: config.audio,
             machine,
             key_mapping,
+            scancode_mapping,
+            macros,
+            macro_player: None,
+            screenshot_on_exit: false,
+            recorder: None,
+            input_recorder: None,
+            input_player: None,
+            frame_hashes: None,
+            side_by_side: None,
+            flag_storage: None,
+            save_ram: None,
+            netplay: None,
+            cheats: None,
+            #[cfg(feature = "scripting")]
+            script: None,
+            notification: None,
+            is_turbo: false,
+            speed: 1.0,
+            operations_budget: 0,
+            cycles_budget: 0,
+            is_frame_outdated: true,
+            frame: Vec::new(),
+            is_fullscreen,
+            display_rect: Rect::new(0, 0, 1, 1),
+            intensity: vec![0.0; chip8::DISPLAY_SIZE.square()],
+            frame_history: VecDeque::new(),
+            rom_path: None,
+            patch: None,
+            playlist: None,
+            auto_advance: None,
+            rom_started_at: Instant::now(),
+            menu_stack: Vec::new(),
+            is_running_before_menu: false,
+            is_paused_by_focus_loss: false,
+            is_minimized: false,
+            virtual_keypad,
+            gui: DebugGui::new(),
+            watch_list: WatchList::default(),
+            is_gui_visible: false,
+            debug_server: None,
+            profiler: None,
+            config_watcher: None,
+            rom_settings: None,
+            rom_watcher: None,
+            audio_device: None,
+            is_muted: false,
+            executed_count: 0,
+            presented_count: 0,
+            title_time: Instant::now(),
+            is_title_running: false,
+            is_title_outdated: false,
+            hovered_pixel: None,
+            coverage_path: None,
+            report_path: None,
+            presented_total: 0,
+            failed_opcode: None,
+            symbols: Symbols::default(),
+            is_crash_dump_saved: config.debug.crash_dump,
         })
     }
 
+    pub fn set_screenshot_on_exit(&mut self, value: bool) {
+        self.screenshot_on_exit = value;
+    }
+
+    pub fn set_rom_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.rom_path = Some(path.as_ref().to_path_buf());
+    }
+
+    /// Patch of the current ROM, other ROMs of the playlist are loaded as they are
+    pub fn set_patch_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.patch = self
+            .rom_path
+            .clone()
+            .map(|rom_path| (path.as_ref().to_path_buf(), rom_path));
+    }
+
+    /// Reloads the ROM from its file when it's changed, e.g. an edited Octo source
+    pub fn watch_rom(&mut self) {
+        self.rom_watcher = self.rom_path.as_ref().map(FileWatcher::new);
+    }
+
+    pub fn set_playlist(&mut self, playlist: Playlist, auto_advance: Option<Duration>) {
+        self.playlist = Some(playlist);
+        self.auto_advance = auto_advance;
+    }
+
+    pub fn set_input_recorder(&mut self, recorder: InputRecorder) {
+        self.input_recorder = Some(recorder);
+    }
+
+    pub fn set_input_player(&mut self, player: InputPlayer) {
+        self.input_player = Some(player);
+    }
+
+    pub fn set_frame_hash_writer(&mut self, writer: FrameHashWriter) {
+        self.frame_hashes = Some(writer);
+    }
+
+    pub fn set_side_by_side(&mut self, side_by_side: SideBySide) {
+        self.side_by_side = Some(side_by_side);
+    }
+
+    /// Flags of the loaded program are restored at once
+    pub fn set_flag_storage(&mut self, storage: Box<dyn FlagStorage>) {
+        self.flag_storage = Some(storage);
+        self.load_rpl_flags();
+    }
+
+    /// The memory range is restored at once and saved on exit
+    pub fn set_save_ram(&mut self, save_ram: SaveRam) {
+        if let Err(err) = save_ram.restore(self.machine) {
+            error!("Failed to restore persistent RAM: {err}");
+        }
+        self.save_ram = Some(save_ram);
+    }
+
+    /// Enabled patches are applied at once
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        cheats.apply_patches(self.machine);
+        self.cheats = Some(cheats);
+    }
+
+    pub fn set_netplay(&mut self, netplay: Netplay) {
+        self.netplay = Some(netplay);
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn set_script(&mut self, script: Script) {
+        if script.is_write_hooked() {
+            self.machine.enable_memory_writes();
+        }
+        self.script = Some(script);
+    }
+
+    pub fn set_debug_server(&mut self, server: DebugServer) {
+        self.debug_server = Some(server);
+    }
+
+    pub fn set_config_watcher(&mut self, watcher: ConfigWatcher) {
+        self.config_watcher = Some(watcher);
+    }
+
+    /// Settings are applied again for each loaded ROM and reloaded config
+    pub fn set_rom_settings(&mut self, settings: RomSettings) {
+        self.rom_settings = Some(settings);
+    }
+
+    pub fn set_profile_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.profiler = Some((Profiler::default(), path.as_ref().to_path_buf()));
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.gui.set_symbols(symbols.clone());
+        self.symbols = symbols;
+    }
+
+    /// Machine is expected to have coverage enabled
+    pub fn set_coverage_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.coverage_path = Some(path.as_ref().to_path_buf());
+    }
+
+    pub fn set_report_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.report_path = Some(path.as_ref().to_path_buf());
+    }
+
     pub fn run(&mut self) -> Result<(), String> {
-        let dim = chip8::DISPLAY_SIZE * self.config.scale;
+        let started_at = Instant::now();
+        let dim = self.screen_size() * self.window_scale();
+        // virtual keypad takes the same height as display
+        let keypad_height = if self.virtual_keypad.is_some() {
+            dim.height
+        } else {
+            0
+        };
         // video
-        let window = self
+        let mut window = self
             .video_subsystem
-            .window("Chip8", dim.width as u32, dim.height as u32)
+            .window(
+                APP_NAME,
+                dim.width as u32,
+                (dim.height + keypad_height) as u32,
+            )
             .position_centered()
+            .resizable()
+            .allow_highdpi()
             .build()
             .map_err(|op| op.to_string())?;
-        let mut canvas = window.into_canvas().build().map_err(|op| op.to_string())?;
-        // audio
-        let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
-            channels: Some(1), // mono
-            samples: None,     // default sample size
+        match window_icon(self.foreground_rgb(), self.background_rgb()) {
+            Ok(icon) => window.set_icon(icon),
+            Err(err) => warn!("Failed to create window icon: {err}"),
+        }
+        let is_vsync = self.config.is_vsync;
+        let mut renderer: Box<dyn RendererBackend> = match self.config.renderer {
+            Renderer::Canvas => Box::new(CanvasRenderer::new(window, is_vsync)?),
+            #[cfg(feature = "wgpu")]
+            Renderer::Wgpu => Box::new(WgpuRenderer::new(window, is_vsync)?),
+            #[cfg(not(feature = "wgpu"))]
+            Renderer::Wgpu => {
+                warn!("Built without wgpu support, canvas renderer is used");
+                Box::new(CanvasRenderer::new(window, is_vsync)?)
+            }
         };
-
-        let audio_device = self
-            .audio_subsystem
-            .open_playback(None, &desired_spec, |spec| {
-                // initialize the audio callback
-                SquareWave {
-                    phase_inc: 220.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: self.config.sound_volume,
-                }
-            })
-            .map_err(|op| op.to_string())?;
-        audio_device.pause();
+        let is_vsync = renderer.is_vsync();
+        // audio
+        self.audio_device = self.open_audio();
+        if let Some(audio_device) = &self.audio_device {
+            // device is always playing, buzzer produces silence while its gate is closed
+            audio_device.resume();
+        }
+        // debug windows are drawn into a separate window sized texture
+        let mut gui_texture: Option<Texture> = None;
         // events
         let mut event_pump = self.sdl_context.event_pump()?;
-        let mut refresh_time = Instant::now();
-        let exp_duration = Duration::from_micros(1_000_000 / self.config.operations_per_second);
+        // fixed timestep scheduler: emulated frames are executed at exact 60 Hz rate
+        // while rendering happens at display refresh rate or the configured frame rate
+        let mut accumulator = Duration::ZERO;
+        let mut timer_accumulator = Duration::ZERO; // wall clock time of realtime timers
+        let mut last_time = Instant::now();
+        let mut pixel_density = 1.0;
         'emu_loop: loop {
-            let cycle_start = Instant::now();
+            let render_start = Instant::now();
             for event in event_pump.poll_iter() {
+                if self.on_gui_event(&event) {
+                    continue;
+                }
                 match event {
                     Event::Quit { .. } => break 'emu_loop,
-                    Event::KeyDown { keycode, .. } => self.on_key_down(keycode),
-                    Event::KeyUp { keycode, .. } => self.on_key_up(keycode),
+                    Event::KeyDown {
+                        keycode, scancode, ..
+                    } => self.on_key_down(keycode, scancode),
+                    Event::KeyUp {
+                        keycode, scancode, ..
+                    } => self.on_key_up(keycode, scancode),
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } => self.on_pointer_down(
+                        (x as f32 * pixel_density) as i32,
+                        (y as f32 * pixel_density) as i32,
+                    ),
+                    Event::MouseMotion { x, y, .. } => self.on_pointer_move(
+                        (x as f32 * pixel_density) as i32,
+                        (y as f32 * pixel_density) as i32,
+                    ),
+                    Event::MouseButtonUp {
+                        mouse_btn: MouseButton::Left,
+                        ..
+                    } => self.on_pointer_up(),
+                    Event::Window { win_event, .. } => self.on_window_event(win_event),
                     _ => {}
                 }
             }
-            match self.machine.get_state() {
-                State::Terminated => break,
-                State::Running => {
-                    if let Err(error) = self.machine.teak() {
-                        println!("Machine error: {}", error);
-                        self.machine.terminate();
+            if let Some(server) = &mut self.debug_server {
+                server.poll(self.machine, &self.symbols, &mut self.watch_list);
+            }
+            if matches!(self.machine.get_state(), State::Terminated) {
+                break;
+            }
+            self.reload_config();
+            self.reload_rom();
+            if self.is_auto_advance_due() {
+                self.switch_rom(true);
+            }
+            let now = Instant::now();
+            let real_elapsed = now.duration_since(last_time);
+            // frames and timers are executed faster or slower with the same step
+            let elapsed = real_elapsed.mul_f32(self.speed);
+            accumulator = (accumulator + elapsed).min(MAX_FRAME_LAG.mul_f32(self.speed.max(1.0)));
+            last_time = now;
+            // realtime timers tick by the wall clock instead of every emulated frame
+            let mut timer_ticks = self.is_realtime_timers().then(|| {
+                timer_accumulator = (timer_accumulator + real_elapsed).min(MAX_FRAME_LAG);
+                let ticks = (timer_accumulator.as_nanos() / FRAME_DURATION.as_nanos()) as u32;
+                timer_accumulator -= FRAME_DURATION * ticks;
+                ticks
+            });
+            if self.is_turbo {
+                // run as many frames as possible during a single display refresh
+                while now.elapsed() < FRAME_DURATION {
+                    self.run_frame(take_timer_tick(&mut timer_ticks));
+                }
+                accumulator = Duration::ZERO;
+            }
+            while accumulator >= FRAME_DURATION {
+                self.run_frame(take_timer_tick(&mut timer_ticks));
+                accumulator -= FRAME_DURATION;
+            }
+            // slow motion runs fewer frames than the wall clock ticks
+            for _ in 0..timer_ticks.unwrap_or_default() {
+                if self.machine.is_running() {
+                    self.tick_timers();
+                }
+            }
+            let is_running = self.machine.is_running();
+            let is_sound = is_running && self.machine.is_audio_playing();
+            if let Some(audio_device) = &mut self.audio_device {
+                let mut buzzer = audio_device.lock();
+                buzzer.is_gate_open = is_sound;
+                buzzer.is_paused = !is_running;
+                buzzer.set_volume(if self.is_muted {
+                    0.0
+                } else {
+                    self.config.sound_volume
+                });
+                buzzer.set_pattern(self.machine.audio_pattern());
+                #[cfg(feature = "megachip")]
+                if let Some(command) = self.machine.take_sound_command() {
+                    buzzer.set_digitized(command);
+                }
+            }
+            if let Some(interval) = self.background_interval() {
+                // nothing is visible, the machine keeps running at a lower loop rate
+                ::std::thread::sleep(interval.saturating_sub(render_start.elapsed()));
+                continue;
+            }
+            let fullscreen = if self.is_fullscreen {
+                FullscreenType::Desktop
+            } else {
+                FullscreenType::Off
+            };
+            if renderer.window().fullscreen_state() != fullscreen {
+                renderer.window_mut().set_fullscreen(fullscreen)?;
+                self.sdl_context.mouse().show_cursor(!self.is_fullscreen);
+            }
+            let (width, height) = renderer.window().drawable_size();
+            // high-DPI window has more drawable pixels than points
+            pixel_density = width as f32 / renderer.window().size().0.max(1) as f32;
+            self.gui.set_pixel_density(pixel_density);
+            self.update_layout(width, height);
+            renderer.clear(self.background_rgb())?;
+            self.draw_display(renderer.as_mut())?;
+            // overlays are available only if the renderer provides a canvas
+            if let Some(canvas) = renderer.canvas() {
+                self.draw_virtual_keypad(canvas)?;
+                self.draw_audio_scope(canvas)?;
+                self.draw_notification(canvas)?;
+                self.draw_menu(canvas)?;
+                if self.is_gui_visible {
+                    if self.gui.update(
+                        width,
+                        height,
+                        self.machine,
+                        &mut self.config,
+                        &mut self.watch_list,
+                    ) {
+                        self.is_frame_outdated = true;
+                    }
+                    let size = gui_texture.as_ref().map(|texture| texture.query());
+                    if size.map(|size| (size.width, size.height)) != Some((width, height)) {
+                        let mut texture = canvas
+                            .texture_creator()
+                            .create_texture_streaming(PixelFormatEnum::ABGR8888, width, height)
+                            .map_err(|op| op.to_string())?;
+                        texture.set_blend_mode(BlendMode::Blend);
+                        if let Some(old) = gui_texture.replace(texture) {
+                            // SAFETY: the texture belongs to the canvas which is still alive
+                            unsafe { old.destroy() };
+                        }
+                    }
+                    if let Some(texture) = &mut gui_texture {
+                        texture
+                            .with_lock(None, |buffer, pitch| self.gui.write_rgba(buffer, pitch))?;
+                        canvas.copy(texture, None, None)?;
                     }
                 }
-                State::Paused => audio_device.pause(),
             }
-            if refresh_time.elapsed().as_millis() >= 1000 / 60 {
-                match (self.machine.is_audio_playing(), audio_device.status()) {
-                    (false, AudioStatus::Playing) => audio_device.pause(),
-                    (true, AudioStatus::Paused) => audio_device.resume(),
-                    _ => {}
-                };
-                self.draw_display(&mut canvas)?;
-                canvas.present();
-                self.machine.on_timer();
-                refresh_time = Instant::now();
+            renderer.present()?;
+            self.update_title(renderer.window_mut())?;
+            if let Some(interval) = self.render_interval().filter(|_| !is_vsync) {
+                ::std::thread::sleep(interval.saturating_sub(render_start.elapsed()));
             }
-            let cycle_duration = cycle_start.elapsed();
-            let sleep_time = exp_duration.saturating_sub(cycle_duration);
-            if !sleep_time.is_zero() {
-                ::std::thread::sleep(sleep_time);
+        }
+        self.audio_device = None;
+        if self.screenshot_on_exit {
+            self.take_screenshot();
+        }
+        if self.recorder.is_some() {
+            self.toggle_recording();
+        }
+        self.store_save_ram();
+        if let Some((profiler, path)) = &self.profiler {
+            match profiler.write_report(path, &self.symbols) {
+                Ok(_) => info!("Profile written to {}", path.display()),
+                Err(err) => error!("Failed to write profile: {err}"),
+            }
+        }
+        if let (Some(coverage), Some(path)) = (self.machine.coverage(), &self.coverage_path) {
+            let is_html = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("html"));
+            let report =
+                coverage.report(self.machine.program_address(), self.machine.rom(), is_html);
+            match fs::write(path, report) {
+                Ok(_) => info!("Coverage written to {}", path.display()),
+                Err(err) => error!("Failed to write coverage: {err}"),
+            }
+        }
+        if let Some(path) = &self.report_path {
+            let report = RunReport::new(
+                self.machine,
+                self.presented_total,
+                started_at.elapsed(),
+                self.failed_opcode,
+            );
+            match report.save(path) {
+                Ok(_) => info!("Run report written to {}", path.display()),
+                Err(err) => error!("Failed to write run report: {err}"),
             }
         }
         Ok(())
     }
 
-    fn on_key_down(&mut self, keycode: Option<Keycode>) {
+    /// Executes instructions of a single 60 Hz frame and ticks the timers if it's due
+    fn run_frame(&mut self, is_timer_tick: bool) {
+        if !self.machine.is_running() {
+            return;
+        }
+        self.play_macro();
+        if !self.sync_netplay() {
+            return;
+        }
+        if let Some(cheats) = &self.cheats {
+            cheats.apply_freezes(self.machine);
+        }
+        match self.config.timing {
+            Timing::Ips => {
+                // carry the fractional part of instructions per frame to the next frame
+                self.operations_budget += self.config.operations_per_second;
+                let count = self.operations_budget / FRAMES_PER_SECOND;
+                self.operations_budget %= FRAMES_PER_SECOND;
+                for _ in 0..count {
+                    if !self.step() || self.is_idle() {
+                        break;
+                    }
+                }
+            }
+            Timing::Cosmac => {
+                // the last instruction may overrun the frame, it's paid by the next one
+                self.cycles_budget += chip8::COSMAC_CYCLES_PER_FRAME as i64;
+                while self.cycles_budget > 0 {
+                    if !self.step() || self.is_idle() {
+                        break;
+                    }
+                    self.cycles_budget -= self.machine.instruction_cost() as i64;
+                }
+                self.cycles_budget = self.cycles_budget.min(0);
+            }
+        }
+        if is_timer_tick {
+            self.tick_timers();
+        }
+        // Mega-CHIP color frames aren't recorded
+        let is_color_mode = self.is_color_mode();
+        if let Some(recorder) = self.recorder.as_mut().filter(|_| !is_color_mode) {
+            recorder.capture(self.machine.get_video_ram(), self.machine.display_size());
+        }
+        if let Some(writer) = &mut self.frame_hashes {
+            writer.on_frame(self.machine.get_video_ram());
+        }
+        self.check_side_by_side();
+        #[cfg(feature = "scripting")]
+        self.run_frame_script();
+        self.save_rpl_flags();
+        self.update_intensity();
+    }
+
+    /// Replay has the recorded ticks
+    fn tick_timers(&mut self) {
+        if !self.is_replaying() {
+            self.send_input(InputEvent::Timer);
+        }
+    }
+
+    /// Netplay peers must tick the timers on the same frames
+    fn is_realtime_timers(&self) -> bool {
+        self.config.timer_clock == TimerClock::Realtime && self.netplay.is_none()
+    }
+
+    fn setup_save_ram(&mut self, config: &Config) {
+        match SaveRam::with_config(&config.storage, self.machine.rom()) {
+            Ok(Some(save_ram)) => self.set_save_ram(save_ram),
+            Ok(None) => {}
+            Err(err) => error!("{err}"),
+        }
+    }
+
+    fn store_save_ram(&mut self) {
+        if let Some(save_ram) = &self.save_ram {
+            if let Err(err) = save_ram.save(self.machine) {
+                error!("Failed to save persistent RAM: {err}");
+            }
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    fn run_frame_script(&mut self) {
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        let result = script.on_frame(self.machine);
+        self.finish_script(result);
+    }
+
+    #[cfg(feature = "scripting")]
+    fn run_instruction_script(&mut self) {
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        let result = script.on_instruction(self.machine).and_then(|_| {
+            self.machine
+                .take_memory_writes()
+                .into_iter()
+                .try_for_each(|(address, value)| script.on_write(self.machine, address, value))
+        });
+        self.finish_script(result);
+    }
+
+    /// Script input is handled as the player one, failed script is stopped
+    #[cfg(feature = "scripting")]
+    fn finish_script(&mut self, result: Result<(), String>) {
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        let input = script.take_input();
+        let message = script.take_messages().pop();
+        input
+            .into_iter()
+            .for_each(|event| self.send_local_input(event));
+        if let Some(message) = message {
+            self.notify(message);
+        }
+        if let Err(err) = result {
+            error!("Script error: {err}");
+            self.script = None;
+            self.notify("SCRIPT ERROR".to_string());
+        }
+    }
+
+    /// Cheats of the loaded ROM replace the previous ones
+    fn load_cheats(&mut self) {
+        self.cheats = None;
+        match Cheats::for_rom(self.machine.rom()) {
+            Ok(Some(cheats)) => self.set_cheats(cheats),
+            Ok(None) => {}
+            Err(err) => error!("{err}"),
+        }
+    }
+
+    fn load_rpl_flags(&mut self) {
+        let Some(storage) = &mut self.flag_storage else {
+            return;
+        };
+        let key = rom_db::sha1_hex(self.machine.rom());
+        // programs which never stored the flags start with zeros
+        let flags = storage.load(&key).unwrap_or_default();
+        self.machine.set_rpl_flags(flags);
+    }
+
+    fn save_rpl_flags(&mut self) {
+        let Some(storage) = &mut self.flag_storage else {
+            return;
+        };
+        if !self.machine.poll_rpl_flags_changed() {
+            return;
+        }
+        let key = rom_db::sha1_hex(self.machine.rom());
+        if let Err(err) = storage.save(&key, self.machine.rpl_flags()) {
+            error!("Failed to save RPL flags: {err}");
+        }
+    }
+
+    /// Pauses the machine on the first frame where the compared displays differ
+    fn check_side_by_side(&mut self) {
+        let Some(side_by_side) = &mut self.side_by_side else {
+            return;
+        };
+        side_by_side.sync(self.machine.cycles());
+        self.is_frame_outdated |= side_by_side.poll_display_changed();
+        if side_by_side.check_frame(self.machine.get_video_ram()) {
+            let frame = side_by_side.diverged_frame().unwrap_or_default();
+            self.machine.pause();
+            self.notify(format!("DIVERGED AT FRAME {frame}"));
+        }
+    }
+
+    /// F1 toggles the debug windows, returns true if the event is consumed by them
+    fn on_gui_event(&mut self, event: &Event) -> bool {
+        if let Event::KeyDown {
+            keycode: Some(Keycode::F1),
+            ..
+        } = event
+        {
+            self.is_gui_visible = !self.is_gui_visible;
+            return true;
+        }
+        self.is_gui_visible && self.gui.handle_event(event)
+    }
+
+    /// Executes a single instruction, returns false if the machine isn't running
+    fn step(&mut self) -> bool {
+        if !self.machine.is_running() {
+            return false;
+        }
+        let pc = self.machine.pc();
+        if let Some(server) = &mut self.debug_server {
+            if let Some(reason) = server.stop_reason(self.machine) {
+                self.machine.pause();
+                server.notify_stop(reason, pc);
+                self.notify(format!("Breakpoint {pc:03X}"));
+                return false;
+            }
+        }
+        self.play_input();
+        if let (Some((profiler, _)), Some(opcode)) =
+            (&mut self.profiler, self.machine.current_opcode())
+        {
+            profiler.record(pc, opcode);
+        }
+        self.executed_count += 1;
+        match self.machine.teak() {
+            Ok(()) => {}
+            Err(error) if error.severity() == Severity::Warning => warn!("{error}"),
+            Err(error) => {
+                if let Error::UnknownInstruction(opcode) = error {
+                    self.failed_opcode = Some(opcode);
+                }
+                let dump = CrashDump::new(self.machine, &error, &self.symbols);
+                error!("{}", dump.to_string().trim_end());
+                if self.is_crash_dump_saved {
+                    match dump.save() {
+                        Ok(file_name) => info!("Crash dump saved to {file_name}"),
+                        Err(err) => error!("Failed to save crash dump: {err}"),
+                    }
+                }
+                self.machine.terminate();
+                if let Some(server) = &mut self.debug_server {
+                    server.notify_stop("error", pc);
+                }
+            }
+        }
+        if let Some(writer) = &mut self.frame_hashes {
+            writer.on_instruction(self.machine.cycles(), self.machine.get_video_ram());
+        }
+        #[cfg(feature = "scripting")]
+        self.run_instruction_script();
+        if self.machine.take_step_completed() {
+            if let Some(server) = &mut self.debug_server {
+                server.notify_stop("step", self.machine.pc());
+            }
+        }
+        if let Some(hit) = self.machine.take_watchpoint_hit() {
+            info!(
+                "Watchpoint: {} {:03X} at PC {:03X}",
+                hit.access, hit.address, hit.pc
+            );
+            log_machine_state(self.machine, &self.symbols);
+            self.notify(format!("{} {:03X}", hit.access, hit.address));
+            if let Some(server) = &mut self.debug_server {
+                server.notify_stop("watchpoint", hit.pc);
+            }
+        }
+        true
+    }
+
+    /// Keypad code of the key, explicit bindings are checked first
+    fn keypad_code(&self, keycode: Keycode, scancode: Option<Scancode>) -> Option<u8> {
+        self.key_mapping
+            .get(&keycode)
+            .copied()
+            .or_else(|| scancode.and_then(|scancode| self.scancode_mapping.get(&scancode).copied()))
+    }
+
+    fn on_key_down(&mut self, keycode: Option<Keycode>, scancode: Option<Scancode>) {
         let Some(keycode) = keycode else {
             return;
         };
-        if let Some(code) = self.key_mapping.get(&keycode) {
-            self.machine.key_down(*code);
+        if !self.menu_stack.is_empty() {
+            self.on_menu_key_down(keycode);
+            return;
+        }
+        if let Some(input_macro) = self.macros.get(&keycode) {
+            if !self.is_replaying() && self.macro_player.is_none() {
+                self.macro_player = Some(MacroPlayer::new(input_macro));
+            }
+            return;
+        }
+        if let Some(code) = self.keypad_code(keycode, scancode) {
+            if !self.is_replaying() {
+                self.send_local_input(InputEvent::KeyDown(code));
+            }
             return;
         }
         match keycode {
-            Keycode::Escape => self.machine.terminate(),
+            Keycode::Escape => self.open_menu(),
             Keycode::F5 => self.machine.toggle_execution(),
-            Keycode::F9 => self.machine.reset(),
+            Keycode::F6 => self.toggle_turbo(),
+            Keycode::Tab => self.hold_speed(FAST_FORWARD_SPEED),
+            Keycode::CapsLock => self.hold_speed(SLOW_MOTION_SPEED),
+            Keycode::Equals | Keycode::Plus | Keycode::KpPlus => self.change_speed(true),
+            Keycode::Minus | Keycode::KpMinus => self.change_speed(false),
+            Keycode::F7 => self.config.is_audio_scope = !self.config.is_audio_scope,
+            Keycode::F8 => self.toggle_recording(),
+            Keycode::M => self.toggle_mute(),
+            Keycode::P => self.cycle_palette(),
+            Keycode::RightBracket => self.change_volume(true),
+            Keycode::LeftBracket => self.change_volume(false),
+            Keycode::F9 if !self.is_replaying() => self.send_local_input(InputEvent::Reset),
+            Keycode::F11 => self.is_fullscreen = !self.is_fullscreen,
+            Keycode::F12 => self.take_screenshot(),
+            Keycode::PageDown => self.switch_rom(true),
+            Keycode::PageUp => self.switch_rom(false),
             _ => {
                 // unhandled keys
             }
         }
     }
 
-    fn on_key_up(&mut self, keycode: Option<Keycode>) {
+    fn on_key_up(&mut self, keycode: Option<Keycode>, scancode: Option<Scancode>) {
         let Some(keycode) = keycode else {
             return;
         };
-        if let Some(key_code) = self.key_mapping.get(&keycode) {
-            self.machine.key_up(*key_code);
+        if let Some(key_code) = self.keypad_code(keycode, scancode) {
+            if !self.is_replaying() {
+                self.send_local_input(InputEvent::KeyUp(key_code));
+            }
+            return;
+        }
+        if matches!(keycode, Keycode::Tab | Keycode::CapsLock) {
+            self.speed = 1.0;
         }
     }
 
-    fn draw_display(&mut self, canvas: &mut WindowCanvas) -> Result<(), String> {
-        let memory = self.machine.get_video_ram();
-        let size = self.config.scale;
-        let bg_color = Color::RGB(
-            self.config.background_red,
-            self.config.background_green,
-            self.config.background_blue,
+    fn on_pointer_down(&mut self, x: i32, y: i32) {
+        if !self.menu_stack.is_empty() || self.is_replaying() {
+            return;
+        }
+        if let Some(pixel) = self.pixel_at(x, y) {
+            self.toggle_pixel(pixel);
+            return;
+        }
+        let Some(keypad) = &mut self.virtual_keypad else {
+            return;
+        };
+        if let Some(code) = keypad.press(x, y) {
+            self.send_local_input(InputEvent::KeyDown(code));
+        }
+    }
+
+    fn on_pointer_move(&mut self, x: i32, y: i32) {
+        let pixel = self.pixel_at(x, y);
+        if pixel != self.hovered_pixel {
+            self.hovered_pixel = pixel;
+            self.is_title_outdated = true;
+        }
+    }
+
+    /// Column and row of the main display pixel at the window point, only while paused
+    fn pixel_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let is_inspected = matches!(self.machine.get_state(), State::Paused)
+            && self.menu_stack.is_empty()
+            && !self.is_color_mode();
+        let rect = self.display_rect;
+        if !is_inspected || !rect.contains_point((x, y)) {
+            return None;
+        }
+        // the compared display of side by side mode isn't editable
+        let screen = self.screen_size();
+        let col = (x - rect.x()) as usize * screen.width / rect.width() as usize;
+        let row = (y - rect.y()) as usize * screen.height / rect.height() as usize;
+        let size = self.machine.display_size();
+        (col < size.width && row < size.height).then_some((col, row))
+    }
+
+    /// Flips the pixel of the first plane, e.g. to set up a collision test
+    fn toggle_pixel(&mut self, (col, row): (usize, usize)) {
+        let width = self.machine.display_size().width;
+        let mut pixels = self.machine.get_video_ram().to_vec();
+        pixels[row * width + col] ^= 1;
+        self.machine.set_video_ram(&pixels);
+        self.is_title_outdated = true;
+    }
+
+    fn on_pointer_up(&mut self) {
+        let Some(keypad) = &mut self.virtual_keypad else {
+            return;
+        };
+        if let Some(code) = keypad.release() {
+            self.send_local_input(InputEvent::KeyUp(code));
+        }
+    }
+
+    /// Splits the window between display and virtual keypad
+    fn update_layout(&mut self, width: u32, height: u32) {
+        let size = self.screen_size();
+        let is_integer = self.config.is_integer_scaling;
+        let Some(keypad) = &mut self.virtual_keypad else {
+            self.display_rect = display_rect(width, height, &size, is_integer);
+            return;
+        };
+        let display_height = height / 2;
+        self.display_rect = display_rect(width, display_height, &size, is_integer);
+        keypad.set_area(Rect::new(
+            0,
+            display_height as i32,
+            width.max(1),
+            (height - display_height).max(1),
+        ));
+    }
+
+    /// Configured scale, with integer scaling it is reduced
+    /// to the largest one fitting the usable display area
+    fn window_scale(&self) -> usize {
+        let scale = self.config.scale;
+        if !self.config.is_integer_scaling {
+            return scale;
+        }
+        let Ok(bounds) = self.video_subsystem.display_usable_bounds(0) else {
+            return scale;
+        };
+        let size = self.screen_size();
+        let rows = if self.virtual_keypad.is_some() { 2 } else { 1 };
+        let fitting = (bounds.width() as usize / size.width)
+            .min(bounds.height() as usize / (size.height * rows));
+        scale.min(fitting).max(1)
+    }
+
+    fn draw_virtual_keypad(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let Some(keypad) = &self.virtual_keypad else {
+            return Ok(());
+        };
+        let [r, g, b] = self.foreground_rgb();
+        let fg_color = Color::RGB(r, g, b);
+        let [r, g, b] = self.background_rgb();
+        keypad.draw(canvas, fg_color, Color::RGB(r, g, b))
+    }
+
+    fn on_window_event(&mut self, event: WindowEvent) {
+        match event {
+            WindowEvent::FocusLost => {
+                // netplay keeps in step with the peer, debugger controls the execution itself
+                let is_controlled = self.netplay.is_some() || self.debug_server.is_some();
+                let is_running = self.machine.is_running();
+                if self.config.is_pause_on_focus_loss && !is_controlled && is_running {
+                    self.machine.toggle_execution();
+                    self.is_paused_by_focus_loss = true;
+                }
+            }
+            WindowEvent::FocusGained => {
+                let is_paused = matches!(self.machine.get_state(), State::Paused);
+                if self.is_paused_by_focus_loss && is_paused && self.menu_stack.is_empty() {
+                    self.machine.toggle_execution();
+                }
+                self.is_paused_by_focus_loss = false;
+            }
+            WindowEvent::Minimized => self.is_minimized = true,
+            WindowEvent::Restored | WindowEvent::Maximized | WindowEvent::Shown => {
+                self.is_minimized = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn open_menu(&mut self) {
+        self.is_running_before_menu = self.machine.is_running();
+        if self.is_running_before_menu {
+            self.machine.toggle_execution();
+        }
+        let mut items = vec![
+            MenuItem::new("Resume", MenuAction::Resume),
+            MenuItem::new("Reset", MenuAction::Reset),
+            MenuItem::new("Load ROM", MenuAction::LoadRom),
+            MenuItem::new("Options", MenuAction::Options),
+            MenuItem::new("Quit", MenuAction::Quit),
+        ];
+        if self.cheats.is_some() {
+            items.insert(items.len() - 1, MenuItem::new("Cheats", MenuAction::Cheats));
+        }
+        self.menu_stack = vec![Menu::new("Paused", items)];
+    }
+
+    fn close_menu(&mut self) {
+        self.menu_stack.clear();
+        if self.is_running_before_menu && matches!(self.machine.get_state(), State::Paused) {
+            self.machine.toggle_execution();
+        }
+    }
+
+    fn on_menu_key_down(&mut self, keycode: Keycode) {
+        let Some(menu) = self.menu_stack.last_mut() else {
+            return;
+        };
+        match keycode {
+            Keycode::Up => menu.select_prev(),
+            Keycode::Down => menu.select_next(),
+            Keycode::Return | Keycode::KpEnter | Keycode::Space => {
+                if let Some(action) = menu.selected_action() {
+                    self.on_menu_action(action);
+                }
+            }
+            Keycode::Escape | Keycode::Backspace => self.on_menu_action(MenuAction::Back),
+            Keycode::F11 => self.is_fullscreen = !self.is_fullscreen,
+            _ => {}
+        }
+    }
+
+    fn on_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Resume => self.close_menu(),
+            MenuAction::Reset => {
+                self.close_menu();
+                if !self.is_replaying() {
+                    self.send_local_input(InputEvent::Reset);
+                }
+            }
+            MenuAction::LoadRom => {
+                let menu = self.roms_menu();
+                self.menu_stack.push(menu);
+            }
+            MenuAction::Options => {
+                let menu = self.options_menu();
+                self.menu_stack.push(menu);
+            }
+            MenuAction::Cheats => {
+                let menu = self.cheats_menu();
+                self.menu_stack.push(menu);
+            }
+            MenuAction::ToggleCheat(index) => {
+                if let Some(cheats) = &mut self.cheats {
+                    cheats.toggle(index, self.machine);
+                }
+                self.refresh_menu(self.cheats_menu());
+            }
+            MenuAction::Quit => {
+                self.menu_stack.clear();
+                self.machine.terminate();
+            }
+            MenuAction::OpenFile(path) => {
+                self.close_menu();
+                self.load_rom_file(path);
+            }
+            MenuAction::TogglePixelStyle => {
+                self.config.is_pixel_style = !self.config.is_pixel_style;
+                self.refresh_options_menu();
+            }
+            MenuAction::ToggleScanlines => {
+                self.config.is_scanline_style = !self.config.is_scanline_style;
+                self.refresh_options_menu();
+            }
+            MenuAction::ToggleFullscreen => {
+                self.is_fullscreen = !self.is_fullscreen;
+                self.refresh_options_menu();
+            }
+            MenuAction::Back => {
+                self.menu_stack.pop();
+                if self.menu_stack.is_empty() {
+                    self.close_menu();
+                }
+            }
+        }
+    }
+
+    fn options_menu(&self) -> Menu {
+        let on_off = |value: bool| if value { "ON" } else { "OFF" };
+        let items = vec![
+            MenuItem::new(
+                format!("Pixel grid: {}", on_off(self.config.is_pixel_style)),
+                MenuAction::TogglePixelStyle,
+            ),
+            MenuItem::new(
+                format!("Scanlines: {}", on_off(self.config.is_scanline_style)),
+                MenuAction::ToggleScanlines,
+            ),
+            MenuItem::new(
+                format!("Fullscreen: {}", on_off(self.is_fullscreen)),
+                MenuAction::ToggleFullscreen,
+            ),
+            MenuItem::new("Back", MenuAction::Back),
+        ];
+        Menu::new("Options", items)
+    }
+
+    fn cheats_menu(&self) -> Menu {
+        let cheats = self.cheats.as_ref().map(Cheats::cheats).unwrap_or_default();
+        let mut items = cheats
+            .iter()
+            .enumerate()
+            .map(|(index, cheat)| {
+                let state = if cheat.is_enabled { "ON" } else { "OFF" };
+                MenuItem::new(
+                    format!("{}: {state}", cheat.name),
+                    MenuAction::ToggleCheat(index),
+                )
+            })
+            .collect::<Vec<_>>();
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("Cheats", items)
+    }
+
+    fn refresh_options_menu(&mut self) {
+        self.refresh_menu(self.options_menu());
+    }
+
+    /// Replaces the top menu keeping the selected item
+    fn refresh_menu(&mut self, mut menu: Menu) {
+        if let Some(current) = self.menu_stack.pop() {
+            menu.set_selected_index(current.selected_index());
+        }
+        self.menu_stack.push(menu);
+    }
+
+    /// Lists ROM files located in the directory of the current ROM
+    fn roms_menu(&self) -> Menu {
+        let dir = self
+            .rom_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .filter(|path| !path.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let paths = playlist::list_roms(dir);
+        let mut items = paths
+            .into_iter()
+            .map(|path| {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                MenuItem::new(name, MenuAction::OpenFile(path))
+            })
+            .collect::<Vec<_>>();
+        items.push(MenuItem::new("Back", MenuAction::Back));
+        Menu::new("Load ROM", items)
+    }
+
+    /// Loads the next or the previous ROM of the playlist
+    fn switch_rom(&mut self, is_next: bool) {
+        if self.is_replaying() {
+            return;
+        }
+        let Some(playlist) = &mut self.playlist else {
+            return;
+        };
+        let path = if is_next {
+            playlist.next()
+        } else {
+            playlist.prev()
+        };
+        let path = path.to_path_buf();
+        self.load_rom_file(path);
+    }
+
+    fn is_auto_advance_due(&self) -> bool {
+        let Some(delay) = self.auto_advance else {
+            return false;
+        };
+        self.menu_stack.is_empty() && self.rom_started_at.elapsed() >= delay
+    }
+
+    fn load_rom_file(&mut self, path: PathBuf) {
+        if self.netplay.is_some() {
+            self.notify("NETPLAY".to_string());
+            return;
+        }
+        let _span = info_span!("load_rom", path = %path.display()).entered();
+        // the range belongs to the settings of the previous ROM
+        self.store_save_ram();
+        self.save_ram = None;
+        let patch = self
+            .patch
+            .as_ref()
+            .filter(|(_, rom_path)| *rom_path == path)
+            .map(|(patch_path, _)| patch_path);
+        let result = rom_loader::load(&path)
+            .and_then(|mut rom| match patch {
+                Some(patch_path) => rom_loader::apply_patch(&mut rom, patch_path).map(|_| rom),
+                None => Ok(rom),
+            })
+            .and_then(|rom| {
+                info!("{}", RomInfo::new(&rom.data));
+                let config = self.rom_settings.as_mut().map(|settings| {
+                    settings.select_rom(&rom);
+                    settings.config()
+                });
+                let Rom { data, symbols, .. } = rom;
+                // labels of the reassembled source replace the old ones
+                if !symbols.is_empty() {
+                    self.symbols = symbols;
+                }
+                if let Some(side_by_side) = &mut self.side_by_side {
+                    side_by_side.load_rom(data.clone())?;
+                }
+                self.machine.load_rom(data).map_err(|err| err.to_string())?;
+                Ok(config)
+            });
+        match result {
+            Ok(config) => {
+                if let Some(config) = config {
+                    self.setup_save_ram(&config);
+                    self.apply_config(config);
+                }
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.notify(name);
+                if self.rom_watcher.is_some() {
+                    self.rom_watcher = Some(FileWatcher::new(&path));
+                }
+                self.rom_path = Some(path);
+                self.rom_started_at = Instant::now();
+                self.load_rpl_flags();
+                self.load_cheats();
+            }
+            Err(err) => {
+                error!("Failed to load ROM {}: {err}", path.display());
+                self.notify("LOAD FAILED".to_string());
+            }
+        }
+    }
+
+    fn draw_menu(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let Some(menu) = self.menu_stack.last() else {
+            return Ok(());
+        };
+        let pixel_size = (self.cell_size() / 3).max(1);
+        let [r, g, b] = self.foreground_rgb();
+        let fg_color = Color::RGB(r, g, b);
+        let [r, g, b] = self.background_rgb();
+        let bg_color = Color::RGB(r, g, b);
+        menu.draw(canvas, self.display_rect, pixel_size, fg_color, bg_color)
+    }
+
+    /// The rest of the frame is skipped while the program spins
+    fn is_idle(&self) -> bool {
+        self.config.is_idle_skip && self.machine.is_idle()
+    }
+
+    fn is_replaying(&self) -> bool {
+        self.input_player.is_some()
+    }
+
+    fn play_input(&mut self) {
+        let Some(player) = &mut self.input_player else {
+            return;
+        };
+        let cycle = self.machine.cycles();
+        let mut events = Vec::new();
+        while let Some(event) = player.next_event(cycle) {
+            events.push(event);
+        }
+        if player.is_finished() {
+            info!("Input replay finished");
+            self.input_player = None;
+        }
+        events.into_iter().for_each(|event| self.send_input(event));
+    }
+
+    /// Sends the macro events due on this frame as the local player input
+    fn play_macro(&mut self) {
+        let Some(player) = &mut self.macro_player else {
+            return;
+        };
+        let events = player.next_frame();
+        if player.is_finished() {
+            self.macro_player = None;
+        }
+        events
+            .into_iter()
+            .for_each(|event| self.send_local_input(event));
+    }
+
+    /// Player input goes through netplay to be applied by both machines at the same frame
+    fn send_local_input(&mut self, event: InputEvent) {
+        let Some(netplay) = &mut self.netplay else {
+            self.send_input(event);
+            return;
+        };
+        match event {
+            InputEvent::KeyDown(code) => netplay.key_down(code),
+            InputEvent::KeyUp(code) => netplay.key_up(code),
+            InputEvent::Reset => netplay.reset(),
+            InputEvent::Timer => self.send_input(event),
+        }
+    }
+
+    /// Applies the input of both players, false if the frame waits for the peer
+    fn sync_netplay(&mut self) -> bool {
+        let Some(netplay) = &mut self.netplay else {
+            return true;
+        };
+        match netplay.poll() {
+            Ok(Some(events)) => {
+                events.into_iter().for_each(|event| self.send_input(event));
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                error!("{err}");
+                self.netplay = None;
+                self.notify("NETPLAY ENDED".to_string());
+                true
+            }
+        }
+    }
+
+    fn send_input(&mut self, event: InputEvent) {
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(self.machine.cycles(), event);
+        }
+        if let Some(side_by_side) = &mut self.side_by_side {
+            side_by_side.send_input(self.machine.cycles(), event);
+        }
+        let result = match event {
+            InputEvent::KeyDown(code) => self.machine.key_down(code),
+            InputEvent::KeyUp(code) => self.machine.key_up(code),
+            InputEvent::Timer => {
+                self.machine.on_timer();
+                self.update_sound_gate();
+                Ok(())
+            }
+            InputEvent::Reset => {
+                self.machine.reset();
+                if let Some(cheats) = &self.cheats {
+                    cheats.apply_patches(self.machine);
+                }
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            warn!("{err}");
+        }
+    }
+
+    /// Beep stops on the timer tick which zeroed the sound timer rather than on the next render
+    fn update_sound_gate(&mut self) {
+        if self.machine.is_audio_playing() {
+            return;
+        }
+        if let Some(audio_device) = &mut self.audio_device {
+            audio_device.lock().is_gate_open = false;
+        }
+    }
+
+    fn change_speed(&mut self, is_faster: bool) {
+        if matches!(self.config.timing, Timing::Cosmac) {
+            self.notify("COSMAC TIMING".to_string());
+            return;
+        }
+        if self.netplay.is_some() {
+            self.notify("NETPLAY".to_string());
+            return;
+        }
+        let ips = self.config.operations_per_second;
+        // change by 25% rounded to tens
+        let ips = if is_faster { ips * 5 / 4 } else { ips * 4 / 5 };
+        let ips = (ips + 5) / 10 * 10;
+        self.config.operations_per_second =
+            ips.clamp(MIN_OPERATIONS_PER_SECOND, MAX_OPERATIONS_PER_SECOND);
+        self.notify(format!("IPS {}", self.config.operations_per_second));
+    }
+
+    /// Speed multiplier applied while the key is held
+    fn hold_speed(&mut self, speed: f32) {
+        if self.speed == speed {
+            // repeated key down event
+            return;
+        }
+        if self.netplay.is_some() {
+            self.notify("NETPLAY".to_string());
+            return;
+        }
+        self.speed = speed;
+        self.notify(format!("SPEED x{speed}"));
+    }
+
+    fn toggle_turbo(&mut self) {
+        self.is_turbo = !self.is_turbo;
+        let text = if self.is_turbo {
+            "TURBO ON"
+        } else {
+            "TURBO OFF"
+        };
+        self.notify(text.to_string());
+    }
+
+    /// Window title shows ROM name, machine state and measured speed
+    fn update_title(&mut self, window: &mut Window) -> Result<(), String> {
+        self.presented_count += 1;
+        self.presented_total += 1;
+        let is_running = self.machine.is_running();
+        let elapsed = self.title_time.elapsed();
+        // state change is shown immediately, speed is averaged over the interval
+        let is_changed = is_running != self.is_title_running || self.is_title_outdated;
+        if elapsed < TITLE_UPDATE_INTERVAL && !is_changed {
+            return Ok(());
+        }
+        let mut title = APP_NAME.to_string();
+        if let Some(name) = self.rom_path.as_ref().and_then(|path| path.file_name()) {
+            title += &format!(" - {}", name.to_string_lossy());
+        }
+        if is_running && !self.is_title_running {
+            // speed isn't known until the machine runs for the whole interval
+            title += " - Running";
+        } else if is_running {
+            let seconds = elapsed.as_secs_f64();
+            let ips = self.executed_count as f64 / seconds;
+            let fps = self.presented_count as f64 / seconds;
+            title += &format!(" - Running - {ips:.0} IPS - {fps:.0} FPS");
+            if self.machine.is_waiting_for_key() {
+                title += " - Waiting for key";
+            }
+        } else if matches!(self.machine.get_state(), State::Paused) {
+            title += " - Paused";
+            // the resolution may be changed since the mouse moved
+            let size = self.machine.display_size();
+            let hovered = self
+                .hovered_pixel
+                .filter(|(col, row)| *col < size.width && *row < size.height);
+            if let Some((col, row)) = hovered {
+                let value = self.machine.get_video_ram()[row * size.width + col];
+                title += &format!(" - X {col} Y {row} = {value}");
+            }
+        } else {
+            title += " - Terminated";
+        }
+        self.executed_count = 0;
+        self.presented_count = 0;
+        self.title_time = Instant::now();
+        self.is_title_running = is_running;
+        self.is_title_outdated = false;
+        window.set_title(&title).map_err(|err| err.to_string())
+    }
+
+    fn reload_rom(&mut self) {
+        let Some(watcher) = &mut self.rom_watcher else {
+            return;
+        };
+        if watcher.is_changed() {
+            let path = watcher.path().to_path_buf();
+            info!("ROM file changed: {}", path.display());
+            self.load_rom_file(path);
+        }
+    }
+
+    fn reload_config(&mut self) {
+        let Some(watcher) = &mut self.config_watcher else {
+            return;
+        };
+        match watcher.poll() {
+            Some(Ok(config)) => {
+                let config = match &mut self.rom_settings {
+                    Some(settings) => {
+                        settings.set_config(config);
+                        settings.config()
+                    }
+                    None => config,
+                };
+                self.apply_config(config);
+                info!("Config reloaded");
+                self.notify("Config reloaded".to_string());
+            }
+            Some(Err(err)) => {
+                // the last valid settings stay in effect
+                error!("Config error: {err}");
+                self.notify("Config error".to_string());
+            }
+            None => {}
+        }
+    }
+
+    /// Applies changed settings without resetting the machine
+    fn apply_config(&mut self, config: Config) {
+        if let Some(side_by_side) = &mut self.side_by_side {
+            side_by_side.apply_config(&config);
+        }
+        (self.key_mapping, self.scancode_mapping, self.macros) = input_bindings(&config);
+        // window size and renderer are chosen on start
+        self.config = AppearanceConfig {
+            scale: self.config.scale,
+            renderer: self.config.renderer,
+            ..config.appearance
+        };
+        self.machine.set_quirks(config.quirks);
+        self.machine.set_strict_memory(config.debug.strict_memory);
+        self.machine.set_undo_depth(config.debug.undo_depth);
+        self.machine
+            .set_key_release_delay(config.input.key_release_delay);
+        self.is_crash_dump_saved = config.debug.crash_dump;
+        self.is_frame_outdated = true;
+    }
+
+    /// Switches to the preset following the current colors
+    fn cycle_palette(&mut self) {
+        let colors = [self.background_rgb(), self.foreground_rgb()];
+        let next = PALETTES
+            .iter()
+            .position(|palette| palette.colors[..2] == colors)
+            .map_or(0, |index| (index + 1) % PALETTES.len());
+        let palette = &PALETTES[next];
+        self.config.set_palette(palette);
+        self.is_frame_outdated = true;
+        self.notify(palette.name.to_uppercase());
+    }
+
+    fn toggle_mute(&mut self) {
+        if self.notify_no_audio() {
+            return;
+        }
+        self.is_muted = !self.is_muted;
+        let text = if self.is_muted {
+            "SOUND OFF"
+        } else {
+            "SOUND ON"
+        };
+        self.notify(text.to_string());
+    }
+
+    fn change_volume(&mut self, is_increase: bool) {
+        let step = if is_increase {
+            VOLUME_STEP
+        } else {
+            -VOLUME_STEP
+        };
+        if self.notify_no_audio() {
+            return;
+        }
+        self.config.sound_volume = (self.config.sound_volume + step).clamp(0.0, 1.0);
+        self.is_muted = false;
+        self.notify(format!("Volume {:.0}%", self.config.sound_volume * 100.0));
+    }
+
+    /// Sound controls are useless without the output device
+    fn notify_no_audio(&mut self) -> bool {
+        if self.audio_device.is_some() {
+            return false;
+        }
+        self.notify("NO AUDIO".to_string());
+        true
+    }
+
+    /// Shows the message over the display for a short time
+    fn notify(&mut self, message: String) {
+        self.notification = Some((message, Instant::now()));
+    }
+
+    fn draw_notification(&mut self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let Some((message, time)) = &self.notification else {
+            return Ok(());
+        };
+        if time.elapsed() > NOTIFICATION_DURATION {
+            self.notification = None;
+            return Ok(());
+        }
+        let pixel_size = (self.cell_size() / 4).max(1);
+        let width = text::text_width(message, pixel_size) + 2 * pixel_size;
+        let height = text::text_height(pixel_size) + 2 * pixel_size;
+        let (x, y) = (self.display_rect.x(), self.display_rect.y());
+        let [r, g, b] = self.background_rgb();
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        canvas.fill_rect(Rect::new(x, y, width as u32, height as u32))?;
+        let [r, g, b] = self.foreground_rgb();
+        text::draw_text(
+            canvas,
+            message,
+            x + pixel_size as i32,
+            y + pixel_size as i32,
+            pixel_size,
+            Color::RGB(r, g, b),
+        )
+    }
+
+    /// Strip along the display bottom with the sound timer value and the recent audio output,
+    /// the waveform is flat without audio device
+    fn draw_audio_scope(&mut self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        if !self.config.is_audio_scope {
+            return Ok(());
+        }
+        let pixel_size = (self.cell_size() / 4).max(1);
+        let height = text::text_height(pixel_size) * 3;
+        let area = self.display_rect;
+        let strip = Rect::new(
+            area.x(),
+            area.bottom() - height as i32,
+            area.width(),
+            height as u32,
+        );
+        let [r, g, b] = self.background_rgb();
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        canvas.fill_rect(strip)?;
+        let (_, sound_timer) = self.machine.timers();
+        let label = format!("ST {sound_timer:02X}");
+        let [r, g, b] = self.foreground_rgb();
+        let foreground = Color::RGB(r, g, b);
+        text::draw_text(
+            canvas,
+            &label,
+            strip.x() + pixel_size as i32,
+            strip.y() + text::text_height(pixel_size) as i32,
+            pixel_size,
+            foreground,
+        )?;
+        let scope = self
+            .audio_device
+            .as_mut()
+            .map(|device| device.lock().scope())
+            .unwrap_or_default();
+        let left = strip.x() + (text::text_width(&label, pixel_size) + 2 * pixel_size) as i32;
+        let width = (strip.right() - left - pixel_size as i32).max(1);
+        let center = strip.center().y();
+        let amplitude = (height / 2 - pixel_size) as f32;
+        let points = (0..width)
+            .map(|x| {
+                let value = scope
+                    .get(x as usize * scope.len() / width as usize)
+                    .copied()
+                    .unwrap_or_default();
+                Point::new(left + x, center - (value * amplitude) as i32)
+            })
+            .collect::<Vec<_>>();
+        canvas.set_draw_color(foreground);
+        canvas.draw_lines(points.as_slice())
+    }
+
+    fn take_screenshot(&self) {
+        if self.is_color_mode() {
+            warn!("Screenshots aren't supported in Mega-CHIP mode");
+            return;
+        }
+        let file_name = config::timestamped_file_name("png");
+        let result = screenshot::save_png(
+            &self.machine.framebuffer(),
+            self.config.scale,
+            &self.config.plane_colors(),
+            &file_name,
+        );
+        match result {
+            Ok(_) => info!("Screenshot saved to {file_name}"),
+            Err(err) => error!("Failed to save screenshot: {err}"),
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            info!("Recording started");
+            self.recorder = Some(Recorder::new());
+            return;
+        };
+        let file_name = config::timestamped_file_name("gif");
+        let result = recorder.save_gif(
+            self.config.scale,
+            self.foreground_rgb(),
+            self.background_rgb(),
+            &file_name,
         );
-        let fg_color = Color::RGB(
+        match result {
+            Ok(_) => info!(
+                "Recording of {} frames saved to {file_name}",
+                recorder.frames_count()
+            ),
+            Err(err) => error!("Failed to save recording: {err}"),
+        }
+    }
+
+    /// True if the display shows Mega-CHIP color frame instead of video memory
+    fn is_color_mode(&self) -> bool {
+        #[cfg(feature = "megachip")]
+        if self.machine.color_frame().is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Opens the configured output device or the default one if it fails,
+    /// None means the emulator runs without sound
+    fn open_audio(&self) -> Option<AudioDevice<Buzzer>> {
+        let audio_subsystem = self.audio_subsystem.as_ref()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(self.audio_config.sample_rate),
+            channels: Some(1), // mono
+            samples: self.audio_config.buffer_size,
+        };
+        let open = |device: Option<&str>| {
+            audio_subsystem.open_playback(device, &desired_spec, |spec| {
+                Buzzer::new(&self.audio_config, self.config.sound_volume, spec.freq)
+            })
+        };
+        let device = self.audio_config.device.as_deref();
+        let result = match open(device) {
+            Err(err) if device.is_some() => {
+                let available = (0..audio_subsystem.num_audio_playback_devices().unwrap_or(0))
+                    .filter_map(|index| audio_subsystem.audio_playback_device_name(index).ok())
+                    .collect::<Vec<_>>();
+                warn!(
+                    "Failed to open audio device '{}': {err}, available: {}",
+                    device.unwrap_or_default(),
+                    available.join(", ")
+                );
+                open(None)
+            }
+            result => result,
+        };
+        result
+            .map_err(|err| warn!("Failed to open audio, continue without sound: {err}"))
+            .ok()
+    }
+
+    /// Loop interval while the window is minimized
+    fn background_interval(&self) -> Option<Duration> {
+        let frame_rate = self.config.background_frame_rate;
+        if !self.is_minimized || frame_rate == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(1_000_000_000 / frame_rate as u64))
+    }
+
+    /// Minimal duration of the rendered frame, None if rendering isn't limited
+    fn render_interval(&self) -> Option<Duration> {
+        let frame_rate = self.config.frame_rate;
+        if self.is_turbo || frame_rate == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(1_000_000_000 / frame_rate as u64))
+    }
+
+    fn foreground_rgb(&self) -> screenshot::Rgb8 {
+        [
             self.config.foreground_red,
             self.config.foreground_green,
             self.config.foreground_blue,
-        );
-        for r in 0..chip8::DISPLAY_SIZE.height {
-            for c in 0..chip8::DISPLAY_SIZE.width {
-                let idx = r * chip8::DISPLAY_SIZE.width + c;
-                let color = if memory[idx] > 0 { fg_color } else { bg_color };
-                canvas.set_draw_color(color);
-                let rect = Rect::new(
-                    (c * size) as i32,
-                    (r * size) as i32,
-                    size as u32,
-                    size as u32,
-                );
-                canvas.fill_rect(rect)?;
-                if self.config.is_pixel_style {
-                    canvas.set_draw_color(bg_color);
-                    canvas.draw_rect(rect)?;
-                }
+        ]
+    }
+
+    fn background_rgb(&self) -> screenshot::Rgb8 {
+        [
+            self.config.background_red,
+            self.config.background_green,
+            self.config.background_blue,
+        ]
+    }
+
+    fn draw_display(&mut self, renderer: &mut dyn RendererBackend) -> Result<(), String> {
+        // pixels are updated only when the machine changed video memory
+        if self.machine.poll_display_changed() || self.is_frame_outdated {
+            self.update_frame();
+            self.is_frame_outdated = false;
+        }
+        let size = self.screen_size();
+        renderer.draw_display(&self.frame, size, self.display_rect)?;
+        let Some(canvas) = renderer.canvas() else {
+            return Ok(());
+        };
+        if self.config.is_scanline_style {
+            self.draw_scanlines(canvas)?;
+        }
+        // grid is useless when pixels are too small
+        if !self.config.is_pixel_style || self.cell_size() < 3 {
+            return Ok(());
+        }
+        let rect = self.display_rect;
+        let (width, height) = (size.width as u32, size.height as u32);
+        let mut grid = Vec::with_capacity(2 * (width + height) as usize);
+        for c in 0..width {
+            let x = rect.x() + (c * rect.width() / width) as i32;
+            let next = rect.x() + ((c + 1) * rect.width() / width) as i32;
+            grid.push(Rect::new(x, rect.y(), 1, rect.height()));
+            grid.push(Rect::new(next - 1, rect.y(), 1, rect.height()));
+        }
+        for r in 0..height {
+            let y = rect.y() + (r * rect.height() / height) as i32;
+            let next = rect.y() + ((r + 1) * rect.height() / height) as i32;
+            grid.push(Rect::new(rect.x(), y, rect.width(), 1));
+            grid.push(Rect::new(rect.x(), next - 1, rect.width(), 1));
+        }
+        let [r, g, b] = self.background_rgb();
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        canvas.fill_rects(&grid)
+    }
+
+    /// Size of the single chip8 pixel on the screen
+    fn cell_size(&self) -> usize {
+        self.display_rect.width() as usize / self.screen_size().width
+    }
+
+    /// Size of the drawn pixels, the compared display is placed next to the main one
+    fn screen_size(&self) -> USize {
+        let size = self.machine.display_size();
+        match &self.side_by_side {
+            Some(side_by_side) => side_by_side.screen_size(size),
+            None => size,
+        }
+    }
+
+    /// Converts video memory to RGB24 pixels
+    fn update_frame(&mut self) {
+        self.update_machine_frame();
+        if let Some(side_by_side) = &self.side_by_side {
+            self.frame = side_by_side.compose(
+                &self.frame,
+                self.machine.display_size(),
+                self.machine.get_video_ram(),
+                self.foreground_rgb(),
+                self.background_rgb(),
+            );
+        }
+    }
+
+    /// Pixels of the main machine display
+    fn update_machine_frame(&mut self) {
+        self.frame.clear();
+        #[cfg(feature = "megachip")]
+        if let Some(frame) = self.machine.color_frame() {
+            for pixel in frame {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                self.frame.extend_from_slice(&[r, g, b]);
             }
+            return;
         }
-        Ok(())
+        let memory = self.machine.get_video_ram();
+        let fg_color = self.foreground_rgb();
+        let bg_color = self.background_rgb();
+        // pixel bits are the planes it is drawn on
+        let plane_colors = self.config.plane_colors();
+        let is_intensity = self.is_intensity_enabled();
+        for (idx, pixel) in memory.iter().enumerate() {
+            let color = if is_intensity {
+                blend_rgb(bg_color, fg_color, self.intensity[idx])
+            } else {
+                plane_colors[(*pixel & 0x3) as usize]
+            };
+            self.frame.extend_from_slice(&color);
+        }
+    }
+
+    /// True if pixel brightness isn't just on/off
+    fn is_intensity_enabled(&self) -> bool {
+        self.config.phosphor_decay > 0.0 || self.config.frame_blending > 1
+    }
+
+    /// Calculates brightness of pixels applying frame blending and phosphor filters
+    fn update_intensity(&mut self) {
+        if !self.is_intensity_enabled() {
+            return;
+        }
+        let memory = self.machine.get_video_ram();
+        if self.intensity.len() != memory.len() {
+            self.intensity = vec![0.0; memory.len()];
+            self.frame_history.clear();
+        }
+        let blend_count = self.config.frame_blending.clamp(1, MAX_FRAME_BLENDING);
+        if self.frame_history.len() == blend_count {
+            self.frame_history.pop_front();
+        }
+        self.frame_history.push_back(memory.to_vec());
+        let frames_count = self.frame_history.len() as f32;
+        let decay = self.config.phosphor_decay.clamp(0.0, MAX_PHOSPHOR_DECAY);
+        let mut is_changed = false;
+        for (idx, value) in self.intensity.iter_mut().enumerate() {
+            let lit_count = self
+                .frame_history
+                .iter()
+                .filter(|frame| frame[idx] > 0)
+                .count();
+            let base = lit_count as f32 / frames_count;
+            // lit pixels get the base intensity, others fade out a bit every frame
+            let faded = if *value < MIN_PHOSPHOR_INTENSITY {
+                0.0
+            } else {
+                *value * decay
+            };
+            let next = base.max(faded);
+            is_changed |= next != *value;
+            *value = next;
+        }
+        self.is_frame_outdated |= is_changed;
+    }
+
+    fn draw_scanlines(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let rect = self.display_rect;
+        let lines = (0..rect.height())
+            .step_by(2)
+            .map(|y| Rect::new(rect.x(), rect.y() + y as i32 + 1, rect.width(), 1))
+            .collect::<Vec<_>>();
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, SCANLINE_ALPHA));
+        let result = canvas.fill_rects(&lines);
+        canvas.set_blend_mode(BlendMode::None);
+        result
     }
 }
 
-// https://docs.rs/sdl2/latest/sdl2/audio/index.html
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+fn log_machine_state(machine: &Chip8, symbols: &Symbols) {
+    let opcode = machine
+        .current_opcode()
+        .map(|opcode| format!("{opcode:04X}"))
+        .unwrap_or("----".to_string());
+    let (dt, st) = machine.timers();
+    let location = if symbols.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", symbols.describe(machine.pc()))
+    };
+    info!(
+        "PC: {:03X}{location}, opcode: {opcode}, I: {:03X}, DT: {dt}, ST: {st}",
+        machine.pc(),
+        machine.index()
+    );
+    let registers = machine
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("V{i:X}={value:02X}"))
+        .collect::<Vec<_>>();
+    info!("{}", registers.join(" "));
+    let stack = machine
+        .stack()
+        .iter()
+        .map(|address| symbols.describe(*address as usize))
+        .collect::<Vec<_>>();
+    info!("Stack: [{}]", stack.join(", "));
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
+/// Keypad keys, keymap bindings and macro hotkeys of the settings
+fn input_bindings(
+    config: &Config,
+) -> (
+    HashMap<Keycode, u8>,
+    HashMap<Scancode, u8>,
+    HashMap<Keycode, InputMacro>,
+) {
+    let (mut key_mapping, mut scancode_mapping) = (HashMap::new(), HashMap::new());
+    for (name, code) in KEYPAD_KEYS {
+        match config.input.key_mapping {
+            KeyMapping::Scancode => {
+                scancode_mapping.extend(Scancode::from_name(name).map(|key| (key, code)))
+            }
+            KeyMapping::Keycode => {
+                key_mapping.extend(Keycode::from_name(name).map(|key| (key, code)))
+            }
+        }
+    }
+    for (name, code) in &config.keymap {
+        match Keycode::from_name(name) {
+            Some(keycode) if *code < 0x10 => {
+                key_mapping.insert(keycode, *code);
+            }
+            _ => warn!("Ignored invalid key binding {name} = {code}"),
+        }
+    }
+    let mut macros = HashMap::new();
+    for config in &config.input.macros {
+        let Some(hotkey) = Keycode::from_name(&config.hotkey) else {
+            warn!("Ignored macro with invalid hotkey {}", config.hotkey);
+            continue;
+        };
+        match InputMacro::parse(config) {
+            Ok(input_macro) => {
+                macros.insert(hotkey, input_macro);
+            }
+            Err(err) => warn!("Ignored macro {}: {err}", config.hotkey),
+        }
+    }
+    (key_mapping, scancode_mapping, macros)
+}
 
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+/// Icon sprite scaled up, the pixels outside of the screen frame are transparent
+fn window_icon(
+    foreground: screenshot::Rgb8,
+    background: screenshot::Rgb8,
+) -> Result<Surface<'static>, String> {
+    let scale = ICON_SCALE as usize;
+    let size = ICON_SPRITE.len() * scale;
+    let mut surface = Surface::new(size as u32, size as u32, PixelFormatEnum::RGBA32)?;
+    let pitch = surface.pitch() as usize;
+    surface.with_lock_mut(|pixels| {
+        for y in 0..size {
+            let (row, bits) = (y / scale, ICON_SPRITE[y / scale]);
+            for x in 0..size {
+                let column = x / scale;
+                let is_set = bits & (0x8000 >> column) > 0;
+                let is_corner = (row == 0 || row == 13) && (column == 0 || column == 15);
+                let is_outside = !is_set && (row > 13 || is_corner);
+                let [r, g, b] = if is_set { foreground } else { background };
+                let alpha = if is_outside { 0 } else { 0xff };
+                let offset = y * pitch + x * 4;
+                pixels[offset..offset + 4].copy_from_slice(&[r, g, b, alpha]);
+            }
+        }
+    });
+    Ok(surface)
+}
+
+/// Linear interpolation between two colors
+fn blend_rgb(from: screenshot::Rgb8, to: screenshot::Rgb8, ratio: f32) -> screenshot::Rgb8 {
+    let mut result = from;
+    for (i, val) in result.iter_mut().enumerate() {
+        *val = (from[i] as f32 + (to[i] as f32 - from[i] as f32) * ratio) as u8;
+    }
+    result
+}
+
+/// Largest area of the display proportions centered in the window,
+/// integer scaling leaves wider borders but keeps all pixels of the same size
+fn display_rect(width: u32, height: u32, size: &USize, is_integer: bool) -> Rect {
+    let (w, h) = (size.width as u32, size.height as u32);
+    let mut scale = (width as f32 / w as f32).min(height as f32 / h as f32);
+    if is_integer && scale >= 1.0 {
+        scale = scale.floor();
+    }
+    let (w, h) = ((w as f32 * scale) as u32, (h as f32 * scale) as u32);
+    Rect::new(
+        (width.saturating_sub(w) / 2) as i32,
+        (height.saturating_sub(h) / 2) as i32,
+        w.max(1),
+        h.max(1),
+    )
+}
+
+/// Emulated clock ticks on every frame, realtime one while the due ticks remain
+fn take_timer_tick(ticks: &mut Option<u32>) -> bool {
+    match ticks {
+        None => true,
+        Some(0) => false,
+        Some(count) => {
+            *count -= 1;
+            true
         }
     }
 }
@@ -0,0 +1,125 @@
+pub const SMALL_FONT_SIZE: usize = 5 * 16;
+
+/// Hexadecimal digits 4x5 sprites used by FX29
+pub type SmallFont = [u8; SMALL_FONT_SIZE];
+
+///
+/// Digits sprites of the original interpreters, programs may look differently
+/// with the font they were designed on
+///
+pub struct Font {
+    pub name: &'static str,
+    pub sprites: SmallFont,
+}
+
+/// The first one is used by default
+pub const FONTS: [Font; 4] = [
+    Font {
+        name: "schip", // also used by CHIP-48 and most modern interpreters
+        sprites: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x20, 0x60, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+    },
+    Font {
+        name: "vip", // COSMAC VIP interpreter
+        sprites: [
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+            0x60, 0x20, 0x20, 0x20, 0x70, // 1
+            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+            0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+            0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+            0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+            0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+        ],
+    },
+    Font {
+        name: "dream6800", // CHIPOS of Dream 6800, 3 pixels wide
+        sprites: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x40, 0x40, 0x40, 0x40, 0x40, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+    },
+    Font {
+        name: "eti660", // ETI-660 interpreter, 3 pixels wide
+        sprites: [
+            0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+            0x20, 0x20, 0x20, 0x20, 0x20, // 1
+            0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+            0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+            0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+            0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+            0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+            0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+            0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+            0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+            0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+            0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+            0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+            0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+            0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+            0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+        ],
+    },
+];
+
+pub const BIG_FONT_SIZE: usize = 10 * 10;
+
+/// SCHIP 8x10 decimal digits sprites used by FX30
+pub const BIG_FONT: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+impl Font {
+    pub fn find(name: &str) -> Option<&'static Font> {
+        FONTS
+            .iter()
+            .find(|font| font.name.eq_ignore_ascii_case(name))
+    }
+}
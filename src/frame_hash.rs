@@ -0,0 +1,111 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use tracing::error;
+
+use crate::rom_db;
+
+///
+/// Display hashes used to compare the emulation with reference emulators.
+/// File format is plain text: `<number> <hash>` lines, where number is the frame
+/// or the executed instructions count and hash is SHA-1 of the display pixels
+/// row by row, one byte per pixel: 1 for lit and 0 for dark
+///
+pub struct FrameHashWriter {
+    writer: BufWriter<File>,
+    interval: Option<u64>, // instructions between hashes, every frame if not set
+    frames_count: u64,
+}
+
+impl FrameHashWriter {
+    pub fn with_file<P: AsRef<Path>>(path: P, interval: Option<u64>) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            interval,
+            frames_count: 0,
+        })
+    }
+
+    /// Should be called on every display refresh (60 Hz)
+    pub fn on_frame(&mut self, video_ram: &[u8]) {
+        if self.interval.is_some() {
+            return;
+        }
+        self.frames_count += 1;
+        self.write(self.frames_count, video_ram);
+    }
+
+    /// Should be called after every executed instruction, `cycles` is the executed count
+    pub fn on_instruction(&mut self, cycles: u64, video_ram: &[u8]) {
+        match self.interval {
+            Some(interval) if cycles % interval == 0 => self.write(cycles, video_ram),
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, number: u64, video_ram: &[u8]) {
+        let hash = display_hash(video_ram);
+        if let Err(err) = writeln!(self.writer, "{number} {hash}") {
+            error!("Failed to write frame hash: {err}");
+        }
+    }
+}
+
+impl Drop for FrameHashWriter {
+    fn drop(&mut self) {
+        _ = self.writer.flush();
+    }
+}
+
+/// SHA-1 of the display pixels, planes aren't distinguished
+pub fn display_hash(video_ram: &[u8]) -> String {
+    let pixels = video_ram
+        .iter()
+        .map(|pixel| (*pixel > 0) as u8)
+        .collect::<Vec<_>>();
+    rom_db::sha1_hex(&pixels)
+}
+
+/// Result of two hash files comparison, lines are compared in order
+pub enum Comparison {
+    Equal(usize), // number of compared hashes
+    Diverged {
+        line: usize,
+        left: Option<(u64, String)>, // None if the file is shorter
+        right: Option<(u64, String)>,
+    },
+}
+
+pub fn compare<P: AsRef<Path>>(left: P, right: P) -> Result<Comparison, String> {
+    let left = read_hashes(left)?;
+    let right = read_hashes(right)?;
+    let count = left.len().max(right.len());
+    for line in 0..count {
+        let (a, b) = (left.get(line), right.get(line));
+        if a.map(|(_, hash)| hash) != b.map(|(_, hash)| hash) {
+            return Ok(Comparison::Diverged {
+                line: line + 1,
+                left: a.cloned(),
+                right: b.cloned(),
+            });
+        }
+    }
+    Ok(Comparison::Equal(count))
+}
+
+/// Hashes are compared case insensitive, blank lines are skipped
+fn read_hashes<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, String)>, String> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let invalid = || format!("{}: malformed frame hash: {line}", path.display());
+            let (number, hash) = line.trim().split_once(' ').ok_or_else(invalid)?;
+            let number = number.parse::<u64>().map_err(|_| invalid())?;
+            Ok((number, hash.trim().to_lowercase()))
+        })
+        .collect()
+}
@@ -0,0 +1,59 @@
+use alloc::vec::Vec;
+
+use crate::common::USize;
+
+///
+/// Read-only view of the display, pixels are stored row by row one byte each,
+/// bits of a pixel are the planes it is drawn on
+///
+pub struct Framebuffer<'a> {
+    pixels: &'a [u8],
+    size: USize,
+}
+
+impl<'a> Framebuffer<'a> {
+    pub fn new(pixels: &'a [u8], size: USize) -> Self {
+        Self { pixels, size }
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.height
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.pixels.chunks(self.size.width.max(1))
+    }
+
+    /// One bit per pixel lit on any plane, most significant bit first,
+    /// each row is padded to whole bytes
+    pub fn to_packed(&self) -> Vec<u8> {
+        let row_bytes = self.width().div_ceil(8);
+        let mut packed = Vec::with_capacity(row_bytes * self.height());
+        for row in self.rows() {
+            for chunk in row.chunks(8) {
+                let byte = chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pixel)| **pixel > 0)
+                    .fold(0u8, |acc, (bit, _)| acc | 0x80 >> bit);
+                packed.push(byte);
+            }
+        }
+        packed
+    }
+
+    /// Four bytes per pixel, the palette holds the background, the first plane,
+    /// the second plane and the both planes colors
+    pub fn to_rgba(&self, palette: &[[u8; 3]; 4]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in self.pixels {
+            let [r, g, b] = palette[(*pixel & 0x3) as usize];
+            rgba.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+        rgba
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_derive::Deserialize;
+
+use crate::chip8::{Chip8, RunBudget, RunStatus};
+use crate::config::{Config, QuirksConfig};
+use crate::frame_hash;
+use crate::rom_loader;
+use crate::screenshot;
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Deserialize)]
+struct SpecsFile {
+    #[serde(default, rename = "test")]
+    tests: Vec<TestSpec>,
+}
+
+///
+/// Visual regression test from a specs file with `[[test]]` entries, e.g.
+/// `rom = "pong.ch8"`, `frames = 300`, `hash = "<SHA-1>"`, `png = "golden/pong.png"`.
+/// Paths are relative to the specs file, missing golden images are created
+///
+#[derive(Deserialize)]
+struct TestSpec {
+    rom: String,
+    name: Option<String>, // ROM path by default
+    frames: u64,
+    operations_per_second: Option<u64>, // config value by default
+    hash: Option<String>,               // display hash as written by --frame-hashes
+    png: Option<String>,
+    #[serde(default)]
+    quirks: QuirksConfig,
+}
+
+/// Runs the ROMs of the specs file without display, returns true if all of them passed
+pub fn run<P: AsRef<Path>>(path: P, config: &Config) -> Result<bool, String> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    let specs: SpecsFile =
+        toml::from_str(&content).map_err(|err| format!("{}: {err}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut failed_count = 0;
+    for spec in &specs.tests {
+        let name = spec.name.as_deref().unwrap_or(&spec.rom);
+        match run_test(spec, dir, config) {
+            Ok(notes) if notes.is_empty() => println!("{name}: ok"),
+            Ok(notes) => println!("{name}: ok, {}", notes.join(", ")),
+            Err(err) => {
+                println!("{name}: FAILED, {err}");
+                failed_count += 1;
+            }
+        }
+    }
+    println!(
+        "{} passed, {failed_count} failed",
+        specs.tests.len() - failed_count
+    );
+    Ok(failed_count == 0)
+}
+
+/// Notes of the passed test, e.g. created golden files
+fn run_test(spec: &TestSpec, dir: &Path, config: &Config) -> Result<Vec<String>, String> {
+    let rom = rom_loader::load(dir.join(&spec.rom))?;
+    let font = config.appearance.load_font()?;
+    let mut machine = Chip8::builder(rom.data)
+        .quirks(spec.quirks.apply(config.quirks))
+        .font(&font)
+        .seed(0)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let operations_per_second = spec
+        .operations_per_second
+        .unwrap_or(config.appearance.operations_per_second);
+    for frame in 0..spec.frames {
+        let status = machine
+            .run_for(RunBudget::Time {
+                duration: FRAME_DURATION,
+                operations_per_second,
+            })
+            .map_err(|err| format!("{err} on frame {frame}"))?;
+        if status == RunStatus::Stopped {
+            return Err(format!("machine stopped on frame {frame}"));
+        }
+    }
+    let mut notes = Vec::new();
+    if let Some(expected) = &spec.hash {
+        let hash = frame_hash::display_hash(machine.get_video_ram());
+        if !hash.eq_ignore_ascii_case(expected) {
+            return Err(format!("display hash {hash}"));
+        }
+    }
+    if let Some(png) = &spec.png {
+        let path = dir.join(png);
+        let palette = config.appearance.plane_colors();
+        if !path.exists() {
+            let scale = config.appearance.scale;
+            screenshot::save_png(&machine.framebuffer(), scale, &palette, &path)?;
+            notes.push(format!("created {}", path.display()));
+        } else if !screenshot::matches_png(&machine.framebuffer(), &palette, &path)? {
+            return Err(format!("display differs from {}", path.display()));
+        }
+    }
+    Ok(notes)
+}
@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use egui::epaint::{ImageData, Primitive, Vertex};
+use egui::{Color32, Context, Modifiers, Pos2, RawInput, Rect, TextureId, Vec2};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use tracing::error;
+
+use crate::chip8::{Chip8, Severity, State};
+use crate::config::{AppearanceConfig, Timing, PALETTES};
+use crate::symbols::Symbols;
+use crate::watch_list::WatchList;
+
+const MEMORY_ROWS: usize = 16;
+const MEMORY_COLUMNS: usize = 16;
+const TILE_ROWS: usize = 8;
+const TILE_COLUMNS: usize = 8;
+const TILE_PIXEL_SIZE: f32 = 3.0;
+const TILE_SPACING: f32 = 4.0;
+
+struct GuiTexture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl GuiTexture {
+    /// Nearest neighbor sampling
+    fn sample(&self, uv: Pos2) -> Color32 {
+        let [width, height] = self.size;
+        let x = ((uv.x * width as f32) as usize).min(width - 1);
+        let y = ((uv.y * height as f32) as usize).min(height - 1);
+        self.pixels[y * width + x]
+    }
+}
+
+///
+/// Settings and debugging windows drawn with egui into a software frame buffer
+///
+pub struct DebugGui {
+    context: Context,
+    textures: HashMap<TextureId, GuiTexture>,
+    events: Vec<egui::Event>,
+    modifiers: Modifiers,
+    pointer: Pos2,
+    pixel_density: f32, // drawable pixels per window point on high-DPI displays
+    started_at: Instant,
+    width: usize,
+    height: usize,
+    pixels: Vec<Color32>,   // premultiplied RGBA frame
+    memory_address: String, // hex address of the first memory view row
+    is_following_pc: bool,
+    tiles_address: String, // hex address of the first sprite in the tiles view
+    tile_height: usize,
+    watch_input: String,         // expression to be added to the watch list
+    watch_error: Option<String>, // parse error of the entered expression
+    symbols: Symbols,
+}
+
+impl DebugGui {
+    pub fn new() -> Self {
+        Self {
+            context: Context::default(),
+            textures: HashMap::new(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            pointer: Pos2::ZERO,
+            pixel_density: 1.0,
+            started_at: Instant::now(),
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            memory_address: "200".to_string(),
+            is_following_pc: true,
+            tiles_address: "200".to_string(),
+            tile_height: 8,
+            watch_input: String::new(),
+            watch_error: None,
+            symbols: Symbols::default(),
+        }
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Mouse events come in window points while the GUI is drawn in pixels
+    pub fn set_pixel_density(&mut self, pixel_density: f32) {
+        self.pixel_density = pixel_density;
+    }
+
+    /// Returns true if the event is consumed by the GUI
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::MouseMotion { x, y, .. } => {
+                self.pointer = Pos2::new(*x as f32, *y as f32) * self.pixel_density;
+                self.events.push(egui::Event::PointerMoved(self.pointer));
+                false
+            }
+            Event::MouseButtonDown { mouse_btn, .. } | Event::MouseButtonUp { mouse_btn, .. } => {
+                let Some(button) = pointer_button(*mouse_btn) else {
+                    return false;
+                };
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: matches!(event, Event::MouseButtonDown { .. }),
+                    modifiers: self.modifiers,
+                });
+                self.context.wants_pointer_input()
+            }
+            Event::MouseWheel { x, y, .. } => {
+                let delta = Vec2::new(*x as f32, *y as f32) * 20.0;
+                self.events.push(egui::Event::Scroll(delta));
+                self.context.wants_pointer_input()
+            }
+            Event::TextInput { text, .. } => {
+                if !self.context.wants_keyboard_input() {
+                    return false;
+                }
+                self.events.push(egui::Event::Text(text.clone()));
+                true
+            }
+            Event::KeyDown {
+                keycode, keymod, ..
+            }
+            | Event::KeyUp {
+                keycode, keymod, ..
+            } => {
+                self.modifiers = modifiers(*keymod);
+                if !self.context.wants_keyboard_input() {
+                    return false;
+                }
+                if let Some(key) = keycode.and_then(key) {
+                    self.events.push(egui::Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed: matches!(event, Event::KeyDown { .. }),
+                        repeat: false,
+                        modifiers: self.modifiers,
+                    });
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs the windows and draws them, returns true if display settings were changed
+    pub fn update(
+        &mut self,
+        width: u32,
+        height: u32,
+        machine: &mut Chip8,
+        config: &mut AppearanceConfig,
+        watch_list: &mut WatchList,
+    ) -> bool {
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                Vec2::new(width as f32, height as f32),
+            )),
+            time: Some(self.started_at.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let mut is_changed = false;
+        let memory_address = &mut self.memory_address;
+        let is_following_pc = &mut self.is_following_pc;
+        let tiles_address = &mut self.tiles_address;
+        let tile_height = &mut self.tile_height;
+        let symbols = &self.symbols;
+        let watch_input = &mut self.watch_input;
+        let watch_error = &mut self.watch_error;
+        let output = self.context.run(raw_input, |ctx| {
+            is_changed |= settings_window(ctx, config);
+            quirks_window(ctx, machine);
+            registers_window(ctx, machine, symbols);
+            call_stack_window(ctx, machine, symbols);
+            memory_window(ctx, machine, memory_address, is_following_pc);
+            tiles_window(ctx, machine, config, tiles_address, tile_height);
+            watch_window(ctx, machine, watch_list, watch_input, watch_error);
+        });
+        for (id, delta) in output.textures_delta.set {
+            let (size, pixels) = match &delta.image {
+                ImageData::Color(image) => (image.size, image.pixels.clone()),
+                ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+            };
+            match delta.pos {
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(&id) else {
+                        continue;
+                    };
+                    for row in 0..size[1] {
+                        let start = (y + row) * texture.size[0] + x;
+                        texture.pixels[start..start + size[0]]
+                            .copy_from_slice(&pixels[row * size[0]..(row + 1) * size[0]]);
+                    }
+                }
+                None => {
+                    self.textures.insert(id, GuiTexture { size, pixels });
+                }
+            }
+        }
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        self.width = width as usize;
+        self.height = height as usize;
+        self.pixels.clear();
+        self.pixels
+            .resize(self.width * self.height, Color32::TRANSPARENT);
+        for primitive in primitives {
+            if let Primitive::Mesh(mesh) = &primitive.primitive {
+                self.draw_mesh(
+                    &mesh.vertices,
+                    &mesh.indices,
+                    mesh.texture_id,
+                    primitive.clip_rect,
+                );
+            }
+        }
+        for id in output.textures_delta.free {
+            self.textures.remove(&id);
+        }
+        is_changed
+    }
+
+    /// Writes the frame as RGBA bytes with straight alpha
+    pub fn write_rgba(&self, buffer: &mut [u8], pitch: usize) {
+        for (idx, pixel) in self.pixels.iter().enumerate() {
+            let offset = idx / self.width * pitch + idx % self.width * 4;
+            let color = pixel.to_srgba_unmultiplied();
+            buffer[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+
+    fn draw_mesh(&mut self, vertices: &[Vertex], indices: &[u32], id: TextureId, clip: Rect) {
+        let Some(texture) = self.textures.get(&id) else {
+            return;
+        };
+        let clip = clip.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(self.width as f32, self.height as f32),
+        ));
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| &vertices[i as usize]);
+            let area = edge(a.pos, b.pos, c.pos);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+            let min_x = a.pos.x.min(b.pos.x).min(c.pos.x).max(clip.min.x) as usize;
+            let max_x = a.pos.x.max(b.pos.x).max(c.pos.x).min(clip.max.x).ceil() as usize;
+            let min_y = a.pos.y.min(b.pos.y).min(c.pos.y).max(clip.min.y) as usize;
+            let max_y = a.pos.y.max(b.pos.y).max(c.pos.y).min(clip.max.y).ceil() as usize;
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let point = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let wa = edge(b.pos, c.pos, point) / area;
+                    let wb = edge(c.pos, a.pos, point) / area;
+                    let wc = 1.0 - wa - wb;
+                    if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                        continue;
+                    }
+                    let uv = Pos2::new(
+                        a.uv.x * wa + b.uv.x * wb + c.uv.x * wc,
+                        a.uv.y * wa + b.uv.y * wb + c.uv.y * wc,
+                    );
+                    let texel = texture.sample(uv);
+                    let mut src = [0u8; 4];
+                    for (i, value) in src.iter_mut().enumerate() {
+                        let tint = a.color[i] as f32 * wa
+                            + b.color[i] as f32 * wb
+                            + c.color[i] as f32 * wc;
+                        *value = (tint * texel[i] as f32 / 255.0) as u8;
+                    }
+                    let dst = &mut self.pixels[y * self.width + x];
+                    *dst = blend_premultiplied(*dst, src);
+                }
+            }
+        }
+    }
+}
+
+fn edge(a: Pos2, b: Pos2, point: Pos2) -> f32 {
+    (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+}
+
+fn blend_premultiplied(dst: Color32, src: [u8; 4]) -> Color32 {
+    let inverse = 255 - src[3] as u32;
+    let channel = |i: usize| (src[i] as u32 + dst[i] as u32 * inverse / 255).min(255) as u8;
+    Color32::from_rgba_premultiplied(channel(0), channel(1), channel(2), channel(3))
+}
+
+fn pointer_button(button: MouseButton) -> Option<egui::PointerButton> {
+    match button {
+        MouseButton::Left => Some(egui::PointerButton::Primary),
+        MouseButton::Right => Some(egui::PointerButton::Secondary),
+        MouseButton::Middle => Some(egui::PointerButton::Middle),
+        _ => None,
+    }
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: false,
+        command: ctrl,
+    }
+}
+
+/// Keys used by text fields
+fn key(keycode: Keycode) -> Option<egui::Key> {
+    let key = match keycode {
+        Keycode::Backspace => egui::Key::Backspace,
+        Keycode::Delete => egui::Key::Delete,
+        Keycode::Return => egui::Key::Enter,
+        Keycode::Tab => egui::Key::Tab,
+        Keycode::Escape => egui::Key::Escape,
+        Keycode::Left => egui::Key::ArrowLeft,
+        Keycode::Right => egui::Key::ArrowRight,
+        Keycode::Up => egui::Key::ArrowUp,
+        Keycode::Down => egui::Key::ArrowDown,
+        Keycode::Home => egui::Key::Home,
+        Keycode::End => egui::Key::End,
+        Keycode::A => egui::Key::A,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn settings_window(ctx: &Context, config: &mut AppearanceConfig) -> bool {
+    let mut is_changed = false;
+    egui::Window::new("Settings").show(ctx, |ui| {
+        ui.add(
+            egui::Slider::new(&mut config.operations_per_second, 60..=100_000)
+                .logarithmic(true)
+                .text("IPS"),
+        );
+        egui::ComboBox::from_label("Timing")
+            .selected_text(match config.timing {
+                Timing::Ips => "IPS",
+                Timing::Cosmac => "COSMAC VIP",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut config.timing, Timing::Ips, "IPS");
+                ui.selectable_value(&mut config.timing, Timing::Cosmac, "COSMAC VIP");
+            });
+        let mut foreground = [
+            config.foreground_red,
+            config.foreground_green,
+            config.foreground_blue,
+        ];
+        let mut background = [
+            config.background_red,
+            config.background_green,
+            config.background_blue,
+        ];
+        egui::ComboBox::from_label("Palette")
+            .selected_text("Preset")
+            .show_ui(ui, |ui| {
+                for palette in &PALETTES {
+                    if ui.selectable_label(false, palette.name).clicked() {
+                        [background, foreground, ..] = palette.colors;
+                        is_changed = true;
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            is_changed |= ui.color_edit_button_srgb(&mut foreground).changed();
+            ui.label("Foreground");
+            is_changed |= ui.color_edit_button_srgb(&mut background).changed();
+            ui.label("Background");
+        });
+        [
+            config.foreground_red,
+            config.foreground_green,
+            config.foreground_blue,
+        ] = foreground;
+        [
+            config.background_red,
+            config.background_green,
+            config.background_blue,
+        ] = background;
+        is_changed |= ui
+            .checkbox(&mut config.is_pixel_style, "Pixel grid")
+            .changed();
+        is_changed |= ui
+            .checkbox(&mut config.is_scanline_style, "Scanlines")
+            .changed();
+        is_changed |= ui
+            .add(egui::Slider::new(&mut config.phosphor_decay, 0.0..=0.95).text("Phosphor decay"))
+            .changed();
+        is_changed |= ui
+            .add(egui::Slider::new(&mut config.frame_blending, 1..=3).text("Frame blending"))
+            .changed();
+    });
+    is_changed
+}
+
+fn quirks_window(ctx: &Context, machine: &mut Chip8) {
+    egui::Window::new("Quirks").show(ctx, |ui| {
+        let mut quirks = machine.quirks();
+        ui.checkbox(&mut quirks.vf_reset, "VF reset");
+        ui.checkbox(&mut quirks.memory, "Memory");
+        ui.checkbox(&mut quirks.shifting, "Shifting");
+        ui.checkbox(&mut quirks.jumping, "Jumping");
+        ui.checkbox(&mut quirks.hires, "Hi-res (on reset)");
+        ui.checkbox(&mut quirks.key_wait_release, "Key wait release");
+        ui.checkbox(&mut quirks.eti660, "ETI-660 layout (on reset)");
+        ui.checkbox(&mut quirks.half_scroll, "Half scroll");
+        machine.set_quirks(quirks);
+    });
+}
+
+fn registers_window(ctx: &Context, machine: &mut Chip8, symbols: &Symbols) {
+    egui::Window::new("Registers").show(ctx, |ui| {
+        egui::Grid::new("registers").show(ui, |ui| {
+            for (i, value) in machine.registers().iter().enumerate() {
+                ui.monospace(format!("V{i:X} {value:02X}"));
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+        let (dt, st) = machine.timers();
+        ui.monospace(format!(
+            "PC {:03X}  I {:03X}  DT {dt:02X}  ST {st:02X}",
+            machine.pc(),
+            machine.index()
+        ));
+        let opcode = machine
+            .current_opcode()
+            .map(|opcode| format!("{opcode:04X}"))
+            .unwrap_or("----".to_string());
+        ui.monospace(format!("Opcode {opcode}  Cycles {}", machine.cycles()));
+        if !symbols.is_empty() {
+            ui.monospace(format!("At {}", symbols.describe(machine.pc())));
+        }
+        let stack = machine
+            .stack()
+            .iter()
+            .map(|address| format!("{address:03X}"))
+            .collect::<Vec<_>>();
+        ui.monospace(format!("Stack [{}]", stack.join(" ")));
+        ui.horizontal(|ui| {
+            let state = machine.get_state();
+            let title = if matches!(state, State::Paused) {
+                "Resume"
+            } else {
+                "Pause"
+            };
+            if ui.button(title).clicked() {
+                machine.toggle_execution();
+            }
+            let is_paused = matches!(state, State::Paused);
+            if ui
+                .add_enabled(
+                    is_paused && machine.undo_count() > 0,
+                    egui::Button::new("Step back"),
+                )
+                .clicked()
+            {
+                machine.step_back();
+            }
+            if ui
+                .add_enabled(is_paused, egui::Button::new("Step"))
+                .clicked()
+            {
+                if let Err(error) = machine.teak() {
+                    error!("Machine error: {error}");
+                    if error.severity() == Severity::Fatal {
+                        machine.terminate();
+                    }
+                }
+            }
+            if ui
+                .add_enabled(is_paused, egui::Button::new("Step over"))
+                .clicked()
+            {
+                if let Err(error) = machine.step_over() {
+                    error!("Machine error: {error}");
+                    if error.severity() == Severity::Fatal {
+                        machine.terminate();
+                    }
+                }
+            }
+            let is_in_subroutine = !machine.stack().is_empty();
+            if ui
+                .add_enabled(is_paused && is_in_subroutine, egui::Button::new("Step out"))
+                .clicked()
+            {
+                machine.step_out();
+            }
+            if ui.button("Reset").clicked() {
+                machine.reset();
+            }
+        });
+    });
+}
+
+fn call_stack_window(ctx: &Context, machine: &Chip8, symbols: &Symbols) {
+    egui::Window::new("Call stack").show(ctx, |ui| {
+        let frames = machine.call_stack();
+        if frames.is_empty() {
+            ui.label("No active calls");
+        }
+        for (depth, frame) in frames.iter().enumerate() {
+            let subroutine = frame
+                .subroutine
+                .map(|address| symbols.label_or_hex(address))
+                .unwrap_or("???".to_string());
+            ui.monospace(format!(
+                "#{depth} {subroutine}  returns to {}",
+                symbols.describe(frame.return_address)
+            ));
+        }
+    });
+}
+
+fn memory_window(ctx: &Context, machine: &Chip8, address: &mut String, is_following_pc: &mut bool) {
+    egui::Window::new("Memory").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.add_enabled(
+                !*is_following_pc,
+                egui::TextEdit::singleline(address).desired_width(60.0),
+            );
+            ui.checkbox(is_following_pc, "Follow PC");
+        });
+        if *is_following_pc {
+            *address = format!("{:03X}", machine.pc());
+        }
+        let memory = machine.memory();
+        let start = usize::from_str_radix(address.trim(), 16).unwrap_or_default() / MEMORY_COLUMNS
+            * MEMORY_COLUMNS;
+        for row in 0..MEMORY_ROWS {
+            let offset = start + row * MEMORY_COLUMNS;
+            let Some(bytes) = memory.get(offset..offset + MEMORY_COLUMNS) else {
+                break;
+            };
+            let line = bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>();
+            ui.monospace(format!("{offset:04X}  {}", line.join(" ")));
+        }
+    });
+}
+
+/// Memory shown as 8xN sprites laid out in a grid, so graphics data is recognizable at a glance
+fn tiles_window(
+    ctx: &Context,
+    machine: &Chip8,
+    config: &AppearanceConfig,
+    address: &mut String,
+    height: &mut usize,
+) {
+    egui::Window::new("Tiles").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.add(egui::TextEdit::singleline(address).desired_width(60.0));
+            ui.add(egui::Slider::new(height, 1..=16).text("Height"));
+        });
+        let [background, foreground, ..] = config
+            .plane_colors()
+            .map(|[r, g, b]| Color32::from_rgb(r, g, b));
+        let memory = machine.memory();
+        let start = usize::from_str_radix(address.trim(), 16).unwrap_or_default();
+        let tile_size = Vec2::new(8.0, *height as f32) * TILE_PIXEL_SIZE;
+        let step = tile_size + Vec2::splat(TILE_SPACING);
+        let (response, painter) = ui.allocate_painter(
+            Vec2::new(TILE_COLUMNS as f32, TILE_ROWS as f32) * step,
+            egui::Sense::hover(),
+        );
+        let origin = response.rect.min;
+        let mut hovered = None;
+        for index in 0..TILE_ROWS * TILE_COLUMNS {
+            let offset = start + index * *height;
+            let Some(bytes) = memory.get(offset..offset + *height) else {
+                break;
+            };
+            let corner = origin
+                + Vec2::new((index % TILE_COLUMNS) as f32, (index / TILE_COLUMNS) as f32) * step;
+            let tile = Rect::from_min_size(corner, tile_size);
+            painter.rect_filled(tile, 0.0, background);
+            for (row, byte) in bytes.iter().enumerate() {
+                for col in (0..8).filter(|col| byte >> (7 - col) & 1 > 0) {
+                    let pixel = corner + Vec2::new(col as f32, row as f32) * TILE_PIXEL_SIZE;
+                    let rect = Rect::from_min_size(pixel, Vec2::splat(TILE_PIXEL_SIZE));
+                    painter.rect_filled(rect, 0.0, foreground);
+                }
+            }
+            if response
+                .hover_pos()
+                .is_some_and(|position| tile.contains(position))
+            {
+                hovered = Some(offset);
+            }
+        }
+        if let Some(offset) = hovered {
+            response.on_hover_text(format!("{offset:03X}"));
+        }
+    });
+}
+
+fn watch_window(
+    ctx: &Context,
+    machine: &Chip8,
+    watch_list: &mut WatchList,
+    input: &mut String,
+    error: &mut Option<String>,
+) {
+    egui::Window::new("Watch").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            let edit = ui.add(egui::TextEdit::singleline(input).hint_text("mem[I], V[4] * 2, DT"));
+            let is_entered =
+                edit.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if (ui.button("Add").clicked() || is_entered) && !input.trim().is_empty() {
+                *error = watch_list.add(input).err();
+                if error.is_none() {
+                    input.clear();
+                }
+            }
+        });
+        if let Some(error) = error {
+            ui.colored_label(Color32::LIGHT_RED, error.as_str());
+        }
+        let mut removed = None;
+        for (index, (expression, value)) in watch_list.values(machine).into_iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("x").clicked() {
+                    removed = Some(index);
+                }
+                ui.monospace(format!("{expression} = {value} (0x{value:X})"));
+            });
+        }
+        if let Some(index) = removed {
+            _ = watch_list.remove(index);
+        }
+    });
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use tracing::error;
+
+///
+/// Recording and playback of the machine input
+/// File format is plain text: header line `seed <number>` followed by `<cycle> <event>` lines
+///
+#[derive(Clone, Copy)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    Timer,
+    Reset,
+}
+
+impl Display for InputEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KeyDown(code) => write!(f, "down {code:x}"),
+            Self::KeyUp(code) => write!(f, "up {code:x}"),
+            Self::Timer => write!(f, "timer"),
+            Self::Reset => write!(f, "reset"),
+        }
+    }
+}
+
+impl InputEvent {
+    fn parse(tokens: &[&str]) -> Option<Self> {
+        let key_code = || {
+            tokens
+                .get(1)
+                .and_then(|val| u8::from_str_radix(val, 16).ok())
+                .filter(|code| *code < 0x10)
+        };
+        match *tokens.first()? {
+            "down" => Some(Self::KeyDown(key_code()?)),
+            "up" => Some(Self::KeyUp(key_code()?)),
+            "timer" => Some(Self::Timer),
+            "reset" => Some(Self::Reset),
+            _ => None,
+        }
+    }
+}
+
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn with_file<P: AsRef<Path>>(path: P, seed: u64) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "seed {seed}")?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, cycle: u64, event: InputEvent) {
+        if let Err(err) = writeln!(self.writer, "{cycle} {event}") {
+            error!("Failed to record input: {err}");
+        }
+    }
+}
+
+impl Drop for InputRecorder {
+    fn drop(&mut self) {
+        _ = self.writer.flush();
+    }
+}
+
+pub struct InputPlayer {
+    seed: u64,
+    events: VecDeque<(u64, InputEvent)>,
+}
+
+impl InputPlayer {
+    pub fn with_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let invalid_data = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed input record: {line}"),
+            )
+        };
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().unwrap_or_default();
+        let seed = header
+            .strip_prefix("seed ")
+            .and_then(|val| val.trim().parse::<u64>().ok())
+            .ok_or_else(|| invalid_data(header))?;
+        let mut events = VecDeque::new();
+        for line in lines {
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            let cycle = tokens
+                .first()
+                .and_then(|val| val.parse::<u64>().ok())
+                .ok_or_else(|| invalid_data(line))?;
+            let event = InputEvent::parse(&tokens[1..]).ok_or_else(|| invalid_data(line))?;
+            events.push_back((cycle, event));
+        }
+        Ok(Self { seed, events })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns next event that should be applied before executing instruction with number `cycle`
+    pub fn next_event(&mut self, cycle: u64) -> Option<InputEvent> {
+        match self.events.front() {
+            Some((at, _)) if *at <= cycle => self.events.pop_front().map(|(_, event)| event),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use crate::config::MacroConfig;
+use crate::input_log::InputEvent;
+
+///
+/// Keypad events of a macro scheduled by frames from its start, e.g. `1 +30 4 5`
+/// presses the key 1, waits 30 more frames, then presses the keys 4 and 5
+///
+#[derive(Clone)]
+pub struct InputMacro {
+    events: Vec<(u32, InputEvent)>,
+}
+
+impl InputMacro {
+    pub fn parse(config: &MacroConfig) -> Result<Self, String> {
+        let mut events = Vec::new();
+        let mut frame = 0;
+        for token in config.keys.split_whitespace() {
+            if let Some(frames) = token.strip_prefix('+') {
+                frame += frames
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid macro delay {token}"))?;
+                continue;
+            }
+            let code = u8::from_str_radix(token, 16)
+                .ok()
+                .filter(|code| *code < 0x10)
+                .ok_or_else(|| format!("Invalid macro key {token}"))?;
+            events.push((frame, InputEvent::KeyDown(code)));
+            frame += config.hold.max(1);
+            events.push((frame, InputEvent::KeyUp(code)));
+            frame += config.delay;
+        }
+        if events.is_empty() {
+            return Err("Macro has no keys".to_string());
+        }
+        Ok(Self { events })
+    }
+}
+
+/// Playback of the started macro, advanced once per emulated frame
+pub struct MacroPlayer {
+    events: VecDeque<(u32, InputEvent)>,
+    frame: u32,
+}
+
+impl MacroPlayer {
+    pub fn new(input_macro: &InputMacro) -> Self {
+        Self {
+            events: input_macro.events.iter().copied().collect(),
+            frame: 0,
+        }
+    }
+
+    /// Events due on the current frame
+    pub fn next_frame(&mut self) -> Vec<InputEvent> {
+        let mut result = Vec::new();
+        while let Some((_, event)) = self
+            .events
+            .front()
+            .filter(|(frame, _)| *frame <= self.frame)
+        {
+            result.push(*event);
+            self.events.pop_front();
+        }
+        self.frame += 1;
+        result
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use rand::rngs::StdRng;
+
+///
+/// Changes made by a single instruction, keeps the previous values only:
+/// registers are small enough to be copied, memory, stack and pixels are recorded on write
+///
+pub struct Delta {
+    pub reg: [u8; 16],
+    pub ri: u32,
+    pub pc: usize,
+    pub sp: usize,
+    pub dt: u8,
+    pub st: u8,
+    pub pitch: u8,
+    pub scroll_remainder: (isize, isize),
+    pub wait_key: Option<u8>,
+    pub cycles: u64,
+    pub stack: Option<(usize, u16)>,   // overwritten stack slot
+    pub memory: Vec<(usize, u8)>,      // overwritten bytes in the order of writes
+    pub pixels: Vec<usize>,            // flipped pixels of video memory
+    pub video_memory: Option<Vec<u8>>, // the whole screen before clear
+    pub audio_pattern: Option<([u8; 16], bool)>, // pattern before load and its loaded flag
+    pub rpl_flags: Option<[u8; 8]>,    // SCHIP user flags before FX75
+    pub rng: Option<Box<StdRng>>,      // generator state before CXNN
+}
+
+///
+/// Bounded history of executed instructions for stepping backwards,
+/// the oldest deltas are dropped when the capacity is exceeded
+///
+pub struct Journal {
+    deltas: VecDeque<Delta>,
+    capacity: usize,
+}
+
+impl Journal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            deltas: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.deltas.len() > capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Starts recording of the next instruction
+    pub fn begin(&mut self, delta: Delta) {
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(delta);
+    }
+
+    /// Delta of the instruction being executed
+    pub fn current(&mut self) -> Option<&mut Delta> {
+        self.deltas.back_mut()
+    }
+
+    pub fn pop(&mut self) -> Option<Delta> {
+        self.deltas.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+    }
+}
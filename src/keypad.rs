@@ -0,0 +1,103 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+use crate::text;
+
+// the original COSMAC VIP keypad layout
+const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xc],
+    [0x4, 0x5, 0x6, 0xd],
+    [0x7, 0x8, 0x9, 0xe],
+    [0xa, 0x0, 0xb, 0xf],
+];
+
+///
+/// On-screen 4x4 keypad controlled by mouse or touch
+///
+pub struct VirtualKeypad {
+    area: Rect,
+    pressed: Option<u8>,
+}
+
+impl VirtualKeypad {
+    pub fn new() -> Self {
+        Self {
+            area: Rect::new(0, 0, 1, 1),
+            pressed: None,
+        }
+    }
+
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    /// Returns key code if the point hits a key
+    pub fn press(&mut self, x: i32, y: i32) -> Option<u8> {
+        self.pressed = LAYOUT
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, code)| (r, c, *code)))
+            .find(|(r, c, _)| self.key_rect(*r, *c).contains_point((x, y)))
+            .map(|(_, _, code)| code);
+        self.pressed
+    }
+
+    /// Returns key code of previously pressed key
+    pub fn release(&mut self) -> Option<u8> {
+        self.pressed.take()
+    }
+
+    fn key_size(&self) -> u32 {
+        (self.area.width() / 4).min(self.area.height() / 4).max(1)
+    }
+
+    fn key_rect(&self, row: usize, col: usize) -> Rect {
+        let size = self.key_size();
+        let left = self.area.x() + (self.area.width() - 4 * size) as i32 / 2;
+        let top = self.area.y() + (self.area.height() - 4 * size) as i32 / 2;
+        Rect::new(
+            left + (col as u32 * size) as i32,
+            top + (row as u32 * size) as i32,
+            size,
+            size,
+        )
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        fg_color: Color,
+        bg_color: Color,
+    ) -> Result<(), String> {
+        let size = self.key_size();
+        let pixel_size = (size as usize / 12).max(1);
+        let margin = (size / 16).max(1);
+        for (r, row) in LAYOUT.iter().enumerate() {
+            for (c, code) in row.iter().enumerate() {
+                let rect = self.key_rect(r, c);
+                let key = Rect::new(
+                    rect.x() + margin as i32,
+                    rect.y() + margin as i32,
+                    size.saturating_sub(2 * margin).max(1),
+                    size.saturating_sub(2 * margin).max(1),
+                );
+                let is_pressed = self.pressed == Some(*code);
+                let (key_color, label_color) = if is_pressed {
+                    (fg_color, bg_color)
+                } else {
+                    (bg_color, fg_color)
+                };
+                canvas.set_draw_color(key_color);
+                canvas.fill_rect(key)?;
+                canvas.set_draw_color(fg_color);
+                canvas.draw_rect(key)?;
+                let label = format!("{code:X}");
+                let x = key.center().x() - text::text_width(&label, pixel_size) as i32 / 2;
+                let y = key.center().y() - text::text_height(pixel_size) as i32 / 2;
+                text::draw_text(canvas, &label, x, y, pixel_size, label_color)?;
+            }
+        }
+        Ok(())
+    }
+}
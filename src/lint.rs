@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+use crate::chip8::PROGRAM_BASE_ADDRESS;
+use crate::rom_info::{self, call_target, opcode_at, successors};
+
+// SUPER-CHIP DXY0 draws 16x16 sprite
+const LARGE_SPRITE_SIZE: usize = 32;
+
+pub struct Warning {
+    pub address: usize, // instruction which caused the warning
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X}: {}", self.address, self.message)
+    }
+}
+
+/// Index register value known before the instruction, None if it can't be determined statically
+type IndexState = Option<u16>;
+
+///
+/// Static analysis of the program, walks control flow from the entry point
+/// and reports suspicious code which usually works by accident or crashes
+///
+pub fn analyze(rom: &[u8]) -> Vec<Warning> {
+    let states = index_states(rom);
+    let code = rom_info::code_addresses(rom);
+    let mut warnings = Vec::new();
+    let mut written = BTreeSet::new();
+    let mut is_written_anywhere = false; // memory is stored with unknown index register
+    let mut reads = Vec::new(); // instruction address, read range and true for sprites
+    for (&address, &index) in &states {
+        let Some(opcode) = opcode_at(rom, address) else {
+            continue;
+        };
+        let (x, n, nn) = ((opcode >> 8 & 0xf) as usize, opcode & 0xf, opcode & 0xff);
+        match opcode >> 12 {
+            0x1 | 0x2 if opcode & 1 == 1 => warnings.push(Warning {
+                address,
+                message: format!("jump to odd address {:03X}", opcode & 0xfff),
+            }),
+            _ => {}
+        }
+        let Some(index) = index.map(|value| value as usize) else {
+            is_written_anywhere |= opcode >> 12 == 0xf && matches!(nn, 0x33 | 0x55);
+            continue;
+        };
+        match (opcode >> 12, nn) {
+            (0xf, 0x55) => written.extend(index..=index + x),
+            (0xf, 0x33) => written.extend(index..index + 3),
+            (0xf, 0x65) => reads.push((address, index..index + x + 1, false)),
+            (0xd, _) => {
+                let size = if n == 0 {
+                    LARGE_SPRITE_SIZE
+                } else {
+                    n as usize
+                };
+                reads.push((address, index..index + size, true));
+            }
+            _ => {}
+        }
+    }
+    let rom_end = PROGRAM_BASE_ADDRESS + rom.len();
+    for (address, range, is_sprite) in reads {
+        let is_uninitialized =
+            |addr: &usize| *addr >= rom_end && !written.contains(addr) && !is_written_anywhere;
+        if let Some(uninitialized) = range.clone().find(is_uninitialized) {
+            warnings.push(Warning {
+                address,
+                message: format!("reads uninitialized memory at {uninitialized:03X}"),
+            });
+        }
+        // instructions take two bytes
+        let is_code = |addr: &usize| {
+            code.contains(addr) || addr.checked_sub(1).is_some_and(|prev| code.contains(&prev))
+        };
+        if is_sprite {
+            if let Some(instruction) = range.clone().find(is_code) {
+                warnings.push(Warning {
+                    address,
+                    message: format!("draws sprite from code at {instruction:03X}"),
+                });
+            }
+        }
+    }
+    warnings.extend(stack_warnings(rom));
+    warnings.sort_by_key(|warning| warning.address);
+    warnings
+}
+
+/// Propagates known index register values along the control flow,
+/// values merged from different paths and values after calls are unknown
+fn index_states(rom: &[u8]) -> BTreeMap<usize, IndexState> {
+    let mut states = BTreeMap::<usize, IndexState>::new();
+    let mut pending = vec![(PROGRAM_BASE_ADDRESS, Some(0))];
+    while let Some((address, state)) = pending.pop() {
+        let Some(opcode) = opcode_at(rom, address) else {
+            continue;
+        };
+        let state = match states.get(&address) {
+            None => state,
+            Some(known) if *known == state || known.is_none() => continue,
+            Some(_) => None,
+        };
+        states.insert(address, state);
+        let nn = opcode & 0xff;
+        let next_state = match opcode >> 12 {
+            0xa => Some(opcode & 0xfff),
+            0xf if opcode == 0xf000 => opcode_at(rom, address + 2),
+            0xf if matches!(nn, 0x1e | 0x29 | 0x30 | 0x55 | 0x65) => None,
+            0x2 => None,
+            _ => state,
+        };
+        for next in successors(rom, address, opcode) {
+            pending.push((next, next_state));
+        }
+        if let Some(target) = call_target(opcode) {
+            pending.push((target, state));
+        }
+    }
+    states
+}
+
+/// Subroutines which never return and returns which aren't preceded by a call
+fn stack_warnings(rom: &[u8]) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if let Some(address) = find_return(rom, PROGRAM_BASE_ADDRESS) {
+        warnings.push(Warning {
+            address,
+            message: "return outside of subroutine".to_string(),
+        });
+    }
+    let targets = rom_info::code_addresses(rom)
+        .into_iter()
+        .filter_map(|address| {
+            let opcode = opcode_at(rom, address)?;
+            call_target(opcode).map(|target| (target, address))
+        })
+        .collect::<BTreeMap<_, _>>();
+    for (target, address) in targets {
+        if find_return(rom, target).is_none() {
+            warnings.push(Warning {
+                address,
+                message: format!("subroutine {target:03X} never returns"),
+            });
+        }
+    }
+    warnings
+}
+
+/// Address of the first reachable return, nested calls are expected to return
+fn find_return(rom: &[u8], start: usize) -> Option<usize> {
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![start];
+    while let Some(address) = pending.pop() {
+        let Some(opcode) = opcode_at(rom, address) else {
+            continue;
+        };
+        if !visited.insert(address) {
+            continue;
+        }
+        if opcode == 0x00ee {
+            return Some(address);
+        }
+        pending.extend(successors(rom, address, opcode));
+    }
+    None
+}
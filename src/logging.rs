@@ -0,0 +1,22 @@
+use std::io;
+
+use tracing::Level;
+
+/// Log events go to stderr, so reports printed to stdout aren't interleaved with them.
+/// Verbosity 0 shows informational messages, 1 adds debug details and 2 traces every instruction
+pub fn init(verbosity: u8, is_json: bool) {
+    let level = match verbosity {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_target(verbosity > 0);
+    if is_json {
+        builder.json().init();
+    } else {
+        builder.without_time().init();
+    }
+}
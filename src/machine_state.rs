@@ -0,0 +1,140 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::chip8::Chip8;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+///
+/// Machine state as JSON for external tools, e.g. test harnesses which set up
+/// a scenario and check the outcome. Memory is base64 encoded, display rows hold
+/// one digit per pixel: 0 for dark, 1..3 for lit planes
+///
+#[derive(Serialize, Deserialize)]
+pub struct MachineState {
+    pub pc: usize,
+    pub index: u32,
+    pub registers: Vec<u8>,
+    pub stack: Vec<u16>, // return addresses from the outermost call
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub memory: String,
+    pub display: Vec<String>,
+}
+
+impl MachineState {
+    pub fn new(machine: &Chip8) -> Self {
+        let (dt, st) = machine.timers();
+        let display = machine
+            .framebuffer()
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| char::from(b'0' + (*pixel & 0x3)))
+                    .collect()
+            })
+            .collect();
+        Self {
+            pc: machine.pc(),
+            index: machine.index(),
+            registers: machine.registers().to_vec(),
+            stack: machine.stack().to_vec(),
+            delay_timer: dt,
+            sound_timer: st,
+            memory: encode_base64(machine.memory()),
+            display,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| format!("Invalid machine state: {err}"))
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Replaces the machine state, the display must have the current resolution
+    pub fn apply(&self, machine: &mut Chip8) -> Result<(), String> {
+        if self.registers.len() != machine.registers().len() {
+            return Err(format!(
+                "{} registers expected, the state has {}",
+                machine.registers().len(),
+                self.registers.len()
+            ));
+        }
+        let size = machine.display_size();
+        let pixels = self
+            .display
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|ch| match ch {
+                '0'..='3' => Ok(ch as u8 - b'0'),
+                _ => Err(format!("Invalid display pixel {ch}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let is_size_matching = self.display.len() == size.height
+            && self.display.iter().all(|row| row.len() == size.width);
+        if !is_size_matching {
+            return Err(format!(
+                "Display isn't {}x{} as the machine one",
+                size.width, size.height
+            ));
+        }
+        let memory = decode_base64(&self.memory)?;
+        machine.poke(0, &memory).map_err(|err| err.to_string())?;
+        machine
+            .set_stack(&self.stack)
+            .map_err(|err| err.to_string())?;
+        for (index, value) in self.registers.iter().enumerate() {
+            machine.set_register(index, *value);
+        }
+        machine.set_pc(self.pc);
+        machine.set_index(self.index);
+        machine.set_timers(self.delay_timer, self.sound_timer);
+        machine.set_video_ram(&pixels);
+        Ok(())
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = bits >> (18 - 6 * index) & 0x3f;
+                result.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let invalid = || "Invalid base64 memory".to_string();
+    let text = text.trim_end_matches('=');
+    let mut result = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for ch in text.bytes() {
+        let sextet = BASE64_ALPHABET
+            .iter()
+            .position(|symbol| *symbol == ch)
+            .ok_or_else(invalid)?;
+        bits = bits << 6 | sextet as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            result.push((bits >> count) as u8);
+        }
+    }
+    Ok(result)
+}
@@ -1,52 +1,697 @@
+extern crate alloc;
+
 use std::env;
-use std::fs::File;
-use std::io;
-use std::io::Read;
-use std::path::Path;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tracing::{error, info, info_span, warn};
+
+mod assembler;
+
+mod audio;
+
+mod cheats;
+use cheats::Cheats;
 
 mod chip8;
 use chip8::*;
 
+mod cli;
+use cli::Arguments;
+
+mod condition;
+
 mod config;
-use config::Config;
+use config::{Config, ConfigWatcher};
+
+mod coverage;
+
+mod crash_dump;
+
+mod debug_server;
+use debug_server::DebugServer;
+
+mod disasm;
 
 mod common;
 
 mod environ;
 use environ::Environment;
 
+mod font;
+
+mod framebuffer;
+
+mod frame_hash;
+use frame_hash::{Comparison, FrameHashWriter};
+
+mod golden;
+
+mod gui;
+
+mod input_log;
+use input_log::{InputPlayer, InputRecorder};
+
+mod input_macro;
+
+mod journal;
+
+mod keypad;
+
+mod logging;
+
+mod lint;
+
+mod machine_state;
+use machine_state::MachineState;
+
+#[cfg(feature = "megachip")]
+mod megachip;
+
+mod menu;
+
+mod netplay;
+use netplay::{Netplay, NetplayRole};
+
+mod opcode;
+use opcode::Opcode;
+
+mod platform;
+
+mod playlist;
+use playlist::Playlist;
+
+mod profiler;
+
+mod quirks;
+
+mod recorder;
+
+mod renderer;
+
+mod rom_db;
+
+mod rom_info;
+use rom_info::RomInfo;
+
+mod rom_loader;
+use rom_loader::Rom;
+
+mod rom_settings;
+use rom_settings::RomSettings;
+
+mod rpl_storage;
+use rpl_storage::FileFlagStorage;
+
+mod run_report;
+
+mod save_ram;
+use save_ram::SaveRam;
+
+mod screenshot;
+
+#[cfg(feature = "scripting")]
+mod script;
+#[cfg(feature = "scripting")]
+use script::Script;
+
+mod selftest;
+
+mod side_by_side;
+use side_by_side::SideBySide;
+
+mod sprite_editor;
+
+mod symbols;
+use symbols::Symbols;
+
+mod text;
+
+mod tui;
+use tui::TerminalEnvironment;
+
+mod watch_list;
+
+#[cfg(feature = "wgpu")]
+mod wgpu_renderer;
+
 const CONFIG_FILE_NAME: &str = "chip8.toml";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 1 {
+    if env::args().len() == 1 {
         show_usage();
         return;
     }
-    let config = Config::with_file(CONFIG_FILE_NAME).unwrap_or_default();
+    let args = match Arguments::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            show_usage();
+            return;
+        }
+    };
+    logging::init(args.verbosity, args.is_log_json);
+    if args.write_default_config {
+        write_default_config(args.config_path.as_deref());
+        return;
+    }
+    // explicit path, then platform config directory, then current directory
+    let config_path = args
+        .config_path
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| config::find_file(CONFIG_FILE_NAME));
+    let config_span = info_span!("config").entered();
+    let mut config = match config_path.as_ref().map(Config::with_file) {
+        Some(Ok((config, warnings))) => {
+            warnings.iter().for_each(|warning| warn!("{warning}"));
+            if args.is_strict_config && !warnings.is_empty() {
+                std::process::exit(1);
+            }
+            config
+        }
+        Some(Err(err)) => {
+            error!("Config error: {err}");
+            if args.is_strict_config {
+                std::process::exit(1);
+            }
+            Config::default()
+        }
+        None => Config::default(),
+    };
+    config_span.exit();
+    if args.is_selftest {
+        args.apply(&mut config);
+        if !selftest::run(config.quirks) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.is_sprite_editor {
+        args.apply(&mut config);
+        if let Err(err) = sprite_editor::run(&config) {
+            error!("{err}");
+        }
+        return;
+    }
+    if let Some(path) = &args.test_specs_path {
+        args.apply(&mut config);
+        match golden::run(path, &config) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                error!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some((left, right)) = &args.compared_hashes {
+        if !compare_frame_hashes(left, right) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
     // setup chip8
-    let Ok(rom) = load_rom(&args[1]) else {
-        println!("Failed to load ROM {}", args[1]);
+    let playlist = match Playlist::with_paths(&args.rom_paths) {
+        Ok(playlist) => playlist,
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+    let rom_path = playlist.current().to_path_buf();
+    let mut rom = match rom_loader::load(&rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            error!("Failed to load ROM {}: {err}", rom_path.display());
+            return;
+        }
+    };
+    if let Some(path) = &args.patch_path {
+        if let Err(err) = rom_loader::apply_patch(&mut rom, path) {
+            error!("Failed to patch ROM {}: {err}", rom_path.display());
+            return;
+        }
+        info!("Patched {} with {path}", rom.name);
+    }
+    let rom_info = RomInfo::new(&rom.data);
+    if args.is_lint {
+        println!("{rom_info}");
+        let warnings = lint::analyze(&rom.data);
+        warnings
+            .iter()
+            .for_each(|warning| println!("Warning: {warning}"));
+        println!("{} warning(s)", warnings.len());
+        if !warnings.is_empty() {
+            std::process::exit(1);
+        }
         return;
+    }
+    info!("{rom_info}");
+    let overrides = args.clone();
+    let mut rom_settings = RomSettings::new(config, move |config| overrides.apply(config));
+    rom_settings.select_rom(&rom);
+    let mut config = rom_settings.config();
+    let Rom {
+        data: rom,
+        symbols: rom_symbols,
+        ..
+    } = rom;
+    let input_player = match &args.replay_path {
+        Some(path) => match InputPlayer::with_file(path) {
+            Ok(player) => Some(player),
+            Err(err) => {
+                error!("Failed to load input record {path}: {err}");
+                return;
+            }
+        },
+        None => None,
     };
-    let Ok(mut machine) = Chip8::with_rom(rom, config.quirks) else {
-        println!("Failed to load program into memory");
+    // replay requires the same seed as recorded one,
+    // recording always uses a seed to make it reproducible
+    // as well as side by side machines which must produce the same random numbers
+    // as well as netplay machines
+    let is_seeded =
+        args.record_path.is_some() || args.side_by_side.is_some() || args.netplay.is_some();
+    let seed = match &input_player {
+        Some(player) => Some(player.seed()),
+        None if is_seeded => Some(args.seed.unwrap_or_else(rand::random)),
+        None => args.seed,
+    };
+    let netplay = match (&args.netplay, seed) {
+        _ if args.is_tui || input_player.is_some() => {
+            if args.netplay.is_some() {
+                warn!("Netplay isn't supported by terminal frontend and input replay");
+            }
+            None
+        }
+        (Some(NetplayRole::Host(address)), Some(seed)) => {
+            let input_delay = args.input_delay.unwrap_or(netplay::DEFAULT_INPUT_DELAY);
+            match Netplay::host(address, &rom, &config, seed, input_delay) {
+                Ok(netplay) => Some(netplay),
+                Err(err) => {
+                    error!("Netplay error: {err}");
+                    return;
+                }
+            }
+        }
+        (Some(NetplayRole::Client(address)), _) => {
+            match Netplay::connect(address, &rom, &mut config) {
+                Ok(netplay) => Some(netplay),
+                Err(err) => {
+                    error!("Netplay error: {err}");
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+    // the host seed is used by both netplay machines
+    let seed = netplay.as_ref().map(Netplay::seed).or(seed);
+    let input_recorder = match (&args.record_path, seed) {
+        (Some(path), Some(seed)) => match InputRecorder::with_file(path, seed) {
+            Ok(recorder) => Some(recorder),
+            Err(err) => {
+                error!("Failed to create input record {path}: {err}");
+                return;
+            }
+        },
+        _ => None,
+    };
+    let side_by_side = match (&args.side_by_side, seed) {
+        (Some(quirks), Some(seed)) => {
+            match SideBySide::new(rom.clone(), &config, quirks.clone(), seed) {
+                Ok(side_by_side) => Some(side_by_side),
+                Err(err) => {
+                    error!("Failed to start compared machine: {err}");
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+    let font = match config.appearance.load_font() {
+        Ok(font) => font,
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+    let mut builder = Chip8::builder(rom)
+        .quirks(config.quirks)
+        .font(&font)
+        .strict_memory(config.debug.strict_memory)
+        .permissive(config.debug.permissive)
+        .undo_depth(config.debug.undo_depth)
+        .key_release_delay(config.input.key_release_delay)
+        .coverage(args.coverage_path.is_some());
+    if let Some(seed) = seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(address) = args.load_address {
+        builder = builder.program_address(address);
+    }
+    for watchpoint in &args.watchpoints {
+        builder = builder.watchpoint(*watchpoint);
+    }
+    let Ok(mut machine) = builder.build() else {
+        error!("Failed to load program into memory");
         return;
     };
-    let mut environ =
-        Environment::new(config.appearance, &mut machine).expect("Failed to setup SDL2");
+    if !args.pause_at.is_empty() || !args.skip_at.is_empty() {
+        machine.set_pre_instruction_hook(address_hook(args.pause_at.clone(), args.skip_at.clone()));
+    }
+    if let Some(path) = &args.state_path {
+        let result = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| MachineState::from_json(&json))
+            .and_then(|state| state.apply(&mut machine));
+        if let Err(err) = result {
+            error!("Failed to load state {path}: {err}");
+            return;
+        }
+    }
+    if args.is_tui {
+        if input_recorder.is_some() || input_player.is_some() {
+            warn!("Input recording and replay aren't supported by terminal frontend");
+        }
+        if args.frame_hashes_path.is_some() || side_by_side.is_some() {
+            warn!("Frame hashes and side by side run aren't supported by terminal frontend");
+        }
+        if args.script_path.is_some() || args.cheats_path.is_some() {
+            warn!("Scripts and cheats aren't supported by terminal frontend");
+        }
+        if args.is_monitor || args.debug_address.is_some() {
+            warn!("Debugger isn't supported by terminal frontend");
+        }
+        if args.report_path.is_some() {
+            warn!("Run report isn't supported by terminal frontend");
+        }
+        if let Err(err) = TerminalEnvironment::new(config, &mut machine).run() {
+            error!("Terminal error: {err}");
+        }
+        log_unknown_opcodes(&machine);
+        return;
+    }
+    let is_rpl_flags_saved = config.storage.rpl_flags;
+    let save_ram = match SaveRam::with_config(&config.storage, machine.rom()) {
+        Ok(save_ram) => save_ram,
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+    let cheats = match &args.cheats_path {
+        Some(path) => Cheats::with_file(path).map(Some),
+        None => Cheats::for_rom(machine.rom()),
+    };
+    let cheats = cheats.unwrap_or_else(|err| {
+        error!("{err}");
+        None
+    });
+    #[cfg(feature = "scripting")]
+    let script = match &args.script_path {
+        Some(path) => match Script::with_file(path, &mut machine) {
+            Ok(script) => Some(script),
+            Err(err) => {
+                error!("Script error: {err}");
+                return;
+            }
+        },
+        None => None,
+    };
+    #[cfg(not(feature = "scripting"))]
+    if args.script_path.is_some() {
+        warn!("Built without scripting support, the script isn't loaded");
+    }
+    let mut environ = match Environment::new(config, &mut machine) {
+        Ok(environ) => environ,
+        Err(err) => {
+            error!("Failed to setup SDL2: {err}");
+            return;
+        }
+    };
+    environ.set_rom_path(&rom_path);
+    if let Some(path) = &args.patch_path {
+        environ.set_patch_path(path);
+    }
+    // netplay settings are chosen by the host and mustn't be changed
+    if let Some(path) = config_path.as_ref().filter(|_| netplay.is_none()) {
+        environ.set_config_watcher(ConfigWatcher::new(path));
+    }
+    environ.set_rom_settings(rom_settings);
+    let auto_advance = args.auto_advance.map(Duration::from_secs);
+    environ.set_playlist(playlist, auto_advance);
+    environ.set_screenshot_on_exit(args.screenshot_on_exit);
+    if args.is_watch_rom {
+        environ.watch_rom();
+    }
+    if let Some(recorder) = input_recorder {
+        environ.set_input_recorder(recorder);
+    }
+    if let Some(player) = input_player {
+        environ.set_input_player(player);
+    }
+    if let Some(path) = &args.coverage_path {
+        environ.set_coverage_path(path);
+    }
+    if let Some(side_by_side) = side_by_side {
+        environ.set_side_by_side(side_by_side);
+    }
+    if is_rpl_flags_saved {
+        match FileFlagStorage::new() {
+            Some(storage) => environ.set_flag_storage(Box::new(storage)),
+            None => warn!("Data directory isn't available, RPL flags aren't saved"),
+        }
+    }
+    if let Some(save_ram) = save_ram {
+        environ.set_save_ram(save_ram);
+    }
+    if let Some(netplay) = netplay {
+        environ.set_netplay(netplay);
+    }
+    if let Some(cheats) = cheats {
+        environ.set_cheats(cheats);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = script {
+        environ.set_script(script);
+    }
+    if let Some(path) = &args.frame_hashes_path {
+        match FrameHashWriter::with_file(path, args.hash_interval) {
+            Ok(writer) => environ.set_frame_hash_writer(writer),
+            Err(err) => {
+                error!("Failed to create frame hashes file {path}: {err}");
+                return;
+            }
+        }
+    }
+    if let Some(path) = &args.symbols_path {
+        match Symbols::with_file(path) {
+            Ok(symbols) => {
+                info!("Loaded {} symbols", symbols.len());
+                environ.set_symbols(symbols);
+            }
+            Err(err) => error!("{err}"),
+        }
+    } else if !rom_symbols.is_empty() {
+        info!("Using {} labels of the source", rom_symbols.len());
+        environ.set_symbols(rom_symbols);
+    }
+    if let Some(path) = &args.profile_path {
+        environ.set_profile_path(path);
+    }
+    if let Some(path) = &args.report_path {
+        environ.set_report_path(path);
+    }
+    if args.is_monitor {
+        println!("Monitor is ready: peek, poke, bp, step, resume and debug server commands");
+        environ.set_debug_server(DebugServer::console());
+    } else if let Some(address) = &args.debug_address {
+        match DebugServer::bind(address) {
+            Ok(server) => {
+                info!("Debug server is listening on {address}");
+                environ.set_debug_server(server);
+            }
+            Err(err) => {
+                error!("Failed to start debug server on {address}: {err}");
+                return;
+            }
+        }
+    }
     _ = environ.run();
+    drop(environ);
+    log_unknown_opcodes(&machine);
+}
+
+/// Pauses or skips instructions at the addresses given in command line
+fn address_hook(
+    pause_at: Vec<usize>,
+    skip_at: Vec<usize>,
+) -> impl FnMut(&Chip8, Opcode) -> HookAction {
+    // after resume the hook is called again for the same instruction
+    let mut resumed_at = None;
+    move |machine, instruction| {
+        let pc = machine.pc();
+        if skip_at.contains(&pc) {
+            return HookAction::Skip;
+        }
+        if pause_at.contains(&pc) && resumed_at != Some(pc) {
+            info!("Paused at {pc:03X} before {instruction}");
+            resumed_at = Some(pc);
+            return HookAction::Pause;
+        }
+        resumed_at = None;
+        HookAction::Continue
+    }
+}
+
+/// Histogram of the instructions skipped in permissive mode
+fn log_unknown_opcodes(machine: &Chip8) {
+    let mut opcodes = machine.unknown_opcodes();
+    if opcodes.is_empty() {
+        return;
+    }
+    opcodes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let total = opcodes.iter().map(|(_, count)| count).sum::<u64>();
+    warn!("Skipped {total} unknown instructions:");
+    for (opcode, count) in opcodes {
+        warn!("\t{opcode:04X}: {count}");
+    }
 }
 
 fn show_usage() {
     println!("Chip8 Interpreter");
-    println!("\tusage: chip8 <path-to-rom-file>");
+    println!("\tusage: chip8 <path-to-rom-file>... [options]");
+    println!("\t       chip8 --write-default-config [--config <file>]");
+    println!("\t       chip8 --selftest [options]");
+    println!("\t       chip8 --lint <path-to-rom-file>");
+    println!("\t       chip8 --sprite-editor [options]");
+    println!("\t       chip8 test <specs-file> [options]");
+    println!("\t       chip8 --compare-hashes <file> <file>");
+    println!("\tROM is a file, an Octo source (.8o), a directory, a zip archive or an http(s) URL with download feature and curl installed");
+    println!("\toptions:");
+    println!("\t\t--config <file>\tuse config file instead of {CONFIG_FILE_NAME}");
+    println!("\t\t--write-default-config\tcreate config template and exit");
+    println!("\t\t--selftest\trun embedded test programs and verify their display output");
+    println!("\t\t--lint\treport suspicious code found by static analysis and exit");
+    println!("\t\t--sprite-editor\tdraw 8xN sprites or 16x16 ones (Tab) with mouse, Up/Down - height, I - invert, Delete - clear, Ctrl+C - copy bytes");
+    println!("\t\ttest <specs-file>\trun ROMs listed as [[test]] entries and compare their display with golden hashes and PNG files");
+    println!(
+        "\t\t--compare-hashes <file> <file>\treport the first divergent frame of two hash files"
+    );
+    println!("\t\t--strict-config\trefuse to start if config has errors or unknown keys");
+    println!("\t\t--ips <number>\tinstructions per second");
+    println!("\t\t--timing <ips|cosmac>\tflat speed or original COSMAC VIP timing");
+    println!("\t\t--timer-clock <emulated|realtime>\ttimers follow fast forward and turbo or always tick at 60 Hz");
+    println!("\t\t--renderer <canvas|wgpu>\tgraphics backend, wgpu draws only the display");
+    println!("\t\t--audio-device <name>\toutput audio device");
+    println!("\t\t--no-vsync\tdon't wait for the display refresh");
+    println!("\t\t--fps <number>\trendering cap without vsync, 0 - uncapped");
+    println!("\t\t--scale <number>\tdisplay scale");
+    println!("\t\t--integer-scaling\tcrisp pixels, scale is reduced to fit the screen");
+    println!("\t\t--palette <name>\tcolors preset: classic, green, amber, lcd or octo");
+    println!("\t\t--font <name|file>\tdigits font: schip, vip, dream6800, eti660 or 80 bytes file");
+    println!("\t\t--foreground <r,g,b|#rrggbb>\tforeground color");
+    println!("\t\t--background <r,g,b|#rrggbb>\tbackground color");
+    println!(
+        "\t\t--model <name>\tmachine model: cosmac, chip48, schip, hires, dream6800 or eti660"
+    );
+    println!(
+        "\t\t--quirk <name=bool>\toverride quirk: vf_reset, memory, shifting, jumping, hires, key_wait_release, eti660 or half_scroll"
+    );
+    println!("\t\t--compare-model <name>\trun second machine of the model side by side");
+    println!("\t\t--compare-quirk <name=bool>\toverride quirk of the side by side machine");
+    println!("\t\t--strict-memory\tterminate on writes below 0x200");
+    println!("\t\t--crash-dump\tsave machine state to crash-<timestamp>.json on errors");
+    println!("\t\t--load-address <hex>\tprogram address instead of 200 or 600 of ETI-660");
+    println!("\t\t--patch <file>\tapply IPS or BPS patch to the ROM on load");
+    println!("\t\t--permissive\tskip unknown instructions instead of terminating");
+    println!("\t\t--watch <start[-end][:r|w|rw]>\tpause on memory access, hex addresses");
+    println!("\t\t--pause-at <hex>\tpause before the instruction at the address, repeatable");
+    println!("\t\t--skip-at <hex>\tnever execute the instruction at the address, repeatable");
+    println!("\t\t--seed <number>\tseed random generator for reproducible runs");
+    println!("\t\t--screenshot-on-exit\tsave display content to PNG file on exit");
+    println!("\t\t--record <file>\trecord keypad input to file");
+    println!("\t\t--replay <file>\treplay keypad input from file");
+    println!("\t\t--frame-hashes <file>\twrite display hash of every frame to file");
+    println!("\t\t--hash-interval <number>\thash display every N instructions instead of frames");
+    println!("\t\t--auto-advance <seconds>\tswitch to the next ROM after the delay");
+    println!("\t\t--watch-rom\treload the ROM or reassemble the source when the file changes");
+    println!("\t\t--symbols <file>\tlabels shown by debugger and profiler, e.g. Octo symbols");
+    println!("\t\t--load-state <file>\tset registers, memory, timers and display from JSON exported by debug server");
+    println!("\t\t--debug-server <[host:]port>\taccept remote debugger connections");
+    println!("\t\t--monitor\ttype debugger commands in the console, e.g. peek 300 10, poke V3 1F, bp add 2A4, resume");
+    println!("\t\t--cheats <file>\tpatches and memory freezes, cheats/<ROM SHA-1>.toml in data directory by default");
+    println!("\t\t--script <file>\tRhai script for cheats and bots, requires scripting feature");
+    println!("\t\t--profile <file>\twrite execution hotspots on exit, HTML heatmap for .html");
+    println!("\t\t--coverage <file>\twrite map of executed code and read data on exit");
+    println!("\t\t--report <file>\twrite JSON with executed instructions, frames, average IPS, unknown opcodes and peak stack depth on exit");
+    println!(
+        "\t\t--netplay-host <[host:]port>\twait for the second player, settings are sent to them"
+    );
+    println!("\t\t--netplay-connect <host:port>\tplay with the netplay host");
+    println!(
+        "\t\t--input-delay <frames>\tnetplay input latency, {} by default",
+        netplay::DEFAULT_INPUT_DELAY
+    );
+    println!("\t\t--tui\trun in terminal without SDL window, Esc quits");
+    println!("\t\t-v, -vv\tverbose log: debug messages, then executed instructions");
+    println!("\t\t--log-json\twrite log events to stderr as JSON lines");
+    println!("\thotkeys:");
+    println!("\t\tF5 - pause/resume, F9 - reset, Esc - menu");
+    println!("\t\t+/- - change speed, F6 - turbo on/off");
+    println!("\t\tTab (hold) - fast forward, CapsLock (hold) - slow motion");
+    println!("\t\tM - mute/unmute, [/] - volume down/up, P - next palette");
+    println!("\t\tF7 - sound timer and waveform strip, F8 - start/stop GIF recording");
+    println!("\t\tF11 - fullscreen, F12 - screenshot");
+    println!("\t\tkeys of [[input.macros]] config entries - play the keypad sequence");
+    println!("\t\tPageDown/PageUp - next/previous ROM");
+    println!("\t\tF1 - settings and debug windows");
+    println!("\t\tmouse while paused - pixel coordinates in the title, click toggles the pixel");
 }
 
-fn load_rom<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
-    let mut file = File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    Ok(buffer)
+fn write_default_config(path: Option<&str>) {
+    let Some(path) = path
+        .map(PathBuf::from)
+        .or_else(|| config::platform_path(CONFIG_FILE_NAME))
+    else {
+        println!("Config directory isn't available, use --config to specify the path");
+        return;
+    };
+    match Config::write_template(&path) {
+        Ok(_) => println!("Config template written to {}", path.display()),
+        Err(err) => println!("Failed to write config template: {err}"),
+    }
+}
+
+/// Prints the comparison result, returns false if the files diverge
+fn compare_frame_hashes(left: &str, right: &str) -> bool {
+    let describe = |entry: &Option<(u64, String)>| match entry {
+        Some((number, hash)) => format!("{number} {hash}"),
+        None => "end of file".to_string(),
+    };
+    match frame_hash::compare(left, right) {
+        Ok(Comparison::Equal(count)) => {
+            println!("{count} frame hash(es) match");
+            true
+        }
+        Ok(Comparison::Diverged {
+            line,
+            left: left_entry,
+            right: right_entry,
+        }) => {
+            println!("First divergent frame at line {line}");
+            println!("\t{left}: {}", describe(&left_entry));
+            println!("\t{right}: {}", describe(&right_entry));
+            false
+        }
+        Err(err) => {
+            error!("{err}");
+            false
+        }
+    }
 }
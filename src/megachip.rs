@@ -0,0 +1,186 @@
+///
+/// Mega-CHIP extension: 256x192 color display, palette sprites and digitized sound
+///
+use alloc::{vec, vec::Vec};
+
+use crate::common::USize;
+
+pub const MEMORY_SIZE: usize = 16 * 1024 * 1024; // 24-bit address space
+pub const DISPLAY_SIZE: USize = USize {
+    height: 192,
+    width: 256,
+};
+const PALETTE_SIZE: usize = 256;
+const SOUND_HEADER_SIZE: usize = 5;
+
+#[derive(Clone, Copy)]
+enum BlendMode {
+    Normal,
+    Alpha25,
+    Alpha50,
+    Additive,
+    Multiply,
+}
+
+pub enum SoundCommand {
+    Play {
+        samples: Vec<u8>, // unsigned 8-bit PCM
+        rate: u16,
+        is_looped: bool,
+    },
+    Stop,
+}
+
+pub struct MegaChip {
+    front: Vec<u32>,  // displayed ARGB frame, updated by CLS
+    back: Vec<u32>,   // ARGB frame being drawn
+    indices: Vec<u8>, // palette indices of the drawn pixels, used for collision detection
+    palette: [u32; PALETTE_SIZE],
+    sprite_width: usize,
+    sprite_height: usize,
+    blend_mode: BlendMode,
+    collision_index: u8,
+    sound_command: Option<SoundCommand>,
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        let square = DISPLAY_SIZE.square();
+        Self {
+            front: vec![0u32; square],
+            back: vec![0u32; square],
+            indices: vec![0u8; square],
+            palette: [0u32; PALETTE_SIZE],
+            sprite_width: PALETTE_SIZE,
+            sprite_height: PALETTE_SIZE,
+            blend_mode: BlendMode::Normal,
+            collision_index: 0,
+            sound_command: None,
+        }
+    }
+
+    pub fn frame(&self) -> &[u32] {
+        &self.front
+    }
+
+    /// The display is double buffered, drawn frame is shown on clear
+    pub fn clear(&mut self) {
+        core::mem::swap(&mut self.front, &mut self.back);
+        self.back.iter_mut().for_each(|x| *x = 0);
+        self.indices.iter_mut().for_each(|x| *x = 0);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        let offset = (lines * DISPLAY_SIZE.width).min(self.back.len());
+        self.back.copy_within(offset.., 0);
+        self.indices.copy_within(offset.., 0);
+        let start = self.back.len() - offset;
+        self.back[start..].iter_mut().for_each(|x| *x = 0);
+        self.indices[start..].iter_mut().for_each(|x| *x = 0);
+    }
+
+    /// Colors are stored in ARGB order, the first one gets index 1
+    pub fn load_palette(&mut self, data: &[u8]) {
+        for (i, color) in data.chunks_exact(4).enumerate().take(PALETTE_SIZE - 1) {
+            self.palette[i + 1] = u32::from_be_bytes([color[0], color[1], color[2], color[3]]);
+        }
+    }
+
+    /// Zero value means 256 pixels
+    pub fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { width as usize };
+    }
+
+    /// Zero value means 256 pixels
+    pub fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 { 256 } else { height as usize };
+    }
+
+    /// Number of bytes taken by the sprite in memory
+    pub fn sprite_size(&self) -> usize {
+        self.sprite_width * self.sprite_height
+    }
+
+    /// Returns false if the mode is unknown
+    pub fn set_blend_mode(&mut self, mode: u8) -> bool {
+        self.blend_mode = match mode {
+            0 => BlendMode::Normal,
+            1 => BlendMode::Alpha25,
+            2 => BlendMode::Alpha50,
+            3 => BlendMode::Additive,
+            4 => BlendMode::Multiply,
+            _ => return false,
+        };
+        true
+    }
+
+    pub fn set_collision_index(&mut self, index: u8) {
+        self.collision_index = index;
+    }
+
+    /// Draws sprite of palette indices, zero index is transparent.
+    /// Returns true if any pixel of the collision color was overdrawn
+    pub fn draw(&mut self, sprite: &[u8], x: usize, y: usize) -> bool {
+        let mut is_collision = false;
+        for (i, index) in sprite.iter().enumerate() {
+            let (r, c) = (y + i / self.sprite_width, x + i % self.sprite_width);
+            if *index == 0 || r >= DISPLAY_SIZE.height || c >= DISPLAY_SIZE.width {
+                continue;
+            }
+            let idx = r * DISPLAY_SIZE.width + c;
+            is_collision |= self.indices[idx] == self.collision_index;
+            self.indices[idx] = *index;
+            self.back[idx] = self.blend(self.back[idx], self.palette[*index as usize]);
+        }
+        is_collision
+    }
+
+    fn blend(&self, dst: u32, src: u32) -> u32 {
+        let alpha = (src >> 24) as f32 / 255.0;
+        let alpha = match self.blend_mode {
+            BlendMode::Alpha25 => alpha * 0.25,
+            BlendMode::Alpha50 => alpha * 0.5,
+            _ => alpha,
+        };
+        let [_, dst_r, dst_g, dst_b] = dst.to_be_bytes();
+        let [_, src_r, src_g, src_b] = src.to_be_bytes();
+        let channel = |d: u8, s: u8| -> u8 {
+            let (d, s) = (d as f32, s as f32);
+            let value = match self.blend_mode {
+                BlendMode::Additive => d + s * alpha,
+                BlendMode::Multiply => d * s / 255.0,
+                _ => d + (s - d) * alpha,
+            };
+            value.clamp(0.0, 255.0) as u8
+        };
+        u32::from_be_bytes([
+            0xff,
+            channel(dst_r, src_r),
+            channel(dst_g, src_g),
+            channel(dst_b, src_b),
+        ])
+    }
+
+    /// Sound data starts with 16-bit sample rate and 24-bit length
+    pub fn play_sound(&mut self, data: &[u8], is_looped: bool) {
+        if data.len() < SOUND_HEADER_SIZE {
+            return;
+        }
+        let rate = u16::from_be_bytes([data[0], data[1]]);
+        let length = u32::from_be_bytes([0, data[2], data[3], data[4]]) as usize;
+        let end = (SOUND_HEADER_SIZE + length).min(data.len());
+        self.sound_command = Some(SoundCommand::Play {
+            samples: data[SOUND_HEADER_SIZE..end].to_vec(),
+            rate,
+            is_looped,
+        });
+    }
+
+    pub fn stop_sound(&mut self) {
+        self.sound_command = Some(SoundCommand::Stop);
+    }
+
+    pub fn take_sound_command(&mut self) -> Option<SoundCommand> {
+        self.sound_command.take()
+    }
+}
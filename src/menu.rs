@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+use crate::text;
+
+const MAX_VISIBLE_ITEMS: usize = 10;
+const OVERLAY_ALPHA: u8 = 200;
+
+#[derive(Clone)]
+pub enum MenuAction {
+    Resume,
+    Reset,
+    LoadRom,
+    Options,
+    Cheats,
+    Quit,
+    OpenFile(PathBuf),
+    TogglePixelStyle,
+    ToggleScanlines,
+    ToggleFullscreen,
+    ToggleCheat(usize),
+    Back,
+}
+
+pub struct MenuItem {
+    title: String,
+    action: MenuAction,
+}
+
+impl MenuItem {
+    pub fn new(title: impl Into<String>, action: MenuAction) -> Self {
+        Self {
+            title: title.into(),
+            action,
+        }
+    }
+}
+
+///
+/// Simple list menu rendered over the display
+///
+pub struct Menu {
+    title: String,
+    items: Vec<MenuItem>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: impl Into<String>, items: Vec<MenuItem>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_selected_index(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn selected_action(&self) -> Option<MenuAction> {
+        self.items
+            .get(self.selected)
+            .map(|item| item.action.clone())
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        area: Rect,
+        pixel_size: usize,
+        fg_color: Color,
+        bg_color: Color,
+    ) -> Result<(), String> {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(
+            bg_color.r,
+            bg_color.g,
+            bg_color.b,
+            OVERLAY_ALPHA,
+        ));
+        canvas.fill_rect(area)?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let line_height = (text::text_height(pixel_size) + 3 * pixel_size) as i32;
+        // the longest line which fits into the area, 4 chars are taken by margin and cursor
+        let max_chars = (area.width() as usize / ((text::GLYPH_WIDTH + 1) * pixel_size))
+            .saturating_sub(4)
+            .max(1);
+        let first = (self.selected + 1).saturating_sub(MAX_VISIBLE_ITEMS);
+        let visible = self
+            .items
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_VISIBLE_ITEMS);
+        let lines_count = visible.len() as i32 + 2;
+        let left = area.x() + 2 * (text::GLYPH_WIDTH + 1) as i32 * pixel_size as i32;
+        let mut y = area.y() + (area.height() as i32 - lines_count * line_height) / 2;
+        let title = truncate(&self.title, max_chars);
+        text::draw_text(canvas, &title, left, y, pixel_size, fg_color)?;
+        y += 2 * line_height;
+        for (idx, item) in visible {
+            let cursor = if idx == self.selected { "> " } else { "  " };
+            let line = format!("{cursor}{}", truncate(&item.title, max_chars));
+            text::draw_text(canvas, &line, left, y, pixel_size, fg_color)?;
+            y += line_height;
+        }
+        Ok(())
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    let mut result = value
+        .chars()
+        .take(max_chars.saturating_sub(2))
+        .collect::<String>();
+    result.push_str("..");
+    result
+}
@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use tracing::info;
+
+use crate::config::{Config, Timing};
+use crate::input_log::InputEvent;
+use crate::quirks::Quirks;
+use crate::rom_db;
+
+pub const DEFAULT_INPUT_DELAY: u8 = 2;
+
+const MAGIC: &[u8; 4] = b"C8NP";
+const HASH_SIZE: usize = 40; // hex encoded SHA-1
+const MESSAGE_SIZE: usize = 3; // flags byte and keypad state
+const RESET_FLAG: u8 = 0x01;
+
+/// Side of the netplay connection
+#[derive(Clone)]
+pub enum NetplayRole {
+    Host(String),   // address to listen on
+    Client(String), // address of the host
+}
+
+///
+/// Lockstep netplay of two instances running the same ROM: every frame each side sends
+/// its keypad state, the frame is executed when the input of both players is known,
+/// so the machines stay identical. Local input is applied after `input_delay` frames
+/// to hide the network latency. The host chooses the seed and the settings
+/// affecting execution, they are sent to the client in the handshake:
+/// `magic, ROM hash, seed, ips, timing, quirks, key release delay, input delay`
+///
+pub struct Netplay {
+    stream: TcpStream,
+    seed: u64,
+    local_keys: u16,            // current keypad state of the local player
+    is_reset_requested: bool,   // local reset sent with the next frame input
+    is_frame_sent: bool,        // the local input of the awaited frame is sent
+    local: VecDeque<(u8, u16)>, // sent input of the next frames
+    remote: VecDeque<(u8, u16)>,
+    received: Vec<u8>, // incomplete message of the peer
+    keys: u16,         // state applied to the machine
+}
+
+impl Netplay {
+    /// Waits for the peer connection, the session settings are sent to the peer
+    pub fn host(
+        address: &str,
+        rom: &[u8],
+        config: &Config,
+        seed: u64,
+        input_delay: u8,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|err| err.to_string())?;
+        info!("Waiting for netplay peer on {address}");
+        let (mut stream, peer) = listener.accept().map_err(|err| err.to_string())?;
+        info!("Netplay peer connected from {peer}");
+        let peer_hash = read_hello(&mut stream)?;
+        if peer_hash != rom_db::sha1_hex(rom) {
+            return Err("Netplay peer runs another ROM".to_string());
+        }
+        let mut message = hello(rom);
+        message.extend_from_slice(&seed.to_be_bytes());
+        message.extend_from_slice(&config.appearance.operations_per_second.to_be_bytes());
+        // bit 0 - COSMAC timing, bit 1 - idle skip
+        let timing = matches!(config.appearance.timing, Timing::Cosmac) as u8
+            | (config.appearance.is_idle_skip as u8) << 1;
+        message.push(timing);
+        message.push(quirks_bits(config.quirks));
+        message.push(config.input.key_release_delay);
+        message.push(input_delay);
+        stream.write_all(&message).map_err(|err| err.to_string())?;
+        Self::with_stream(stream, seed, input_delay)
+    }
+
+    /// Connects to the host and takes its session settings: the config is updated
+    pub fn connect(address: &str, rom: &[u8], config: &mut Config) -> Result<Self, String> {
+        let mut stream = TcpStream::connect(address).map_err(|err| err.to_string())?;
+        info!("Connected to netplay host {address}");
+        stream
+            .write_all(&hello(rom))
+            .map_err(|err| err.to_string())?;
+        let host_hash = read_hello(&mut stream)?;
+        if host_hash != rom_db::sha1_hex(rom) {
+            return Err("Netplay host runs another ROM".to_string());
+        }
+        let mut seed = [0u8; 8];
+        let mut ips = [0u8; 8];
+        let mut settings = [0u8; 4];
+        for buffer in [&mut seed[..], &mut ips[..], &mut settings[..]] {
+            stream.read_exact(buffer).map_err(|err| err.to_string())?;
+        }
+        let [timing, quirks, key_release_delay, input_delay] = settings;
+        let (seed, ips) = (u64::from_be_bytes(seed), u64::from_be_bytes(ips));
+        config.appearance.operations_per_second = ips;
+        config.appearance.timing = if timing & 0x1 > 0 {
+            Timing::Cosmac
+        } else {
+            Timing::Ips
+        };
+        config.appearance.is_idle_skip = timing & 0x2 > 0;
+        config.quirks = quirks_from_bits(quirks);
+        config.input.key_release_delay = key_release_delay;
+        Self::with_stream(stream, seed, input_delay)
+    }
+
+    fn with_stream(stream: TcpStream, seed: u64, input_delay: u8) -> Result<Self, String> {
+        stream.set_nodelay(true).map_err(|err| err.to_string())?;
+        stream
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        // the first frames are executed without input
+        let empty = VecDeque::from(vec![(0, 0); input_delay as usize]);
+        Ok(Self {
+            stream,
+            seed,
+            local_keys: 0,
+            is_reset_requested: false,
+            is_frame_sent: false,
+            local: empty.clone(),
+            remote: empty,
+            received: Vec::new(),
+            keys: 0,
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn key_down(&mut self, code: u8) {
+        self.local_keys |= 1 << (code & 0xf);
+    }
+
+    pub fn key_up(&mut self, code: u8) {
+        self.local_keys &= !(1 << (code & 0xf));
+    }
+
+    /// Reset is applied by both machines at the same frame
+    pub fn reset(&mut self) {
+        self.is_reset_requested = true;
+    }
+
+    /// Input events of the next frame, None while the peer input isn't received yet.
+    /// Should be called before the frame execution
+    pub fn poll(&mut self) -> Result<Option<Vec<InputEvent>>, String> {
+        // the local input is sent once per frame while the peer one is awaited
+        if !self.is_frame_sent {
+            self.send()?;
+            self.is_frame_sent = true;
+        }
+        self.receive()?;
+        if self.remote.is_empty() {
+            return Ok(None);
+        }
+        let (Some((local_flags, local_keys)), Some((remote_flags, remote_keys))) =
+            (self.local.pop_front(), self.remote.pop_front())
+        else {
+            return Ok(None);
+        };
+        self.is_frame_sent = false;
+        let mut events = Vec::new();
+        if (local_flags | remote_flags) & RESET_FLAG > 0 {
+            events.push(InputEvent::Reset);
+        }
+        let keys = local_keys | remote_keys;
+        for code in 0..0x10u8 {
+            let mask = 1 << code;
+            match (self.keys & mask > 0, keys & mask > 0) {
+                (false, true) => events.push(InputEvent::KeyDown(code)),
+                (true, false) => events.push(InputEvent::KeyUp(code)),
+                _ => {}
+            }
+        }
+        self.keys = keys;
+        Ok(Some(events))
+    }
+
+    fn send(&mut self) -> Result<(), String> {
+        let flags = if self.is_reset_requested {
+            RESET_FLAG
+        } else {
+            0
+        };
+        self.is_reset_requested = false;
+        let [hi, lo] = self.local_keys.to_be_bytes();
+        self.stream
+            .write_all(&[flags, hi, lo])
+            .map_err(|err| format!("Netplay peer disconnected: {err}"))?;
+        self.local.push_back((flags, self.local_keys));
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<(), String> {
+        let mut buffer = [0u8; 256];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err("Netplay peer disconnected".to_string()),
+                Ok(count) => self.received.extend_from_slice(&buffer[..count]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(format!("Netplay peer disconnected: {err}")),
+            }
+        }
+        let count = self.received.len() / MESSAGE_SIZE * MESSAGE_SIZE;
+        let messages = self.received.drain(..count).collect::<Vec<_>>();
+        for message in messages.chunks(MESSAGE_SIZE) {
+            let keys = u16::from_be_bytes([message[1], message[2]]);
+            self.remote.push_back((message[0], keys));
+        }
+        Ok(())
+    }
+}
+
+fn hello(rom: &[u8]) -> Vec<u8> {
+    let mut message = MAGIC.to_vec();
+    message.extend_from_slice(rom_db::sha1_hex(rom).as_bytes());
+    message
+}
+
+/// Returns the ROM hash of the peer
+fn read_hello(stream: &mut TcpStream) -> Result<String, String> {
+    let mut message = [0u8; MAGIC.len() + HASH_SIZE];
+    stream
+        .read_exact(&mut message)
+        .map_err(|err| err.to_string())?;
+    let (magic, hash) = message.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err("Netplay peer isn't a chip8 instance".to_string());
+    }
+    Ok(String::from_utf8_lossy(hash).to_string())
+}
+
+fn quirks_bits(quirks: Quirks) -> u8 {
+    [
+        quirks.vf_reset,
+        quirks.memory,
+        quirks.shifting,
+        quirks.jumping,
+        quirks.hires,
+        quirks.key_wait_release,
+        quirks.eti660,
+        quirks.half_scroll,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |acc, (i, flag)| acc | ((*flag as u8) << i))
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let flag = |i: u8| bits & (1 << i) > 0;
+    Quirks {
+        vf_reset: flag(0),
+        memory: flag(1),
+        shifting: flag(2),
+        jumping: flag(3),
+        hires: flag(4),
+        key_wait_release: flag(5),
+        eti660: flag(6),
+        half_scroll: flag(7),
+    }
+}
@@ -0,0 +1,225 @@
+// Instruction names follow the classic Cowgod's reference notation
+
+use core::fmt::{self, Display};
+
+use crate::chip8::RPL_FLAGS_COUNT;
+
+///
+/// Decoded instruction, X and Y are register indices.
+/// Opcodes which aren't known to any supported platform are kept as `Unknown`
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Opcode {
+    ClearScreen,    // 00E0
+    Return,         // 00EE
+    System(u16),    // 0NNN, machine code routine or extension of the running mode
+    ScrollDown(u8), // 00CN, SCHIP
+    ScrollUp(u8),   // 00DN, XO-CHIP
+    ScrollRight,    // 00FB, SCHIP, 4 pixels
+    ScrollLeft,     // 00FC, SCHIP, 4 pixels
+    Jump(u16),      // 1NNN
+    Call(u16),      // 2NNN
+    SkipEq { x: usize, nn: u8 },
+    SkipNe { x: usize, nn: u8 },
+    SkipRegEq { x: usize, y: usize }, // 5XYN, the original interpreter ignores N
+    Load { x: usize, nn: u8 },
+    Add { x: usize, nn: u8 },
+    Move { x: usize, y: usize },
+    Or { x: usize, y: usize },
+    And { x: usize, y: usize },
+    Xor { x: usize, y: usize },
+    AddReg { x: usize, y: usize },
+    Sub { x: usize, y: usize },
+    ShiftRight { x: usize, y: usize },
+    SubReversed { x: usize, y: usize },
+    ShiftLeft { x: usize, y: usize },
+    SkipRegNe { x: usize, y: usize }, // 9XYN
+    LoadIndex(u16),
+    JumpOffset(u16),
+    Random { x: usize, nn: u8 },
+    Draw { x: usize, y: usize, n: u8 },
+    SkipKeyPressed { x: usize },
+    SkipKeyReleased { x: usize },
+    LoadAudio, // F002, XO-CHIP pattern at I
+    LoadDelay { x: usize },
+    WaitKey { x: usize },
+    SetDelay { x: usize },
+    SetSound { x: usize },
+    AddIndex { x: usize },
+    LoadFont { x: usize },
+    LoadBigFont { x: usize },
+    Bcd { x: usize },
+    SetPitch { x: usize },
+    StoreRegisters { x: usize },
+    LoadRegisters { x: usize },
+    StoreFlags { x: usize },
+    LoadFlags { x: usize },
+    Unknown(u16),
+}
+
+impl Opcode {
+    pub fn decode(opcode: u16) -> Self {
+        let (nnn, nn, n) = (opcode & 0xfff, (opcode & 0xff) as u8, (opcode & 0xf) as u8);
+        let (x, y) = ((opcode >> 8 & 0xf) as usize, (opcode >> 4 & 0xf) as usize);
+        match opcode >> 12 {
+            0x0 => match nnn {
+                0x0e0 => Self::ClearScreen,
+                0x0ee => Self::Return,
+                0x0c0..=0x0cf => Self::ScrollDown(n),
+                0x0d0..=0x0df => Self::ScrollUp(n),
+                0x0fb => Self::ScrollRight,
+                0x0fc => Self::ScrollLeft,
+                _ => Self::System(nnn),
+            },
+            0x1 => Self::Jump(nnn),
+            0x2 => Self::Call(nnn),
+            0x3 => Self::SkipEq { x, nn },
+            0x4 => Self::SkipNe { x, nn },
+            0x5 => Self::SkipRegEq { x, y },
+            0x6 => Self::Load { x, nn },
+            0x7 => Self::Add { x, nn },
+            0x8 => match n {
+                0x0 => Self::Move { x, y },
+                0x1 => Self::Or { x, y },
+                0x2 => Self::And { x, y },
+                0x3 => Self::Xor { x, y },
+                0x4 => Self::AddReg { x, y },
+                0x5 => Self::Sub { x, y },
+                0x6 => Self::ShiftRight { x, y },
+                0x7 => Self::SubReversed { x, y },
+                0xe => Self::ShiftLeft { x, y },
+                _ => Self::Unknown(opcode),
+            },
+            0x9 => Self::SkipRegNe { x, y },
+            0xa => Self::LoadIndex(nnn),
+            0xb => Self::JumpOffset(nnn),
+            0xc => Self::Random { x, nn },
+            0xd => Self::Draw { x, y, n },
+            0xe => match nn {
+                0x9e => Self::SkipKeyPressed { x },
+                0xa1 => Self::SkipKeyReleased { x },
+                _ => Self::Unknown(opcode),
+            },
+            _ => match nn {
+                0x02 if x == 0 => Self::LoadAudio,
+                0x07 => Self::LoadDelay { x },
+                0x0a => Self::WaitKey { x },
+                0x15 => Self::SetDelay { x },
+                0x18 => Self::SetSound { x },
+                0x1e => Self::AddIndex { x },
+                0x29 => Self::LoadFont { x },
+                0x30 => Self::LoadBigFont { x },
+                0x33 => Self::Bcd { x },
+                0x3a => Self::SetPitch { x },
+                0x55 => Self::StoreRegisters { x },
+                0x65 => Self::LoadRegisters { x },
+                0x75 if x < RPL_FLAGS_COUNT => Self::StoreFlags { x },
+                0x85 if x < RPL_FLAGS_COUNT => Self::LoadFlags { x },
+                _ => Self::Unknown(opcode),
+            },
+        }
+    }
+
+    /// Opcode pattern with operands replaced by letters, e.g. `8XY4`
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Self::ClearScreen => "00E0",
+            Self::Return => "00EE",
+            Self::System(_) => "0NNN",
+            Self::ScrollDown(_) => "00CN",
+            Self::ScrollUp(_) => "00DN",
+            Self::ScrollRight => "00FB",
+            Self::ScrollLeft => "00FC",
+            Self::Jump(_) => "1NNN",
+            Self::Call(_) => "2NNN",
+            Self::SkipEq { .. } => "3XNN",
+            Self::SkipNe { .. } => "4XNN",
+            Self::SkipRegEq { .. } => "5XY0",
+            Self::Load { .. } => "6XNN",
+            Self::Add { .. } => "7XNN",
+            Self::Move { .. } => "8XY0",
+            Self::Or { .. } => "8XY1",
+            Self::And { .. } => "8XY2",
+            Self::Xor { .. } => "8XY3",
+            Self::AddReg { .. } => "8XY4",
+            Self::Sub { .. } => "8XY5",
+            Self::ShiftRight { .. } => "8XY6",
+            Self::SubReversed { .. } => "8XY7",
+            Self::ShiftLeft { .. } => "8XYE",
+            Self::SkipRegNe { .. } => "9XY0",
+            Self::LoadIndex(_) => "ANNN",
+            Self::JumpOffset(_) => "BNNN",
+            Self::Random { .. } => "CXNN",
+            Self::Draw { .. } => "DXYN",
+            Self::SkipKeyPressed { .. } => "EX9E",
+            Self::SkipKeyReleased { .. } => "EXA1",
+            Self::LoadAudio => "F002",
+            Self::LoadDelay { .. } => "FX07",
+            Self::WaitKey { .. } => "FX0A",
+            Self::SetDelay { .. } => "FX15",
+            Self::SetSound { .. } => "FX18",
+            Self::AddIndex { .. } => "FX1E",
+            Self::LoadFont { .. } => "FX29",
+            Self::LoadBigFont { .. } => "FX30",
+            Self::Bcd { .. } => "FX33",
+            Self::SetPitch { .. } => "FX3A",
+            Self::StoreRegisters { .. } => "FX55",
+            Self::LoadRegisters { .. } => "FX65",
+            Self::StoreFlags { .. } => "FX75",
+            Self::LoadFlags { .. } => "FX85",
+            Self::Unknown(_) => "????",
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::ClearScreen => write!(f, "CLS"),
+            Self::Return => write!(f, "RET"),
+            Self::System(nnn) => write!(f, "SYS {nnn:03X}"),
+            Self::ScrollDown(n) => write!(f, "SCD {n:X}"),
+            Self::ScrollUp(n) => write!(f, "SCU {n:X}"),
+            Self::ScrollRight => write!(f, "SCR"),
+            Self::ScrollLeft => write!(f, "SCL"),
+            Self::Jump(nnn) => write!(f, "JP {nnn:03X}"),
+            Self::Call(nnn) => write!(f, "CALL {nnn:03X}"),
+            Self::SkipEq { x, nn } => write!(f, "SE V{x:X}, {nn:02X}"),
+            Self::SkipNe { x, nn } => write!(f, "SNE V{x:X}, {nn:02X}"),
+            Self::SkipRegEq { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            Self::Load { x, nn } => write!(f, "LD V{x:X}, {nn:02X}"),
+            Self::Add { x, nn } => write!(f, "ADD V{x:X}, {nn:02X}"),
+            Self::Move { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Self::Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            Self::And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Self::Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Self::AddReg { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Self::Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            Self::ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            Self::SubReversed { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Self::ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            Self::SkipRegNe { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            Self::LoadIndex(nnn) => write!(f, "LD I, {nnn:03X}"),
+            Self::JumpOffset(nnn) => write!(f, "JP V0, {nnn:03X}"),
+            Self::Random { x, nn } => write!(f, "RND V{x:X}, {nn:02X}"),
+            Self::Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n:X}"),
+            Self::SkipKeyPressed { x } => write!(f, "SKP V{x:X}"),
+            Self::SkipKeyReleased { x } => write!(f, "SKNP V{x:X}"),
+            Self::LoadAudio => write!(f, "AUDIO"),
+            Self::LoadDelay { x } => write!(f, "LD V{x:X}, DT"),
+            Self::WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            Self::SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            Self::SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            Self::AddIndex { x } => write!(f, "ADD I, V{x:X}"),
+            Self::LoadFont { x } => write!(f, "LD F, V{x:X}"),
+            Self::LoadBigFont { x } => write!(f, "LD HF, V{x:X}"),
+            Self::Bcd { x } => write!(f, "LD B, V{x:X}"),
+            Self::SetPitch { x } => write!(f, "PITCH V{x:X}"),
+            Self::StoreRegisters { x } => write!(f, "LD [I], V{x:X}"),
+            Self::LoadRegisters { x } => write!(f, "LD V{x:X}, [I]"),
+            Self::StoreFlags { x } => write!(f, "LD R, V{x:X}"),
+            Self::LoadFlags { x } => write!(f, "LD V{x:X}, R"),
+            Self::Unknown(opcode) => write!(f, "DW {opcode:04X}"),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::string::String;
+
+use crate::chip8::RPL_FLAGS_COUNT;
+use crate::common::USize;
+
+#[derive(Clone, Copy)]
+pub enum KeyEvent {
+    Down(u8),
+    Up(u8),
+}
+
+///
+/// Frontend services called by the machine on `Chip8::sync_platform`,
+/// it's an alternative to polling video memory, sound and setting keys by hand
+///
+pub trait Platform {
+    /// Called when video memory was modified since the previous sync
+    fn draw(&mut self, video_memory: &[u8], size: USize);
+
+    /// Called on every sync with the current sound timer state
+    fn set_sound(&mut self, _is_playing: bool) {}
+
+    /// Called once when the sound timer becomes non-zero
+    fn on_sound_start(&mut self) {}
+
+    /// Called on the timer tick when the sound timer reaches zero
+    fn on_sound_stop(&mut self) {}
+
+    /// Keypad events happened since the previous sync, in order
+    fn next_key_event(&mut self) -> Option<KeyEvent> {
+        None
+    }
+}
+
+///
+/// Persistent storage of SCHIP RPL user flags, programs are identified by the key,
+/// e.g. the desktop frontend keeps them in files and a web one may use localStorage
+///
+pub trait FlagStorage {
+    /// None if the program didn't store the flags yet
+    fn load(&mut self, key: &str) -> Option<[u8; RPL_FLAGS_COUNT]>;
+
+    fn save(&mut self, key: &str, flags: &[u8; RPL_FLAGS_COUNT]) -> Result<(), String>;
+}
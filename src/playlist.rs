@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+const ARCHIVE_EXTENSION: &str = "zip";
+const SOURCE_EXTENSION: &str = "8o";
+
+///
+/// Ordered list of ROM files switched at runtime
+///
+pub struct Playlist {
+    paths: Vec<PathBuf>,
+    index: usize,
+}
+
+impl Playlist {
+    /// Directories are replaced with ROM files they contain
+    pub fn with_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self, String> {
+        let mut result = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                result.extend(list_roms(path));
+            } else {
+                result.push(path.to_path_buf());
+            }
+        }
+        if result.is_empty() {
+            return Err("No ROM files found".to_string());
+        }
+        Ok(Self {
+            paths: result,
+            index: 0,
+        })
+    }
+
+    pub fn current(&self) -> &Path {
+        &self.paths[self.index]
+    }
+
+    pub fn next(&mut self) -> &Path {
+        self.index = (self.index + 1) % self.paths.len();
+        self.current()
+    }
+
+    pub fn prev(&mut self) -> &Path {
+        self.index = (self.index + self.paths.len() - 1) % self.paths.len();
+        self.current()
+    }
+}
+
+/// Sorted list of ROM files in the directory
+pub fn list_roms(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    is_rom_file(path)
+                        || is_source_file(path)
+                        || has_extension(path, &[ARCHIVE_EXTENSION])
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+pub fn is_rom_file(path: &Path) -> bool {
+    has_extension(path, &ROM_EXTENSIONS)
+}
+
+/// Octo assembly source
+pub fn is_source_file(path: &Path) -> bool {
+    has_extension(path, &[SOURCE_EXTENSION])
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or_default()
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::disasm;
+use crate::opcode::Opcode;
+use crate::symbols::Symbols;
+
+const HOTSPOTS_COUNT: usize = 32;
+
+///
+/// Execution counters per instruction address and per opcode pattern
+///
+#[derive(Default)]
+pub struct Profiler {
+    addresses: HashMap<usize, (u16, u64)>, // last executed opcode and execution count
+    total: u64,
+}
+
+impl Profiler {
+    pub fn record(&mut self, address: usize, opcode: u16) {
+        let entry = self.addresses.entry(address).or_insert((opcode, 0));
+        // self-modifying code may replace the instruction
+        entry.0 = opcode;
+        entry.1 += 1;
+        self.total += 1;
+    }
+
+    /// HTML heatmap over disassembly is written for `.html` files, plain text otherwise
+    pub fn write_report<P: AsRef<Path>>(&self, path: P, symbols: &Symbols) -> Result<(), String> {
+        let path = path.as_ref();
+        let is_html = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("html"))
+            .unwrap_or_default();
+        let report = if is_html {
+            self.html_report(symbols)
+        } else {
+            self.text_report(symbols)
+        };
+        fs::write(path, report).map_err(|err| err.to_string())
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.total.max(1) as f64
+    }
+
+    fn text_report(&self, symbols: &Symbols) -> String {
+        let mut report = String::new();
+        _ = writeln!(report, "Executed instructions: {}", self.total);
+        _ = writeln!(report, "\nHotspots:");
+        _ = writeln!(
+            report,
+            "{:>12} {:>7}  addr  opcode  {:<20} location",
+            "count", "%", "instruction"
+        );
+        let mut hotspots = self.addresses.iter().collect::<Vec<_>>();
+        hotspots.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then(a.0.cmp(b.0)));
+        for (address, (opcode, count)) in hotspots.iter().take(HOTSPOTS_COUNT) {
+            _ = writeln!(
+                report,
+                "{count:>12} {:>7.2}  {address:03X}  {opcode:04X}    {:<20} {}",
+                self.share(*count),
+                disasm::disassemble(*opcode, symbols),
+                symbols.describe(**address)
+            );
+        }
+        _ = writeln!(report, "\nOpcodes:");
+        _ = writeln!(report, "{:>12} {:>7}  pattern", "count", "%");
+        for (pattern, count) in self.pattern_counts() {
+            _ = writeln!(report, "{count:>12} {:>7.2}  {pattern}", self.share(count));
+        }
+        report
+    }
+
+    fn html_report(&self, symbols: &Symbols) -> String {
+        let mut report = String::new();
+        report.push_str(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Profile</title>\n",
+        );
+        report
+            .push_str("<style>body{font-family:monospace}td{padding:0 8px}</style></head><body>\n");
+        _ = writeln!(report, "<p>Executed instructions: {}</p>", self.total);
+        report.push_str("<table>\n<tr><th>addr</th><th>label</th><th>opcode</th><th>instruction</th><th>count</th><th>%</th></tr>\n");
+        let max = self
+            .addresses
+            .values()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(1);
+        let mut addresses = self.addresses.iter().collect::<Vec<_>>();
+        addresses.sort_by_key(|(address, _)| **address);
+        for (address, (opcode, count)) in addresses {
+            // logarithmic scale keeps rarely executed code visible
+            let heat = (*count as f64).ln_1p() / (max as f64).ln_1p();
+            let green = (255.0 * (1.0 - heat)) as u8;
+            _ = writeln!(
+                report,
+                "<tr style=\"background:rgb(255,{green},{green})\"><td>{address:03X}</td><td>{}</td><td>{opcode:04X}</td><td>{}</td><td>{count}</td><td>{:.2}</td></tr>",
+                symbols.label(*address).unwrap_or_default(),
+                disasm::disassemble(*opcode, symbols),
+                self.share(*count)
+            );
+        }
+        report.push_str("</table>\n<h3>Opcodes</h3>\n<table>\n");
+        for (pattern, count) in self.pattern_counts() {
+            _ = writeln!(
+                report,
+                "<tr><td>{pattern}</td><td>{count}</td><td>{:.2}</td></tr>",
+                self.share(count)
+            );
+        }
+        report.push_str("</table>\n</body></html>\n");
+        report
+    }
+
+    /// Execution counts grouped by opcode pattern, the most frequent first
+    fn pattern_counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts = HashMap::<&'static str, u64>::new();
+        for (opcode, count) in self.addresses.values() {
+            *counts.entry(Opcode::decode(*opcode).pattern()).or_default() += count;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+}
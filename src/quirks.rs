@@ -0,0 +1,29 @@
+///
+/// Behavior differences of the original interpreters, see `config::Model` for the complete sets
+///
+#[derive(Clone, Copy)]
+pub struct Quirks {
+    pub vf_reset: bool,         // reset vf register after AND, OR, XOR operations
+    pub memory: bool,           // increase RI after register dumb/load operations
+    pub shifting: bool,         // TRUE to SHR/SHL with Vx only, otherwise perform Vx = Vy before
+    pub jumping: bool,          // TRUE to BXNN jump to XNN + VX, otherwise BNNN jumps to NNN + V0
+    pub hires: bool,            // TRUE to run programs starting with 1260 in 64x64 mode from 0x2C0
+    pub key_wait_release: bool, // TRUE to complete FX0A on key release, otherwise on press
+    pub eti660: bool,           // TRUE to load programs at 0x600 and use 64x48 display
+    pub half_scroll: bool, // TRUE to scroll lores by half of SCHIP amounts, odd ones carry half a pixel
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            vf_reset: true,
+            memory: false,
+            shifting: true,
+            jumping: false,
+            hires: false,
+            key_wait_release: true,
+            eti660: false,
+            half_scroll: false,
+        }
+    }
+}
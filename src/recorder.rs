@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::path::Path;
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::common::USize;
+use crate::screenshot::Rgb8;
+
+const FRAMES_PER_SECOND: u32 = 60;
+// browsers treat shorter delays as 10 centiseconds
+const MIN_FRAME_DELAY: u16 = 2;
+
+///
+/// Gameplay recorder, collects video memory snapshots and encodes them to animated GIF
+///
+pub struct Recorder {
+    // snapshot and number of display refreshes it was shown
+    frames: Vec<(Vec<u8>, u32)>,
+    size: Option<USize>, // resolution of the first frame, frames of others are skipped
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            size: None,
+        }
+    }
+
+    /// Should be called on every display refresh (60 Hz)
+    pub fn capture(&mut self, video_ram: &[u8], size: USize) {
+        if *self.size.get_or_insert(size) != size {
+            return;
+        }
+        if let Some((last, count)) = self.frames.last_mut() {
+            if last == video_ram {
+                *count += 1;
+                return;
+            }
+        }
+        self.frames.push((video_ram.to_vec(), 1));
+    }
+
+    pub fn frames_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn save_gif<P: AsRef<Path>>(
+        &self,
+        scale: usize,
+        foreground: Rgb8,
+        background: Rgb8,
+        path: P,
+    ) -> Result<(), String> {
+        let Some(size) = self.size else {
+            return Err("No frames recorded".to_string());
+        };
+        let scale = scale.max(1);
+        let width = size.width * scale;
+        let height = size.height * scale;
+        let palette = [background, foreground].concat();
+        let file = File::create(path).map_err(|err| err.to_string())?;
+        let mut encoder = Encoder::new(file, width as u16, height as u16, &palette)
+            .map_err(|err| err.to_string())?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|err| err.to_string())?;
+        // delays are accumulated to avoid drift caused by rounding to centiseconds
+        let mut ticks = 0u32;
+        let mut elapsed_cs = 0u32;
+        for (snapshot, count) in &self.frames {
+            ticks += count;
+            let time_cs = ticks * 100 / FRAMES_PER_SECOND;
+            let delay = (time_cs.saturating_sub(elapsed_cs) as u16).max(MIN_FRAME_DELAY);
+            elapsed_cs += delay as u32;
+            let mut pixels = vec![0u8; width * height];
+            for (idx, pixel) in pixels.iter_mut().enumerate() {
+                let r = idx / width / scale;
+                let c = idx % width / scale;
+                *pixel = (snapshot[r * size.width + c] > 0) as u8;
+            }
+            let mut frame = Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+            frame.delay = delay;
+            encoder.write_frame(&frame).map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
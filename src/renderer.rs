@@ -0,0 +1,119 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, WindowCanvas};
+use sdl2::sys::SDL_RendererFlags;
+use sdl2::video::Window;
+
+use crate::common::USize;
+use crate::screenshot::Rgb8;
+
+///
+/// Draws the window content, the environment composes the frame
+/// from background, machine display and optional overlays
+///
+pub trait RendererBackend {
+    fn window(&self) -> &Window;
+
+    fn window_mut(&mut self) -> &mut Window;
+
+    /// True if presenting waits for the display refresh
+    fn is_vsync(&self) -> bool;
+
+    /// Starts a new frame filled with the background color
+    fn clear(&mut self, background: Rgb8) -> Result<(), String>;
+
+    /// Draws display pixels in RGB24 format scaled to the target area
+    fn draw_display(&mut self, pixels: &[u8], size: USize, target: Rect) -> Result<(), String>;
+
+    /// Canvas for overlays such as menu and notifications, None if they aren't supported
+    fn canvas(&mut self) -> Option<&mut WindowCanvas>;
+
+    fn present(&mut self) -> Result<(), String>;
+}
+
+///
+/// SDL2 accelerated canvas
+///
+pub struct CanvasRenderer {
+    canvas: WindowCanvas,
+    texture: Option<(Texture, USize)>, // display texture and its size
+    is_vsync: bool,
+}
+
+impl CanvasRenderer {
+    pub fn new(window: Window, is_vsync: bool) -> Result<Self, String> {
+        let mut builder = window.into_canvas();
+        if is_vsync {
+            builder = builder.present_vsync();
+        }
+        let canvas = builder.build().map_err(|op| op.to_string())?;
+        let is_vsync =
+            canvas.info().flags & SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32 != 0;
+        // nearest neighbor scaling keeps pixels sharp
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+        Ok(Self {
+            canvas,
+            texture: None,
+            is_vsync,
+        })
+    }
+}
+
+impl RendererBackend for CanvasRenderer {
+    fn window(&self) -> &Window {
+        self.canvas.window()
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        self.canvas.window_mut()
+    }
+
+    fn is_vsync(&self) -> bool {
+        self.is_vsync
+    }
+
+    fn clear(&mut self, background: Rgb8) -> Result<(), String> {
+        let [r, g, b] = background;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+        Ok(())
+    }
+
+    fn draw_display(&mut self, pixels: &[u8], size: USize, target: Rect) -> Result<(), String> {
+        // resolution depends on the machine mode
+        if self.texture.as_ref().map(|(_, size)| *size) != Some(size) {
+            let texture = self
+                .canvas
+                .texture_creator()
+                .create_texture_streaming(
+                    PixelFormatEnum::RGB24,
+                    size.width as u32,
+                    size.height as u32,
+                )
+                .map_err(|op| op.to_string())?;
+            if let Some((old, _)) = self.texture.replace((texture, size)) {
+                // SAFETY: the texture belongs to the canvas which is still alive
+                unsafe { old.destroy() };
+            }
+        }
+        let Some((texture, _)) = &mut self.texture else {
+            return Ok(());
+        };
+        let row_size = size.width * 3;
+        texture.with_lock(None, |buffer, pitch| {
+            for (r, row) in pixels.chunks_exact(row_size).enumerate() {
+                buffer[r * pitch..r * pitch + row_size].copy_from_slice(row);
+            }
+        })?;
+        self.canvas.copy(texture, None, target)
+    }
+
+    fn canvas(&mut self) -> Option<&mut WindowCanvas> {
+        Some(&mut self.canvas)
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        self.canvas.present();
+        Ok(())
+    }
+}
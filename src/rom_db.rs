@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_derive::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::config::{Color, Config, MacroConfig, Palette, QuirksConfig};
+
+///
+/// Database of ROM specific settings, entries are matched by SHA-1 hash or file name
+///
+#[derive(Default, Deserialize)]
+pub struct RomDatabase {
+    #[serde(default, rename = "rom")]
+    roms: Vec<RomProfile>,
+}
+
+impl RomDatabase {
+    /// Unknown keys are returned as warnings like in the config file
+    pub fn with_file<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<String>), String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let mut warnings = Vec::new();
+        let deserializer = &mut toml::Deserializer::new(&content);
+        let database = serde_ignored::deserialize(deserializer, |key| {
+            warnings.push(format!("{}: unknown key '{key}'", path.display()))
+        })
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok((database, warnings))
+    }
+
+    /// Hash match has priority over file name match
+    pub fn find(&self, sha1: &str, file_name: &str) -> Option<&RomProfile> {
+        self.roms
+            .iter()
+            .find(|rom| {
+                rom.sha1
+                    .as_ref()
+                    .is_some_and(|val| val.eq_ignore_ascii_case(sha1))
+            })
+            .or_else(|| {
+                self.roms.iter().find(|rom| {
+                    rom.file_name
+                        .as_ref()
+                        .is_some_and(|val| val.eq_ignore_ascii_case(file_name))
+                })
+            })
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RomProfile {
+    sha1: Option<String>,
+    file_name: Option<String>,
+    pub title: Option<String>,
+    quirks: Option<QuirksConfig>,
+    operations_per_second: Option<u64>,
+    palette: Option<String>,
+    foreground: Option<Color>,
+    background: Option<Color>,
+    second_plane: Option<Color>,
+    both_planes: Option<Color>,
+    font: Option<String>,
+    #[serde(default)]
+    keymap: HashMap<String, u8>,
+    #[serde(default)]
+    macros: Vec<MacroConfig>,
+    persistent_ram: Option<String>,
+}
+
+impl RomProfile {
+    /// Overrides global settings with ROM specific ones
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(quirks) = &self.quirks {
+            config.quirks = quirks.apply(config.quirks);
+        }
+        let appearance = &mut config.appearance;
+        if let Some(ips) = self.operations_per_second {
+            appearance.operations_per_second = ips;
+        }
+        if let Some(palette) = self.palette.as_deref().and_then(Palette::find) {
+            appearance.set_palette(palette);
+        }
+        if let Some(Color(rgb)) = self.foreground {
+            appearance.set_foreground(rgb);
+        }
+        if let Some(Color(rgb)) = self.background {
+            appearance.set_background(rgb);
+        }
+        let [.., second_plane, both_planes] = appearance.plane_colors();
+        appearance.set_plane_colors(
+            self.second_plane.map_or(second_plane, |Color(rgb)| rgb),
+            self.both_planes.map_or(both_planes, |Color(rgb)| rgb),
+        );
+        if let Some(font) = &self.font {
+            appearance.font = font.clone();
+        }
+        for (key, code) in &self.keymap {
+            config.keymap.insert(key.clone(), *code);
+        }
+        config.input.macros.extend(self.macros.iter().cloned());
+        if let Some(range) = &self.persistent_ram {
+            config.storage.persistent_ram = Some(range.clone());
+        }
+    }
+}
+
+/// Hex encoded SHA-1 hash of the ROM content
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use crate::chip8::PROGRAM_BASE_ADDRESS;
+use crate::rom_db;
+
+// XO-CHIP `F000 NNNN` loads 16-bit address stored in the next word
+const LONG_LOAD_OPCODE: u16 = 0xf000;
+
+/// Instruction set a program relies on, ordered from the basic one
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Chip8 => "CHIP-8",
+            Self::SuperChip => "SUPER-CHIP",
+            Self::XoChip => "XO-CHIP",
+        };
+        write!(f, "{name}")
+    }
+}
+
+///
+/// Summary of the loaded ROM, helps to pick the right profile
+///
+pub struct RomInfo {
+    pub size: usize,
+    pub sha1: String,
+    pub crc32: u32,
+    pub extension: Extension,
+    pub first_extended: Option<(usize, u16)>, // address and opcode which determined the extension
+}
+
+impl RomInfo {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut extension = Extension::Chip8;
+        let mut first_extended = None;
+        for address in code_addresses(rom) {
+            let Some(opcode) = opcode_at(rom, address) else {
+                continue;
+            };
+            let level = opcode_extension(opcode);
+            if level > extension {
+                extension = level;
+                first_extended = Some((address, opcode));
+            }
+        }
+        Self {
+            size: rom.len(),
+            sha1: rom_db::sha1_hex(rom),
+            crc32: crc32(rom),
+            extension,
+            first_extended,
+        }
+    }
+}
+
+impl Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "ROM: {} bytes, SHA-1 {}, CRC32 {:08x}",
+            self.size, self.sha1, self.crc32
+        )?;
+        match self.first_extended {
+            Some((address, opcode)) => write!(
+                f,
+                "Instructions: {} ({opcode:04X} at {address:03X})",
+                self.extension
+            ),
+            None => write!(f, "Instructions: {} only", self.extension),
+        }
+    }
+}
+
+/// Addresses of instructions reachable from the entry point
+pub fn code_addresses(rom: &[u8]) -> BTreeSet<usize> {
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![PROGRAM_BASE_ADDRESS];
+    while let Some(address) = pending.pop() {
+        let Some(opcode) = opcode_at(rom, address) else {
+            continue;
+        };
+        if !visited.insert(address) {
+            continue;
+        }
+        pending.extend(successors(rom, address, opcode));
+        if let Some(target) = call_target(opcode) {
+            pending.push(target);
+        }
+    }
+    visited
+}
+
+/// Addresses where execution may continue after the instruction, a call continues after
+/// its return, computed jumps (BNNN) aren't followed since their targets depend on V0
+pub fn successors(rom: &[u8], address: usize, opcode: u16) -> Vec<usize> {
+    let next = address + instruction_size(opcode);
+    match opcode >> 12 {
+        // return and SUPER-CHIP exit
+        0x0 if opcode == 0x00ee || opcode == 0x00fd => vec![],
+        0x1 => vec![(opcode & 0xfff) as usize],
+        0xb => vec![],
+        0x3 | 0x4 | 0xe => vec![next, skip_target(rom, next)],
+        0x5 | 0x9 if opcode & 0xf == 0 => vec![next, skip_target(rom, next)],
+        _ => vec![next],
+    }
+}
+
+pub fn call_target(opcode: u16) -> Option<usize> {
+    (opcode >> 12 == 0x2).then_some((opcode & 0xfff) as usize)
+}
+
+pub fn opcode_at(rom: &[u8], address: usize) -> Option<u16> {
+    let offset = address.checked_sub(PROGRAM_BASE_ADDRESS)?;
+    let bytes = rom.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn instruction_size(opcode: u16) -> usize {
+    if opcode == LONG_LOAD_OPCODE {
+        4
+    } else {
+        2
+    }
+}
+
+/// Skip instructions jump over the whole next instruction
+fn skip_target(rom: &[u8], next: usize) -> usize {
+    next + opcode_at(rom, next).map_or(2, instruction_size)
+}
+
+fn opcode_extension(opcode: u16) -> Extension {
+    match opcode {
+        // scroll down, scroll right/left, exit, low/high resolution
+        0x00c0..=0x00cf | 0x00fb..=0x00ff => return Extension::SuperChip,
+        // scroll up, long index load, audio pattern
+        0x00d0..=0x00df | LONG_LOAD_OPCODE | 0xf002 => return Extension::XoChip,
+        _ => {}
+    }
+    match (opcode >> 12, opcode & 0xff, opcode & 0xf) {
+        // 16x16 sprite, big font, flag registers
+        (0xd, _, 0) | (0xf, 0x30 | 0x75 | 0x85, _) => Extension::SuperChip,
+        // register range save/load, plane selection, pitch
+        (0x5, _, 0x2 | 0x3) | (0xf, 0x01 | 0x3a, _) => Extension::XoChip,
+        _ => Extension::Chip8,
+    }
+}
+
+/// CRC-32 as used by zip archives and BPS patches
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,477 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::assembler;
+use crate::playlist;
+use crate::rom_info::{self, Extension};
+use crate::symbols::Symbols;
+
+// bigger files aren't ROMs even for Mega-CHIP, archives are limited the same way
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+const ZIP_END_SIGNATURE: u32 = 0x06054b50;
+const ZIP_ENTRY_SIGNATURE: u32 = 0x02014b50;
+const ZIP_LOCAL_SIGNATURE: u32 = 0x04034b50;
+const ZIP_END_SIZE: usize = 22;
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+const BPS_HEADER: &[u8] = b"BPS1";
+const BPS_FOOTER_SIZE: usize = 12; // source, target and patch CRC-32
+
+///
+/// Program bytes with the name of the file they came from,
+/// for archives it's the name of the picked entry.
+/// Programs assembled from Octo sources come with their labels
+///
+pub struct Rom {
+    pub data: Vec<u8>,
+    pub name: String,
+    pub symbols: Symbols,
+}
+
+impl Rom {
+    /// Instruction set suggested by the file suffix, e.g. `.sc8` for SUPER-CHIP
+    pub fn suffix_extension(&self) -> Option<Extension> {
+        let (_, suffix) = self.name.rsplit_once('.')?;
+        match suffix.to_lowercase().as_str() {
+            "ch8" | "c8" => Some(Extension::Chip8),
+            "sc8" => Some(Extension::SuperChip),
+            "xo8" => Some(Extension::XoChip),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a ROM file, an Octo source, a zip archive with them or an http(s) URL,
+/// downloads require the `download` feature
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Rom, String> {
+    let path = path.as_ref();
+    let location = path.to_string_lossy();
+    let name = location
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let data = if location.starts_with("http://") || location.starts_with("https://") {
+        download(&location)?
+    } else {
+        read_file(path)?
+    };
+    let (name, data) = if data.starts_with(&ZIP_LOCAL_SIGNATURE.to_le_bytes()) {
+        unzip_rom(&data).map_err(|err| format!("{name}: {err}"))?
+    } else {
+        (name, data)
+    };
+    if !playlist::is_source_file(Path::new(&name)) {
+        let symbols = Symbols::default();
+        return Ok(Rom {
+            data,
+            name,
+            symbols,
+        });
+    }
+    let source = String::from_utf8(data).map_err(|_| format!("{name} isn't a text file"))?;
+    let program = assembler::assemble(&source).map_err(|err| format!("{name}, {err}"))?;
+    let symbols = Symbols::with_labels(&program.labels);
+    Ok(Rom {
+        data: program.rom,
+        name,
+        symbols,
+    })
+}
+
+/// Applies an IPS or BPS patch file to the ROM, e.g. a translation or a bug fix.
+/// BPS patches are checked to be made for this ROM
+pub fn apply_patch<P: AsRef<Path>>(rom: &mut Rom, path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    let patch = read_file(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let data = if patch.starts_with(IPS_HEADER) {
+        patch_ips(&rom.data, &patch)
+    } else if patch.starts_with(BPS_HEADER) {
+        patch_bps(&rom.data, &patch)
+    } else {
+        Err("Unknown patch format, IPS or BPS expected".to_string())
+    };
+    rom.data = data.map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let size = fs::metadata(path).map_err(|err| err.to_string())?.len();
+    if size > MAX_FILE_SIZE {
+        return Err(format!("File of {size} bytes is too big for a ROM"));
+    }
+    let mut buffer = Vec::with_capacity(size as usize);
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buffer))
+        .map_err(|err| err.to_string())?;
+    Ok(buffer)
+}
+
+/// Runs `curl` instead of linking an HTTP and TLS stack, so it has to be installed
+#[cfg(feature = "download")]
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    tracing::info!("Downloading {url}");
+    let output = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-filesize", &MAX_FILE_SIZE.to_string()])
+        .arg(url)
+        .output()
+        .map_err(|err| format!("Failed to run curl, downloads require it installed: {err}"))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to download {url}: {}", message.trim()));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(not(feature = "download"))]
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    Err(format!(
+        "Can't download {url}, built without download feature"
+    ))
+}
+
+/// The only or the largest ROM file of the archive with its name
+fn unzip_rom(archive: &[u8]) -> Result<(String, Vec<u8>), String> {
+    let corrupted = || "Corrupted zip archive".to_string();
+    let end = (0..=archive.len().saturating_sub(ZIP_END_SIZE))
+        .rev()
+        .find(|offset| read_u32(archive, *offset) == Some(ZIP_END_SIGNATURE))
+        .ok_or_else(corrupted)?;
+    let entries_count = read_u16(archive, end + 10).ok_or_else(corrupted)?;
+    let mut offset = read_u32(archive, end + 16).ok_or_else(corrupted)? as usize;
+    // name, method, compressed and uncompressed sizes, local header offset
+    let mut best: Option<(String, u16, usize, u32, usize)> = None;
+    for _ in 0..entries_count {
+        if read_u32(archive, offset) != Some(ZIP_ENTRY_SIGNATURE) {
+            return Err(corrupted());
+        }
+        let field = |at: usize| read_u32(archive, offset + at).ok_or_else(corrupted);
+        let short_field = |at: usize| read_u16(archive, offset + at).ok_or_else(corrupted);
+        let method = short_field(10)?;
+        let compressed_size = field(20)? as usize;
+        let size = field(24)?;
+        let name_length = short_field(28)? as usize;
+        let header_length =
+            46 + name_length + short_field(30)? as usize + short_field(32)? as usize;
+        let local_offset = field(42)? as usize;
+        let name = archive
+            .get(offset + 46..offset + 46 + name_length)
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .ok_or_else(corrupted)?;
+        offset += header_length;
+        let path = Path::new(&name);
+        let is_rom = playlist::is_rom_file(path) || playlist::is_source_file(path);
+        if is_rom && best.as_ref().map_or(true, |best| size > best.3) {
+            best = Some((name, method, compressed_size, size, local_offset));
+        }
+    }
+    let (name, method, compressed_size, size, local_offset) =
+        best.ok_or("No ROM files in the archive".to_string())?;
+    if size as u64 > MAX_FILE_SIZE {
+        return Err(format!("{name} of {size} bytes is too big for a ROM"));
+    }
+    if read_u32(archive, local_offset) != Some(ZIP_LOCAL_SIGNATURE) {
+        return Err(corrupted());
+    }
+    let name_length = read_u16(archive, local_offset + 26).ok_or_else(corrupted)? as usize;
+    let extra_length = read_u16(archive, local_offset + 28).ok_or_else(corrupted)? as usize;
+    let start = local_offset + 30 + name_length + extra_length;
+    let compressed = archive
+        .get(start..start + compressed_size)
+        .ok_or_else(corrupted)?;
+    let data = match method {
+        ZIP_STORED => compressed.to_vec(),
+        ZIP_DEFLATED => {
+            miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, MAX_FILE_SIZE as usize)
+                .map_err(|_| corrupted())?
+        }
+        _ => {
+            return Err(format!(
+                "{name} uses unsupported compression method {method}"
+            ))
+        }
+    };
+    // entries of ROM collections are often grouped in directories
+    let name = name.rsplit('/').next().unwrap_or_default().to_string();
+    Ok((name, data))
+}
+
+/// Records of offset, size and data, zero size ones are runs of a single byte.
+/// The file may be extended by the records or truncated to the size after the footer
+fn patch_ips(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let corrupted = || "Corrupted IPS patch".to_string();
+    let read_u24 = |offset: usize| {
+        patch
+            .get(offset..offset + 3)
+            .map(|bytes| u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]) as usize)
+    };
+    let read_u16 = |offset: usize| {
+        patch
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+    let mut data = source.to_vec();
+    let mut offset = IPS_HEADER.len();
+    loop {
+        if patch.get(offset..offset + IPS_FOOTER.len()) == Some(IPS_FOOTER) {
+            offset += IPS_FOOTER.len();
+            break;
+        }
+        let address = read_u24(offset).ok_or_else(corrupted)?;
+        let size = read_u16(offset + 3).ok_or_else(corrupted)?;
+        offset += 5;
+        let (size, chunk) = if size > 0 {
+            let chunk = patch.get(offset..offset + size).ok_or_else(corrupted)?;
+            offset += size;
+            (size, chunk.to_vec())
+        } else {
+            let size = read_u16(offset).ok_or_else(corrupted)?;
+            let value = *patch.get(offset + 2).ok_or_else(corrupted)?;
+            offset += 3;
+            (size, vec![value; size])
+        };
+        let end = address + size;
+        if end as u64 > MAX_FILE_SIZE {
+            return Err(corrupted());
+        }
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        data[address..end].copy_from_slice(&chunk);
+    }
+    match patch.len() - offset {
+        0 => {}
+        3 => data.truncate(read_u24(offset).ok_or_else(corrupted)?),
+        _ => return Err(corrupted()),
+    }
+    Ok(data)
+}
+
+/// Target is built by copying ranges of the source, the patch and the target itself,
+/// checksums of all three are stored in the footer
+fn patch_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let corrupted = || "Corrupted BPS patch".to_string();
+    let actions_end = patch
+        .len()
+        .checked_sub(BPS_FOOTER_SIZE)
+        .ok_or_else(corrupted)?;
+    let checksum = |index: usize| {
+        let offset = actions_end + index * 4;
+        read_u32(patch, offset).ok_or_else(corrupted)
+    };
+    if rom_info::crc32(&patch[..patch.len() - 4]) != checksum(2)? {
+        return Err(corrupted());
+    }
+    let mut offset = BPS_HEADER.len();
+    let mut number = || read_bps_number(patch, &mut offset).ok_or_else(corrupted);
+    let source_size = number()?;
+    let target_size = number()?;
+    let metadata_size = number()?;
+    if source_size != source.len() || rom_info::crc32(source) != checksum(0)? {
+        return Err("Patch is made for another ROM".to_string());
+    }
+    if target_size as u64 > MAX_FILE_SIZE {
+        return Err(corrupted());
+    }
+    offset = offset.checked_add(metadata_size).ok_or_else(corrupted)?;
+    let mut target = Vec::with_capacity(target_size);
+    // relative offsets of the source and target copy actions
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    while offset < actions_end {
+        let action = read_bps_number(patch, &mut offset).ok_or_else(corrupted)?;
+        let length = (action >> 2) + 1;
+        if target.len() + length > target_size {
+            return Err(corrupted());
+        }
+        match action & 3 {
+            // source read, the source bytes at the same position
+            0 => {
+                let start = target.len();
+                let chunk = source.get(start..start + length).ok_or_else(corrupted)?;
+                target.extend_from_slice(chunk);
+            }
+            // target read, the bytes follow the action
+            1 => {
+                let chunk = patch.get(offset..offset + length).ok_or_else(corrupted)?;
+                target.extend_from_slice(chunk);
+                offset += length;
+            }
+            // source copy from any position
+            2 => {
+                let delta = read_bps_number(patch, &mut offset).ok_or_else(corrupted)?;
+                source_offset = move_bps_offset(source_offset, delta).ok_or_else(corrupted)?;
+                let chunk = source
+                    .get(source_offset..source_offset + length)
+                    .ok_or_else(corrupted)?;
+                target.extend_from_slice(chunk);
+                source_offset += length;
+            }
+            // target copy, the ranges may overlap to repeat a pattern
+            _ => {
+                let delta = read_bps_number(patch, &mut offset).ok_or_else(corrupted)?;
+                target_offset = move_bps_offset(target_offset, delta).ok_or_else(corrupted)?;
+                if target_offset >= target.len() {
+                    return Err(corrupted());
+                }
+                for _ in 0..length {
+                    target.push(target[target_offset]);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if target.len() != target_size || rom_info::crc32(&target) != checksum(1)? {
+        return Err(corrupted());
+    }
+    Ok(target)
+}
+
+/// Variable length number, 7 bits per byte with the last byte marked by the high bit
+fn read_bps_number(patch: &[u8], offset: &mut usize) -> Option<usize> {
+    let mut result = 0usize;
+    let mut shift = 1usize;
+    loop {
+        let byte = *patch.get(*offset)?;
+        *offset += 1;
+        result = result.checked_add((byte as usize & 0x7f).checked_mul(shift)?)?;
+        if byte & 0x80 != 0 {
+            return Some(result);
+        }
+        shift = shift.checked_mul(0x80)?;
+        result = result.checked_add(shift)?;
+    }
+}
+
+/// Copy offset moved by the signed delta, the lowest bit is the sign
+fn move_bps_offset(offset: usize, delta: usize) -> Option<usize> {
+    let distance = delta >> 1;
+    if delta & 1 > 0 {
+        offset.checked_sub(distance)
+    } else {
+        offset.checked_add(distance)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bps_number(mut value: usize) -> Vec<u8> {
+        let mut result = Vec::new();
+        loop {
+            let low = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                result.push(low | 0x80);
+                return result;
+            }
+            result.push(low);
+            value -= 1;
+        }
+    }
+
+    fn bps_patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_HEADER.to_vec();
+        patch.extend(bps_number(source.len()));
+        patch.extend(bps_number(target.len()));
+        patch.extend(bps_number(0));
+        patch.extend_from_slice(actions);
+        patch.extend(rom_info::crc32(source).to_le_bytes());
+        patch.extend(rom_info::crc32(target).to_le_bytes());
+        patch.extend(rom_info::crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    fn bps_action(kind: usize, length: usize) -> Vec<u8> {
+        bps_number((length - 1) << 2 | kind)
+    }
+
+    #[test]
+    fn ips_applies_records_runs_and_truncation() {
+        let mut patch = IPS_HEADER.to_vec();
+        patch.extend([0, 0, 1, 0, 2, 0xaa, 0xbb]); // 2 bytes at 1
+        patch.extend([0, 0, 6, 0, 0, 0, 3, 0x11]); // run of 3 bytes at 6 extends the file
+        patch.extend(IPS_FOOTER);
+        patch.extend([0, 0, 8]); // truncated to 8 bytes
+        let data = patch_ips(&[0; 4], &patch).unwrap();
+        assert_eq!(data, [0, 0xaa, 0xbb, 0, 0, 0, 0x11, 0x11]);
+    }
+
+    #[test]
+    fn ips_without_footer_is_rejected() {
+        let mut patch = IPS_HEADER.to_vec();
+        patch.extend([0, 0, 1, 0, 2, 0xaa]);
+        assert!(patch_ips(&[0; 4], &patch).is_err());
+    }
+
+    #[test]
+    fn bps_applies_all_actions() {
+        let source = [1, 2, 3, 4, 5, 6, 7, 8];
+        let target = [1, 2, 9, 9, 8, 6, 7, 9, 9, 8, 6, 1];
+        let mut actions = bps_action(0, 2); // source read of 1, 2
+        actions.extend(bps_action(1, 3)); // target read
+        actions.extend([9, 9, 8]);
+        actions.extend(bps_action(2, 2)); // source copy of 6, 7 from 5
+        actions.extend(bps_number(5 << 1));
+        actions.extend(bps_action(3, 4)); // overlapped target copy from 2
+        actions.extend(bps_number(2 << 1));
+        actions.extend(bps_action(2, 1)); // source copy moved back by 7 to 1
+        actions.extend(bps_number(7 << 1 | 1));
+        let patch = bps_patch(&source, &target, &actions);
+        assert_eq!(patch_bps(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn bps_for_another_source_is_rejected() {
+        let (source, target) = ([1, 2, 3, 4], [1, 2, 3, 5]);
+        let mut actions = bps_action(0, 3);
+        actions.extend(bps_action(1, 1));
+        actions.push(5);
+        let patch = bps_patch(&source, &target, &actions);
+        assert_eq!(patch_bps(&source, &patch).unwrap(), target);
+        let result = patch_bps(&[1, 2, 3, 6], &patch);
+        assert_eq!(result, Err("Patch is made for another ROM".to_string()));
+    }
+
+    #[test]
+    fn truncated_bps_is_rejected() {
+        let (source, target) = ([1, 2], [1, 2]);
+        let patch = bps_patch(&source, &target, &bps_action(0, 2));
+        for size in [2, BPS_HEADER.len() + 3, patch.len() - 1] {
+            assert!(patch_bps(&source, &patch[..size]).is_err());
+        }
+    }
+
+    #[test]
+    fn bps_number_round_trips() {
+        for value in [0, 1, 0x7f, 0x80, 0x407f, 0x4080, 1 << 30] {
+            let bytes = bps_number(value);
+            let mut offset = 0;
+            assert_eq!(read_bps_number(&bytes, &mut offset), Some(value));
+            assert_eq!(offset, bytes.len());
+        }
+    }
+
+    #[test]
+    fn bps_offset_moves_both_ways() {
+        assert_eq!(move_bps_offset(5, 3 << 1), Some(8));
+        assert_eq!(move_bps_offset(5, 3 << 1 | 1), Some(2));
+        assert_eq!(move_bps_offset(2, 3 << 1 | 1), None);
+    }
+}
@@ -0,0 +1,75 @@
+use tracing::{error, info, warn};
+
+use crate::config::{self, Config, Model};
+use crate::rom_db::{self, RomDatabase, RomProfile};
+use crate::rom_info::Extension;
+use crate::rom_loader::Rom;
+
+const ROM_DATABASE_FILE_NAME: &str = "roms.toml";
+
+///
+/// Settings of the loaded ROM layered over the config file by priority:
+/// the file suffix hint, the ROM profile and the command line values.
+/// Layers of the ROM are replaced when another one is loaded
+///
+pub struct RomSettings {
+    config: Config,                      // content of the config file
+    overrides: Box<dyn Fn(&mut Config)>, // command line values
+    is_superchip: bool,                  // `.sc8` suffix of the ROM file
+    profile: Option<RomProfile>,
+}
+
+impl RomSettings {
+    pub fn new(config: Config, overrides: impl Fn(&mut Config) + 'static) -> Self {
+        Self {
+            config,
+            overrides: Box::new(overrides),
+            is_superchip: false,
+            profile: None,
+        }
+    }
+
+    pub fn select_rom(&mut self, rom: &Rom) {
+        self.is_superchip = rom.suffix_extension() == Some(Extension::SuperChip);
+        if self.is_superchip {
+            info!("Using SUPER-CHIP quirks for {}", rom.name);
+        }
+        self.profile = find_rom_profile(&rom.name, &rom.data);
+    }
+
+    /// Edited config file keeps the layers of the ROM
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> Config {
+        let mut config = self.config.clone();
+        // the suffix is a weaker hint than the profile and the command line
+        if self.is_superchip {
+            config.quirks = Model::Schip.quirks();
+        }
+        if let Some(profile) = &self.profile {
+            profile.apply(&mut config);
+        }
+        (self.overrides)(&mut config);
+        config
+    }
+}
+
+fn find_rom_profile(file_name: &str, rom: &[u8]) -> Option<RomProfile> {
+    let path = config::find_file(ROM_DATABASE_FILE_NAME)?;
+    let database = match RomDatabase::with_file(path) {
+        Ok((database, warnings)) => {
+            warnings.iter().for_each(|warning| warn!("{warning}"));
+            database
+        }
+        Err(err) => {
+            error!("ROM database error: {err}");
+            return None;
+        }
+    };
+    let profile = database.find(&rom_db::sha1_hex(rom), file_name)?;
+    let title = profile.title.as_deref().unwrap_or(file_name);
+    info!("Applied ROM profile: {title}");
+    Some(profile.clone())
+}
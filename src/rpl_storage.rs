@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::chip8::RPL_FLAGS_COUNT;
+use crate::config;
+use crate::platform::FlagStorage;
+
+const FLAGS_DIRECTORY: &str = "flags";
+
+///
+/// RPL flags kept in the platform data directory, one small file per program,
+/// e.g. `$XDG_DATA_HOME/chip8/flags/<ROM SHA-1>.rpl` on Linux
+///
+pub struct FileFlagStorage {
+    directory: PathBuf,
+}
+
+impl FileFlagStorage {
+    /// None if the platform has no data directory
+    pub fn new() -> Option<Self> {
+        let directory = config::data_path(FLAGS_DIRECTORY)?;
+        Some(Self { directory })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{key}.rpl"))
+    }
+}
+
+impl FlagStorage for FileFlagStorage {
+    fn load(&mut self, key: &str) -> Option<[u8; RPL_FLAGS_COUNT]> {
+        let data = fs::read(self.path(key)).ok()?;
+        data.try_into().ok()
+    }
+
+    fn save(&mut self, key: &str, flags: &[u8; RPL_FLAGS_COUNT]) -> Result<(), String> {
+        fs::create_dir_all(&self.directory).map_err(|err| err.to_string())?;
+        fs::write(self.path(key), flags).map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_derive::Serialize;
+
+use crate::chip8::Chip8;
+
+#[derive(Serialize)]
+pub struct UnknownOpcode {
+    pub opcode: String,
+    pub count: u64,
+}
+
+///
+/// Run statistics written as JSON on exit, so performance of ROM builds can be compared
+/// by scripts. Instructions and the stack depth are counted since the machine start
+///
+#[derive(Serialize)]
+pub struct RunReport {
+    pub instructions: u64,
+    pub frames: u64, // frames presented in the window
+    pub seconds: f64,
+    pub average_ips: f64,
+    pub unknown_opcodes: Vec<UnknownOpcode>, // skipped ones and the one which stopped the machine
+    pub peak_stack_depth: usize,
+}
+
+impl RunReport {
+    pub fn new(
+        machine: &Chip8,
+        frames: u64,
+        duration: Duration,
+        failed_opcode: Option<u16>,
+    ) -> Self {
+        let mut opcodes = machine.unknown_opcodes();
+        opcodes.extend(failed_opcode.map(|opcode| (opcode, 1)));
+        opcodes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let seconds = duration.as_secs_f64();
+        let instructions = machine.cycles();
+        Self {
+            instructions,
+            frames,
+            seconds,
+            average_ips: if seconds > 0.0 {
+                instructions as f64 / seconds
+            } else {
+                0.0
+            },
+            unknown_opcodes: opcodes
+                .into_iter()
+                .map(|(opcode, count)| UnknownOpcode {
+                    opcode: format!("{opcode:04X}"),
+                    count,
+                })
+                .collect(),
+            peak_stack_depth: machine.peak_stack_depth(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, json).map_err(|err| err.to_string())
+    }
+}
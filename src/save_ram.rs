@@ -0,0 +1,84 @@
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use tracing::warn;
+
+use crate::chip8::Chip8;
+use crate::config::{self, StorageConfig};
+use crate::rom_db;
+
+const RAM_DIRECTORY: &str = "ram";
+
+///
+/// Memory range of the program kept between runs like a battery backed cartridge RAM,
+/// e.g. high scores stored at a fixed address. The content is saved per ROM hash
+/// to `$XDG_DATA_HOME/chip8/ram/<ROM SHA-1>.ram` on Linux
+///
+pub struct SaveRam {
+    range: RangeInclusive<usize>,
+    path: PathBuf,
+}
+
+impl SaveRam {
+    /// None if the platform has no data directory
+    pub fn new(range: RangeInclusive<usize>, rom: &[u8]) -> Option<Self> {
+        let path = config::data_path(RAM_DIRECTORY)?.join(format!("{}.ram", rom_db::sha1_hex(rom)));
+        Some(Self { range, path })
+    }
+
+    /// None if the settings have no range for the ROM or the platform has no data directory
+    pub fn with_config(config: &StorageConfig, rom: &[u8]) -> Result<Option<Self>, String> {
+        let Some(range) = config
+            .persistent_ram
+            .as_deref()
+            .map(parse_range)
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+        let save_ram = Self::new(range, rom);
+        if save_ram.is_none() {
+            warn!("Data directory isn't available, persistent RAM isn't saved");
+        }
+        Ok(save_ram)
+    }
+
+    /// Writes the saved content into the machine memory, nothing is changed on the first run
+    pub fn restore(&self, machine: &mut Chip8) -> Result<(), String> {
+        let Ok(data) = fs::read(&self.path) else {
+            return Ok(());
+        };
+        // the range may be changed since the last save
+        let len = data.len().min(self.range.clone().count());
+        machine
+            .poke(*self.range.start(), &data[..len])
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, machine: &Chip8) -> Result<(), String> {
+        let data = machine
+            .memory()
+            .get(self.range.clone())
+            .ok_or_else(|| "Persistent RAM range is out of memory bounds".to_string())?;
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+        }
+        fs::write(&self.path, data).map_err(|err| err.to_string())
+    }
+}
+
+/// Range is expected in `start-end` format, addresses are hexadecimal and inclusive
+pub fn parse_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let invalid = || format!("Invalid persistent RAM range: {value}");
+    let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+    let parse_address = |address: &str| {
+        let address = address.trim().trim_start_matches("0x");
+        usize::from_str_radix(address, 16).map_err(|_| invalid())
+    };
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if start > end {
+        return Err(invalid());
+    }
+    Ok(start..=end)
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+
+use crate::framebuffer::Framebuffer;
+
+pub type Rgb8 = [u8; 3];
+
+/// Saves the display as PNG image, each chip8 pixel becomes square of `scale` size.
+/// The palette holds the background and the planes colors
+pub fn save_png<P: AsRef<Path>>(
+    framebuffer: &Framebuffer,
+    scale: usize,
+    palette: &[Rgb8; 4],
+    path: P,
+) -> Result<(), String> {
+    let scale = scale.max(1) as u32;
+    let width = framebuffer.width() as u32;
+    let height = framebuffer.height() as u32;
+    let image = RgbaImage::from_raw(width, height, framebuffer.to_rgba(palette))
+        .ok_or("Video memory doesn't match the display size".to_string())?;
+    imageops::resize(&image, width * scale, height * scale, FilterType::Nearest)
+        .save(path)
+        .map_err(|err| err.to_string())
+}
+
+/// Whether the PNG image shows the display, the image may be scaled
+pub fn matches_png<P: AsRef<Path>>(
+    framebuffer: &Framebuffer,
+    palette: &[Rgb8; 4],
+    path: P,
+) -> Result<bool, String> {
+    let image = image::open(path).map_err(|err| err.to_string())?.to_rgba8();
+    let (width, height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+    let scale = image.width() / width;
+    if scale == 0 || image.width() != width * scale || image.height() != height * scale {
+        return Ok(false);
+    }
+    let expected = framebuffer.to_rgba(palette);
+    let is_matching = expected.chunks(4).enumerate().all(|(index, pixel)| {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        image.get_pixel(x * scale, y * scale).0 == pixel
+    });
+    Ok(is_matching)
+}
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST};
+use tracing::info;
+
+use crate::chip8::Chip8;
+use crate::input_log::InputEvent;
+
+// Mega-CHIP memory is too large to be copied on every call
+const SNAPSHOT_SIZE: usize = 0x10000;
+
+const FRAME_HOOK: &str = "on_frame";
+const INSTRUCTION_HOOK: &str = "on_instruction";
+const WRITE_HOOK: &str = "on_write";
+
+/// Machine state visible to the script and its changes applied after the call
+#[derive(Default)]
+struct Context {
+    memory: Vec<u8>,
+    registers: [u8; 16],
+    index: u32,
+    pc: usize,
+    cycles: u64,
+    pokes: Vec<(usize, u8)>,
+    is_registers_changed: bool,
+    is_index_changed: bool,
+    input: Vec<InputEvent>,
+    messages: Vec<String>,
+}
+
+type Shared = Rc<RefCell<Context>>;
+
+///
+/// Rhai script for cheats, bots and custom HUDs. Top level statements run once on load,
+/// then the functions `on_frame()`, `on_instruction()` and `on_write(address, value)`
+/// are called if defined. Machine API: `peek(address)`, `poke(address, value)`,
+/// `reg(x)`, `set_reg(x, value)`, `index()`, `set_index(value)`, `pc()`, `cycles()`,
+/// `key_down(code)`, `key_up(code)` and `show(text)` for on screen messages
+///
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    context: Shared,
+    is_frame_hooked: bool,
+    is_instruction_hooked: bool,
+    is_write_hooked: bool,
+}
+
+impl Script {
+    pub fn with_file<P: AsRef<Path>>(path: P, machine: &mut Chip8) -> Result<Self, String> {
+        let context = Shared::default();
+        let mut engine = Engine::new();
+        engine.on_print(|text| info!("Script: {text}"));
+        register_api(&mut engine, &context);
+        let ast = engine
+            .compile_file(path.as_ref().to_path_buf())
+            .map_err(|err| err.to_string())?;
+        let is_hooked = |name: &str| ast.iter_functions().any(|func| func.name == name);
+        let mut script = Self {
+            is_frame_hooked: is_hooked(FRAME_HOOK),
+            is_instruction_hooked: is_hooked(INSTRUCTION_HOOK),
+            is_write_hooked: is_hooked(WRITE_HOOK),
+            engine,
+            ast,
+            scope: Scope::new(),
+            context,
+        };
+        script.capture(machine);
+        script
+            .engine
+            .run_ast_with_scope(&mut script.scope, &script.ast)
+            .map_err(|err| err.to_string())?;
+        script.apply(machine);
+        Ok(script)
+    }
+
+    /// Machine should log its memory writes for `on_write` hook
+    pub fn is_write_hooked(&self) -> bool {
+        self.is_write_hooked
+    }
+
+    pub fn on_frame(&mut self, machine: &mut Chip8) -> Result<(), String> {
+        if !self.is_frame_hooked {
+            return Ok(());
+        }
+        self.call(machine, FRAME_HOOK, ())
+    }
+
+    pub fn on_instruction(&mut self, machine: &mut Chip8) -> Result<(), String> {
+        if !self.is_instruction_hooked {
+            return Ok(());
+        }
+        self.call(machine, INSTRUCTION_HOOK, ())
+    }
+
+    pub fn on_write(
+        &mut self,
+        machine: &mut Chip8,
+        address: usize,
+        value: u8,
+    ) -> Result<(), String> {
+        if !self.is_write_hooked {
+            return Ok(());
+        }
+        self.call(machine, WRITE_HOOK, (address as i64, value as i64))
+    }
+
+    /// Key events produced by the script since the previous call
+    pub fn take_input(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.context.borrow_mut().input)
+    }
+
+    /// Texts to show on screen
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.context.borrow_mut().messages)
+    }
+
+    fn call(&mut self, machine: &mut Chip8, name: &str, args: impl FuncArgs) -> Result<(), String> {
+        self.capture(machine);
+        // top level statements aren't repeated on every call
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+        self.apply(machine);
+        result.map(|_| ()).map_err(|err| format!("{name}: {err}"))
+    }
+
+    fn capture(&mut self, machine: &Chip8) {
+        let mut context = self.context.borrow_mut();
+        let memory = machine.memory();
+        context.memory.clear();
+        context
+            .memory
+            .extend_from_slice(&memory[..memory.len().min(SNAPSHOT_SIZE)]);
+        context.registers = *machine.registers();
+        context.index = machine.index();
+        context.pc = machine.pc();
+        context.cycles = machine.cycles();
+    }
+
+    fn apply(&mut self, machine: &mut Chip8) {
+        let mut context = self.context.borrow_mut();
+        for (address, value) in std::mem::take(&mut context.pokes) {
+            // addresses are checked by the script API
+            _ = machine.poke(address, &[value]);
+        }
+        if std::mem::take(&mut context.is_registers_changed) {
+            for (index, value) in context.registers.iter().enumerate() {
+                machine.set_register(index, *value);
+            }
+        }
+        if std::mem::take(&mut context.is_index_changed) {
+            machine.set_index(context.index);
+        }
+    }
+}
+
+fn register_api(engine: &mut Engine, context: &Shared) {
+    let ctx = context.clone();
+    engine.register_fn(
+        "peek",
+        move |address: i64| -> Result<i64, Box<EvalAltResult>> {
+            let context = ctx.borrow();
+            let value = usize::try_from(address)
+                .ok()
+                .and_then(|address| context.memory.get(address))
+                .ok_or_else(|| format!("Address {address:X} is out of memory"))?;
+            Ok(*value as i64)
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn(
+        "poke",
+        move |address: i64, value: i64| -> Result<(), Box<EvalAltResult>> {
+            let mut context = ctx.borrow_mut();
+            let cell = usize::try_from(address)
+                .ok()
+                .filter(|address| *address < context.memory.len())
+                .ok_or_else(|| format!("Address {address:X} is out of memory"))?;
+            context.memory[cell] = value as u8;
+            context.pokes.push((cell, value as u8));
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn("reg", move |x: i64| -> Result<i64, Box<EvalAltResult>> {
+        let context = ctx.borrow();
+        let value = usize::try_from(x)
+            .ok()
+            .and_then(|x| context.registers.get(x))
+            .ok_or_else(|| format!("Invalid register V{x}"))?;
+        Ok(*value as i64)
+    });
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_reg",
+        move |x: i64, value: i64| -> Result<(), Box<EvalAltResult>> {
+            let mut context = ctx.borrow_mut();
+            let register = usize::try_from(x)
+                .ok()
+                .and_then(|x| context.registers.get_mut(x))
+                .ok_or_else(|| format!("Invalid register V{x}"))?;
+            *register = value as u8;
+            context.is_registers_changed = true;
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn("index", move || ctx.borrow().index as i64);
+    let ctx = context.clone();
+    engine.register_fn("set_index", move |value: i64| {
+        let mut context = ctx.borrow_mut();
+        context.index = value as u32;
+        context.is_index_changed = true;
+    });
+    let ctx = context.clone();
+    engine.register_fn("pc", move || ctx.borrow().pc as i64);
+    let ctx = context.clone();
+    engine.register_fn("cycles", move || ctx.borrow().cycles as i64);
+    let ctx = context.clone();
+    engine.register_fn("key_down", move |code: i64| {
+        let code = (code & 0xf) as u8;
+        ctx.borrow_mut().input.push(InputEvent::KeyDown(code));
+    });
+    let ctx = context.clone();
+    engine.register_fn("key_up", move |code: i64| {
+        let code = (code & 0xf) as u8;
+        ctx.borrow_mut().input.push(InputEvent::KeyUp(code));
+    });
+    let ctx = context.clone();
+    engine.register_fn("show", move |text: &str| {
+        ctx.borrow_mut().messages.push(text.to_string());
+    });
+}
@@ -0,0 +1,68 @@
+use crate::chip8::{Chip8, RunBudget, RunStatus};
+use crate::quirks::Quirks;
+use crate::rom_db;
+
+const OPERATIONS_PER_FRAME: u64 = 12;
+
+///
+/// Test program with SHA-1 of the display content it's expected to produce
+///
+struct TestRom {
+    name: &'static str,
+    rom: &'static [u8],
+    frames: usize,
+    display_hash: &'static str,
+}
+
+// programs avoid quirk dependent instructions, so the results don't depend on settings
+const TEST_ROMS: [TestRom; 2] = [
+    TestRom {
+        name: "font",
+        rom: include_bytes!("../roms/selftest/font.ch8"),
+        frames: 30,
+        display_hash: "4019f1508a41fc1493b1c1e6c713822c47b65034",
+    },
+    TestRom {
+        name: "opcodes",
+        rom: include_bytes!("../roms/selftest/opcodes.ch8"),
+        frames: 60,
+        display_hash: "3661d3f178e50e0a5c4d7a17214d53e7d7cf06a9",
+    },
+];
+
+/// Runs embedded test programs without display, returns true if all of them passed
+pub fn run(quirks: Quirks) -> bool {
+    let mut is_passed = true;
+    for test in &TEST_ROMS {
+        match display_hash(test, quirks) {
+            Ok(hash) if hash == test.display_hash => println!("{}: ok", test.name),
+            Ok(hash) => {
+                println!("{}: FAILED, display hash {hash}", test.name);
+                is_passed = false;
+            }
+            Err(err) => {
+                println!("{}: FAILED, {err}", test.name);
+                is_passed = false;
+            }
+        }
+    }
+    is_passed
+}
+
+fn display_hash(test: &TestRom, quirks: Quirks) -> Result<String, String> {
+    let mut machine = Chip8::builder(test.rom.to_vec())
+        .quirks(quirks)
+        .seed(0)
+        .build()
+        .map_err(|err| err.to_string())?;
+    for _ in 0..test.frames {
+        let status = machine
+            .run_for(RunBudget::Instructions(OPERATIONS_PER_FRAME))
+            .map_err(|err| err.to_string())?;
+        if status == RunStatus::Stopped {
+            return Err("machine stopped".to_string());
+        }
+        machine.on_timer();
+    }
+    Ok(rom_db::sha1_hex(machine.get_video_ram()))
+}
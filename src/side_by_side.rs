@@ -0,0 +1,158 @@
+use tracing::{info, warn};
+
+use crate::chip8::{Chip8, Severity, State};
+use crate::common::USize;
+use crate::config::{Config, QuirksConfig};
+use crate::input_log::InputEvent;
+use crate::screenshot::Rgb8;
+
+// pixels of the second display which differ from the first one
+const DIVERGENCE_COLOR: Rgb8 = [0xff, 0x30, 0x30];
+const SEPARATOR_COLOR: Rgb8 = [0x80, 0x80, 0x80];
+const SEPARATOR_WIDTH: usize = 1;
+
+///
+/// Second machine running the same program and input with other quirks,
+/// its display is shown next to the main one to find the frame where the behavior differs.
+/// The machine follows the main one by executed instructions count
+///
+pub struct SideBySide {
+    machine: Chip8,
+    quirks: QuirksConfig, // overrides of the main machine quirks
+    frames_count: u64,
+    diverged_frame: Option<u64>,
+}
+
+impl SideBySide {
+    pub fn new(
+        rom: Vec<u8>,
+        config: &Config,
+        quirks: QuirksConfig,
+        seed: u64,
+    ) -> Result<Self, String> {
+        let machine = Chip8::builder(rom)
+            .quirks(quirks.apply(config.quirks))
+            .seed(seed)
+            .key_release_delay(config.input.key_release_delay)
+            .build()
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            machine,
+            quirks,
+            frames_count: 0,
+            diverged_frame: None,
+        })
+    }
+
+    /// Settings of the main machine were changed, the own quirks are applied on top of them
+    pub fn apply_config(&mut self, config: &Config) {
+        self.machine.set_quirks(self.quirks.apply(config.quirks));
+        self.machine
+            .set_key_release_delay(config.input.key_release_delay);
+    }
+
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        self.frames_count = 0;
+        self.diverged_frame = None;
+        self.machine.load_rom(rom).map_err(|err| err.to_string())
+    }
+
+    /// Executes instructions until the count reaches the main machine one
+    pub fn sync(&mut self, cycles: u64) {
+        while self.machine.cycles() < cycles {
+            if matches!(self.machine.get_state(), State::Terminated) {
+                return;
+            }
+            if let Err(err) = self.machine.teak() {
+                warn!("Compared machine error: {err}");
+                if err.severity() == Severity::Fatal {
+                    self.machine.terminate();
+                }
+            }
+        }
+    }
+
+    /// Input is applied after the instructions executed by the main machine
+    pub fn send_input(&mut self, cycles: u64, event: InputEvent) {
+        self.sync(cycles);
+        // invalid key codes are already reported by the main machine
+        match event {
+            InputEvent::KeyDown(code) => _ = self.machine.key_down(code),
+            InputEvent::KeyUp(code) => _ = self.machine.key_up(code),
+            InputEvent::Timer => self.machine.on_timer(),
+            InputEvent::Reset => {
+                self.frames_count = 0;
+                self.diverged_frame = None;
+                self.machine.reset();
+            }
+        }
+    }
+
+    /// Compares the displays at the end of the frame, returns true on the first divergence
+    pub fn check_frame(&mut self, video_ram: &[u8]) -> bool {
+        self.frames_count += 1;
+        if self.diverged_frame.is_some() || self.machine.get_video_ram() == video_ram {
+            return false;
+        }
+        info!("Displays diverged at frame {}", self.frames_count);
+        self.diverged_frame = Some(self.frames_count);
+        true
+    }
+
+    pub fn diverged_frame(&self) -> Option<u64> {
+        self.diverged_frame
+    }
+
+    pub fn poll_display_changed(&mut self) -> bool {
+        self.machine.poll_display_changed()
+    }
+
+    /// Size of both displays with the separator between them
+    pub fn screen_size(&self, size: USize) -> USize {
+        let other = self.machine.display_size();
+        USize {
+            width: size.width + SEPARATOR_WIDTH + other.width,
+            height: size.height.max(other.height),
+        }
+    }
+
+    /// Places RGB24 pixels of the main display on the left and this machine display
+    /// on the right, pixels which differ from `video_ram` are highlighted
+    pub fn compose(
+        &self,
+        frame: &[u8],
+        size: USize,
+        video_ram: &[u8],
+        fg_color: Rgb8,
+        bg_color: Rgb8,
+    ) -> Vec<u8> {
+        let other = self.machine.display_size();
+        let screen = self.screen_size(size);
+        let mut pixels = Vec::with_capacity(screen.square() * 3);
+        let is_same_size = size == other;
+        for row in 0..screen.height {
+            for col in 0..size.width {
+                let color = match frame.get((row * size.width + col) * 3..) {
+                    Some([r, g, b, ..]) if row < size.height => [*r, *g, *b],
+                    _ => bg_color,
+                };
+                pixels.extend_from_slice(&color);
+            }
+            (0..SEPARATOR_WIDTH).for_each(|_| pixels.extend_from_slice(&SEPARATOR_COLOR));
+            for col in 0..other.width {
+                let idx = row * other.width + col;
+                let is_lit = |memory: &[u8]| memory.get(idx).map(|pixel| *pixel > 0);
+                let pixel = is_lit(self.machine.get_video_ram()).filter(|_| row < other.height);
+                let color = match pixel {
+                    Some(pixel) if is_same_size && is_lit(video_ram) != Some(pixel) => {
+                        DIVERGENCE_COLOR
+                    }
+                    Some(true) => fg_color,
+                    _ => bg_color,
+                };
+                pixels.extend_from_slice(&color);
+            }
+        }
+        pixels
+    }
+}
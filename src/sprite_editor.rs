@@ -0,0 +1,310 @@
+use std::thread;
+use std::time::Duration;
+
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::environ::APP_NAME;
+use crate::renderer::{CanvasRenderer, RendererBackend};
+use crate::screenshot::Rgb8;
+use crate::text;
+
+const MAX_SIZE: usize = 16; // 16x16 SCHIP sprite
+const MAX_HEIGHT: usize = 15; // DXYN height of 8 pixels wide sprites
+const CELL_SIZE: usize = 24; // editing grid cell in screen pixels
+const MARGIN: usize = 16;
+const TEXT_PIXEL_SIZE: usize = 3;
+const BYTES_PER_LINE: usize = 8;
+const GRID_COLOR: Color = Color::RGB(0x60, 0x60, 0x60);
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+///
+/// Sprite being edited, 8xN for DXYN or 16x16 for SCHIP DXY0.
+/// Rows are kept as 16 bits with the leftmost pixel in the highest bit
+///
+struct Sprite {
+    rows: [u16; MAX_SIZE],
+    is_wide: bool,
+    height: usize, // rows of 8 pixels wide sprite
+}
+
+impl Sprite {
+    fn new() -> Self {
+        Self {
+            rows: [0; MAX_SIZE],
+            is_wide: false,
+            height: 8,
+        }
+    }
+
+    fn size(&self) -> (usize, usize) {
+        if self.is_wide {
+            (MAX_SIZE, MAX_SIZE)
+        } else {
+            (8, self.height)
+        }
+    }
+
+    fn pixel(&self, col: usize, row: usize) -> bool {
+        self.rows[row] >> (MAX_SIZE - 1 - col) & 1 > 0
+    }
+
+    fn set_pixel(&mut self, col: usize, row: usize, is_lit: bool) {
+        let mask = 1 << (MAX_SIZE - 1 - col);
+        if is_lit {
+            self.rows[row] |= mask;
+        } else {
+            self.rows[row] &= !mask;
+        }
+    }
+
+    /// Only the visible part is inverted, hidden pixels stay as they are
+    fn invert(&mut self) {
+        let (width, height) = self.size();
+        for row in 0..height {
+            for col in 0..width {
+                self.set_pixel(col, row, !self.pixel(col, row));
+            }
+        }
+    }
+
+    /// Sprite data as the program stores it, wide rows take 2 bytes
+    fn bytes(&self) -> Vec<u8> {
+        let (_, height) = self.size();
+        self.rows[..height]
+            .iter()
+            .flat_map(|row| {
+                let [high, low] = row.to_be_bytes();
+                if self.is_wide {
+                    vec![high, low]
+                } else {
+                    vec![high]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Standalone window to draw 8xN and 16x16 sprites with mouse, the bytes are shown in hex
+/// and copied to the clipboard as Octo source. The display colors and scale come from the config
+pub fn run(config: &Config) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let [background, foreground, ..] = config.appearance.plane_colors();
+    let mut editor = SpriteEditor {
+        sprite: Sprite::new(),
+        scale: config.appearance.scale.max(2),
+        foreground,
+        background,
+        paint: None,
+    };
+    let size = editor.window_size();
+    let window = video_subsystem
+        .window(APP_NAME, size.width(), size.height())
+        .position_centered()
+        .allow_highdpi()
+        .build()
+        .map_err(|op| op.to_string())?;
+    let mut renderer = CanvasRenderer::new(window, true)?;
+    let clipboard = video_subsystem.clipboard();
+    let mut event_pump = sdl_context.event_pump()?;
+    'editor_loop: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'editor_loop,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    ..
+                } => editor.on_key_down(keycode, keymod, &clipboard),
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => editor.on_mouse_down(mouse_btn, x, y),
+                Event::MouseMotion { x, y, .. } => editor.on_mouse_move(x, y),
+                Event::MouseButtonUp { .. } => editor.paint = None,
+                _ => {}
+            }
+        }
+        let (width, height) = editor.sprite.size();
+        let title = format!("{APP_NAME} - Sprite editor {width}x{height}");
+        if renderer.window().title() != title {
+            renderer
+                .window_mut()
+                .set_title(&title)
+                .map_err(|err| err.to_string())?;
+        }
+        renderer.clear(editor.background)?;
+        if let Some(canvas) = renderer.canvas() {
+            editor.draw(canvas)?;
+        }
+        renderer.present()?;
+        thread::sleep(FRAME_INTERVAL);
+    }
+    println!("{}", octo_bytes(&editor.sprite.bytes()));
+    Ok(())
+}
+
+struct SpriteEditor {
+    sprite: Sprite,
+    scale: usize, // display pixel size of the emulator window
+    foreground: Rgb8,
+    background: Rgb8,
+    paint: Option<bool>, // pixel value set while the mouse button is held
+}
+
+impl SpriteEditor {
+    /// Grid on the left, previews with hires and lores pixels on the right, hex below them
+    fn window_size(&self) -> Rect {
+        let grid = MAX_SIZE * CELL_SIZE;
+        let previews = MAX_SIZE * self.scale / 2 + MARGIN + MAX_SIZE * self.scale;
+        let width = MARGIN + grid + MARGIN + previews + MARGIN;
+        let lines = MAX_SIZE * 2 / BYTES_PER_LINE;
+        let text_height = lines * (text::text_height(TEXT_PIXEL_SIZE) + MARGIN);
+        let height = MARGIN + grid.max(MAX_SIZE * self.scale) + MARGIN + text_height;
+        Rect::new(0, 0, width as u32, height as u32)
+    }
+
+    fn on_key_down(&mut self, keycode: Keycode, keymod: Mod, clipboard: &ClipboardUtil) {
+        let sprite = &mut self.sprite;
+        match keycode {
+            Keycode::Tab => sprite.is_wide = !sprite.is_wide,
+            Keycode::Up if !sprite.is_wide => sprite.height = (sprite.height - 1).max(1),
+            Keycode::Down if !sprite.is_wide => sprite.height = (sprite.height + 1).min(MAX_HEIGHT),
+            Keycode::Delete | Keycode::Backspace => sprite.rows = [0; MAX_SIZE],
+            Keycode::I => sprite.invert(),
+            Keycode::C if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                let source = octo_bytes(&sprite.bytes());
+                if let Err(err) = clipboard.set_clipboard_text(&source) {
+                    warn!("Failed to copy the sprite: {err}");
+                }
+                println!("{source}");
+            }
+            _ => {}
+        }
+    }
+
+    fn on_mouse_down(&mut self, button: MouseButton, x: i32, y: i32) {
+        let Some((col, row)) = self.cell_at(x, y) else {
+            return;
+        };
+        // left button toggles the first pixel and paints its new value, right one erases
+        let is_lit = match button {
+            MouseButton::Left => !self.sprite.pixel(col, row),
+            MouseButton::Right => false,
+            _ => return,
+        };
+        self.paint = Some(is_lit);
+        self.sprite.set_pixel(col, row, is_lit);
+    }
+
+    fn on_mouse_move(&mut self, x: i32, y: i32) {
+        if let (Some(is_lit), Some((col, row))) = (self.paint, self.cell_at(x, y)) {
+            self.sprite.set_pixel(col, row, is_lit);
+        }
+    }
+
+    fn cell_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let (width, height) = self.sprite.size();
+        let col = (x - MARGIN as i32).div_euclid(CELL_SIZE as i32);
+        let row = (y - MARGIN as i32).div_euclid(CELL_SIZE as i32);
+        let is_inside = (0..width as i32).contains(&col) && (0..height as i32).contains(&row);
+        is_inside.then_some((col as usize, row as usize))
+    }
+
+    fn draw(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let (width, height) = self.sprite.size();
+        let [r, g, b] = self.foreground;
+        let foreground = Color::RGB(r, g, b);
+        // editing grid
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (col, row)))
+            .map(|(col, row)| {
+                let rect = Rect::new(
+                    (MARGIN + col * CELL_SIZE) as i32,
+                    (MARGIN + row * CELL_SIZE) as i32,
+                    CELL_SIZE as u32,
+                    CELL_SIZE as u32,
+                );
+                (rect, self.sprite.pixel(col, row))
+            })
+            .collect::<Vec<_>>();
+        canvas.set_draw_color(foreground);
+        let lit = cells
+            .iter()
+            .filter(|(_, is_lit)| *is_lit)
+            .map(|(rect, _)| *rect);
+        canvas.fill_rects(&lit.collect::<Vec<_>>())?;
+        canvas.set_draw_color(GRID_COLOR);
+        let outlines = cells.iter().map(|(rect, _)| *rect).collect::<Vec<_>>();
+        canvas.draw_rects(&outlines)?;
+        // previews as the sprite appears in the emulator window with hires and lores pixels
+        let mut left = 2 * MARGIN + MAX_SIZE * CELL_SIZE;
+        for pixel_size in [self.scale / 2, self.scale] {
+            let pixels = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, is_lit))| *is_lit)
+                .map(|(index, _)| {
+                    let (col, row) = (index % width, index / width);
+                    Rect::new(
+                        (left + col * pixel_size) as i32,
+                        (MARGIN + row * pixel_size) as i32,
+                        pixel_size as u32,
+                        pixel_size as u32,
+                    )
+                })
+                .collect::<Vec<_>>();
+            canvas.set_draw_color(foreground);
+            canvas.fill_rects(&pixels)?;
+            canvas.set_draw_color(GRID_COLOR);
+            canvas.draw_rect(Rect::new(
+                left as i32,
+                MARGIN as i32,
+                (width * pixel_size) as u32,
+                (height * pixel_size) as u32,
+            ))?;
+            left += MAX_SIZE * pixel_size + MARGIN;
+        }
+        // hex bytes
+        let bytes = self.sprite.bytes();
+        let line_height = text::text_height(TEXT_PIXEL_SIZE) + MARGIN;
+        let top = 2 * MARGIN + (MAX_SIZE * CELL_SIZE).max(MAX_SIZE * self.scale);
+        for (index, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let hex = line
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let y = top + index * line_height;
+            text::draw_text(
+                canvas,
+                &hex,
+                MARGIN as i32,
+                y as i32,
+                TEXT_PIXEL_SIZE,
+                foreground,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Bytes in Octo source notation, e.g. `0xF0 0x90 0xF0`
+fn octo_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("0x{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+///
+/// Labels of program addresses loaded from a symbol file, e.g. exported by Octo.
+/// Each line holds a name and an address in any order separated by spaces, `=` or `:`,
+/// addresses are hexadecimal with `0x` or `$` prefix or starting with a digit.
+/// Lines starting with `#` or `;` and lines without a name and an address are skipped
+///
+#[derive(Clone, Default)]
+pub struct Symbols {
+    labels: BTreeMap<usize, String>,
+    addresses: HashMap<String, usize>,
+}
+
+impl Symbols {
+    pub fn with_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let mut symbols = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let tokens = line
+                .split(|ch: char| ch.is_whitespace() || ch == '=' || ch == ':')
+                .filter(|token| !token.is_empty())
+                .collect::<Vec<_>>();
+            let [first, second] = tokens[..] else {
+                continue;
+            };
+            let (name, address) = if is_name(first) {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            if let (true, Some(address)) = (is_name(name), parse_address(address)) {
+                symbols.insert(name, address);
+            }
+        }
+        Ok(symbols)
+    }
+
+    /// Symbols of the labels defined by an assembled program
+    pub fn with_labels(labels: &[(String, usize)]) -> Self {
+        let mut symbols = Self::default();
+        for (name, address) in labels {
+            symbols.insert(name, *address);
+        }
+        symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn label(&self, address: usize) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    pub fn address(&self, name: &str) -> Option<usize> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Label of the address or its hexadecimal value
+    pub fn label_or_hex(&self, address: usize) -> String {
+        self.label(address)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{address:03X}"))
+    }
+
+    /// Location inside the code as the nearest preceding label with offset, e.g. `draw_player+4`
+    pub fn describe(&self, address: usize) -> String {
+        match self.labels.range(..=address).next_back() {
+            Some((start, name)) if *start == address => name.clone(),
+            Some((start, name)) => format!("{name}+{:X}", address - start),
+            None => format!("{address:03X}"),
+        }
+    }
+
+    fn insert(&mut self, name: &str, address: usize) {
+        self.labels.insert(address, name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+}
+
+fn is_name(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_alphabetic() || ch == '_')
+}
+
+fn parse_address(token: &str) -> Option<usize> {
+    let digits = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix('$'))
+        .or_else(|| {
+            token
+                .starts_with(|ch: char| ch.is_ascii_digit())
+                .then_some(token)
+        })?;
+    usize::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,118 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+///
+/// Tiny 3x5 bitmap font for on-screen messages
+///
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+const GLYPH_SPACING: usize = 1;
+
+// each row is 3 bits wide, the highest bit is the leftmost pixel
+fn glyph(ch: char) -> [u8; GLYPH_HEIGHT] {
+    match ch.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 2, 2],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        ':' => [0, 2, 0, 2, 0],
+        '-' => [0, 0, 7, 0, 0],
+        '+' => [0, 2, 7, 2, 0],
+        '=' => [0, 7, 0, 7, 0],
+        '*' => [0, 5, 2, 5, 0],
+        '/' => [1, 1, 2, 4, 4],
+        '%' => [5, 1, 2, 4, 5],
+        '(' => [1, 2, 2, 2, 1],
+        ')' => [4, 2, 2, 2, 4],
+        '[' => [3, 2, 2, 2, 3],
+        ']' => [6, 2, 2, 2, 6],
+        '<' => [1, 2, 4, 2, 1],
+        '>' => [4, 2, 1, 2, 4],
+        '!' => [2, 2, 2, 0, 2],
+        '_' => [0, 0, 0, 0, 7],
+        '#' => [5, 7, 5, 7, 5],
+        '\'' => [2, 2, 0, 0, 0],
+        '"' => [5, 5, 0, 0, 0],
+        _ => [6, 1, 2, 0, 2], // '?'
+    }
+}
+
+/// Width of the rendered text in screen pixels
+pub fn text_width(text: &str, pixel_size: usize) -> usize {
+    let len = text.chars().count();
+    if len == 0 {
+        return 0;
+    }
+    (len * (GLYPH_WIDTH + GLYPH_SPACING) - GLYPH_SPACING) * pixel_size
+}
+
+/// Height of the single line of text in screen pixels
+pub fn text_height(pixel_size: usize) -> usize {
+    GLYPH_HEIGHT * pixel_size
+}
+
+pub fn draw_text(
+    canvas: &mut WindowCanvas,
+    text: &str,
+    x: i32,
+    y: i32,
+    pixel_size: usize,
+    color: Color,
+) -> Result<(), String> {
+    let mut rects = Vec::new();
+    let size = pixel_size as i32;
+    for (i, ch) in text.chars().enumerate() {
+        let left = x + (i * (GLYPH_WIDTH + GLYPH_SPACING)) as i32 * size;
+        for (r, row) in glyph(ch).iter().enumerate() {
+            for c in 0..GLYPH_WIDTH {
+                if (row >> (GLYPH_WIDTH - 1 - c)) & 1 == 0 {
+                    continue;
+                }
+                rects.push(Rect::new(
+                    left + c as i32 * size,
+                    y + r as i32 * size,
+                    pixel_size as u32,
+                    pixel_size as u32,
+                ));
+            }
+        }
+    }
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&rects)
+}
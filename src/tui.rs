@@ -0,0 +1,304 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use tracing::{error, info};
+
+use crate::chip8::{self, Chip8, Severity, State};
+use crate::common::USize;
+use crate::config::{AppearanceConfig, Config, Timing};
+use crate::crash_dump::CrashDump;
+use crate::platform::{self, Platform};
+use crate::symbols::Symbols;
+
+const FRAMES_PER_SECOND: u64 = 60;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+const MAX_FRAME_LAG: Duration = Duration::from_millis(250);
+// most terminals don't report key releases, so they are emulated after the delay
+const KEY_RELEASE_DELAY: Duration = Duration::from_millis(150);
+
+///
+/// Terminal output and keypad, two display rows are drawn by a single line of half-block characters
+///
+struct TerminalPlatform {
+    stdout: Stdout,
+    foreground: Color,
+    background: Color,
+    key_events: VecDeque<platform::KeyEvent>,
+    pressed_keys: HashMap<u8, Instant>, // key code and time of its last press
+    is_release_reported: bool,
+}
+
+impl TerminalPlatform {
+    fn on_key(&mut self, code: u8, kind: KeyEventKind) {
+        match kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                self.key_events.push_back(platform::KeyEvent::Down(code));
+                self.pressed_keys.insert(code, Instant::now());
+            }
+            KeyEventKind::Release => {
+                self.key_events.push_back(platform::KeyEvent::Up(code));
+                self.pressed_keys.remove(&code);
+            }
+        }
+    }
+
+    /// Releases keys which weren't repeated for a while if terminal doesn't report releases
+    fn release_keys(&mut self) {
+        if self.is_release_reported {
+            return;
+        }
+        let expired = self
+            .pressed_keys
+            .iter()
+            .filter(|(_, time)| time.elapsed() >= KEY_RELEASE_DELAY)
+            .map(|(code, _)| *code)
+            .collect::<Vec<_>>();
+        for code in expired {
+            self.key_events.push_back(platform::KeyEvent::Up(code));
+            self.pressed_keys.remove(&code);
+        }
+    }
+
+    fn write_frame(&mut self, video_memory: &[u8], size: USize) -> io::Result<()> {
+        let is_lit = |r: usize, c: usize| r < size.height && video_memory[r * size.width + c] > 0;
+        queue!(
+            self.stdout,
+            cursor::MoveTo(0, 0),
+            SetForegroundColor(self.foreground),
+            SetBackgroundColor(self.background)
+        )?;
+        for r in (0..size.height).step_by(2) {
+            let line = (0..size.width)
+                .map(|c| match (is_lit(r, c), is_lit(r + 1, c)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect::<String>();
+            queue!(self.stdout, Print(line), cursor::MoveToNextLine(1))?;
+        }
+        queue!(
+            self.stdout,
+            SetForegroundColor(Color::Reset),
+            SetBackgroundColor(Color::Reset)
+        )?;
+        self.stdout.flush()
+    }
+}
+
+impl Platform for TerminalPlatform {
+    fn draw(&mut self, video_memory: &[u8], size: USize) {
+        // broken output is detected by the next event poll
+        _ = self.write_frame(video_memory, size);
+    }
+
+    /// Terminal bell can't be stopped, so it rings once per beep
+    fn on_sound_start(&mut self) {
+        _ = execute!(self.stdout, Print('\x07'));
+    }
+
+    fn next_key_event(&mut self) -> Option<platform::KeyEvent> {
+        self.key_events.pop_front()
+    }
+}
+
+///
+/// Terminal frontend, doesn't require SDL
+///
+pub struct TerminalEnvironment<'a> {
+    config: AppearanceConfig,
+    machine: &'a mut Chip8,
+    platform: TerminalPlatform,
+    key_mapping: HashMap<char, u8>,
+    operations_budget: u64,
+    cycles_budget: i64,
+    crash: Option<CrashDump>, // reported after the terminal is restored
+    is_crash_dump_saved: bool,
+}
+
+impl<'a> TerminalEnvironment<'a> {
+    pub fn new(config: Config, machine: &'a mut Chip8) -> Self {
+        let key_mapping = HashMap::from([
+            ('1', 0x1),
+            ('2', 0x2),
+            ('3', 0x3),
+            ('4', 0xc),
+            ('q', 0x4),
+            ('w', 0x5),
+            ('e', 0x6),
+            ('r', 0xd),
+            ('a', 0x7),
+            ('s', 0x8),
+            ('d', 0x9),
+            ('f', 0xe),
+            ('z', 0xa),
+            ('x', 0x0),
+            ('c', 0xb),
+            ('v', 0xf),
+        ]);
+        let is_crash_dump_saved = config.debug.crash_dump;
+        let appearance = config.appearance;
+        let platform = TerminalPlatform {
+            stdout: io::stdout(),
+            foreground: Color::Rgb {
+                r: appearance.foreground_red,
+                g: appearance.foreground_green,
+                b: appearance.foreground_blue,
+            },
+            background: Color::Rgb {
+                r: appearance.background_red,
+                g: appearance.background_green,
+                b: appearance.background_blue,
+            },
+            key_events: VecDeque::new(),
+            pressed_keys: HashMap::new(),
+            is_release_reported: false,
+        };
+        Self {
+            config: appearance,
+            machine,
+            platform,
+            key_mapping,
+            operations_budget: 0,
+            cycles_budget: 0,
+            crash: None,
+            is_crash_dump_saved,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+        let is_release_reported = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        self.platform.is_release_reported = is_release_reported;
+        let result =
+            execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide).and_then(|_| {
+                if is_release_reported {
+                    let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+                    execute!(stdout, PushKeyboardEnhancementFlags(flags))?;
+                }
+                self.run_loop(&mut stdout)
+            });
+        // terminal is restored even if the loop failed
+        if is_release_reported {
+            _ = execute!(stdout, PopKeyboardEnhancementFlags);
+        }
+        _ = execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        _ = terminal::disable_raw_mode();
+        if let Some(dump) = &self.crash {
+            error!("{}", dump.to_string().trim_end());
+            if self.is_crash_dump_saved {
+                match dump.save() {
+                    Ok(file_name) => info!("Crash dump saved to {file_name}"),
+                    Err(err) => error!("Failed to save crash dump: {err}"),
+                }
+            }
+        }
+        result.map_err(|err| err.to_string())
+    }
+
+    fn run_loop(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+        execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+        let mut accumulator = Duration::ZERO;
+        let mut last_time = Instant::now();
+        loop {
+            while event::poll(Duration::ZERO)? {
+                if let Event::Key(key) = event::read()? {
+                    if !self.on_key(key) {
+                        return Ok(());
+                    }
+                }
+            }
+            if matches!(self.machine.get_state(), State::Terminated) {
+                return Ok(());
+            }
+            self.platform.release_keys();
+            let now = Instant::now();
+            accumulator = (accumulator + now.duration_since(last_time)).min(MAX_FRAME_LAG);
+            last_time = now;
+            while accumulator >= FRAME_DURATION {
+                self.run_frame();
+                accumulator -= FRAME_DURATION;
+            }
+            self.machine.sync_platform(&mut self.platform);
+            std::thread::sleep(FRAME_DURATION.saturating_sub(accumulator));
+        }
+    }
+
+    /// Returns false if the user asked to quit
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        let is_ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || is_ctrl_c {
+            return false;
+        }
+        if key.code == KeyCode::F(5) && key.kind == KeyEventKind::Press {
+            self.machine.toggle_execution();
+            return true;
+        }
+        let KeyCode::Char(ch) = key.code else {
+            return true;
+        };
+        if let Some(code) = self.key_mapping.get(&ch.to_ascii_lowercase()) {
+            self.platform.on_key(*code, key.kind);
+        }
+        true
+    }
+
+    fn run_frame(&mut self) {
+        if !self.machine.is_running() {
+            return;
+        }
+        match self.config.timing {
+            Timing::Ips => {
+                self.operations_budget += self.config.operations_per_second;
+                let count = self.operations_budget / FRAMES_PER_SECOND;
+                self.operations_budget %= FRAMES_PER_SECOND;
+                for _ in 0..count {
+                    if !self.step() || self.is_idle() {
+                        break;
+                    }
+                }
+            }
+            Timing::Cosmac => {
+                self.cycles_budget += chip8::COSMAC_CYCLES_PER_FRAME as i64;
+                while self.cycles_budget > 0 {
+                    if !self.step() || self.is_idle() {
+                        break;
+                    }
+                    self.cycles_budget -= self.machine.instruction_cost() as i64;
+                }
+                self.cycles_budget = self.cycles_budget.min(0);
+            }
+        }
+        self.machine.on_timer_with(&mut self.platform);
+    }
+
+    /// The rest of the frame is skipped while the program spins
+    fn is_idle(&self) -> bool {
+        self.config.is_idle_skip && self.machine.is_idle()
+    }
+
+    fn step(&mut self) -> bool {
+        if !self.machine.is_running() {
+            return false;
+        }
+        if let Err(error) = self.machine.teak() {
+            // logging would corrupt the terminal screen, warnings are dropped
+            if error.severity() == Severity::Warning {
+                return true;
+            }
+            self.crash = Some(CrashDump::new(self.machine, &error, &Symbols::default()));
+            self.machine.terminate();
+        }
+        true
+    }
+}
@@ -0,0 +1,35 @@
+use crate::chip8::Chip8;
+use crate::condition::Condition;
+
+///
+/// Expressions evaluated on every frame to follow the program state,
+/// e.g. `mem[I]`, `V[4] * 2` or `DT`, see `Condition` for the syntax
+///
+#[derive(Default)]
+pub struct WatchList {
+    expressions: Vec<Condition>,
+}
+
+impl WatchList {
+    pub fn add(&mut self, source: &str) -> Result<(), String> {
+        let expression = Condition::parse(source)?;
+        self.expressions.push(expression);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.expressions.len() {
+            return Err(format!("no watch #{index}"));
+        }
+        self.expressions.remove(index);
+        Ok(())
+    }
+
+    /// Source and current value of every expression
+    pub fn values(&self, machine: &Chip8) -> Vec<(String, u32)> {
+        self.expressions
+            .iter()
+            .map(|expression| (expression.to_string(), expression.evaluate(machine)))
+            .collect()
+    }
+}
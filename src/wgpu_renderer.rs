@@ -0,0 +1,306 @@
+use std::borrow::Cow;
+use std::num::NonZeroU32;
+
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::video::Window;
+
+use crate::common::USize;
+use crate::renderer::RendererBackend;
+use crate::screenshot::Rgb8;
+
+///
+/// GPU renderer, the display is drawn by shader pipeline,
+/// overlays aren't supported
+///
+pub struct WgpuRenderer {
+    surface: wgpu::Surface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    frame: Option<(wgpu::Texture, wgpu::BindGroup, USize)>, // display texture
+    background: wgpu::Color,
+    target: Rect,   // display area of the window
+    rgba: Vec<u8>,  // display pixels converted for upload
+    window: Window, // dropped after the surface
+    is_vsync: bool,
+}
+
+impl WgpuRenderer {
+    pub fn new(window: Window, is_vsync: bool) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        // SAFETY: the window is owned by the renderer and outlives the surface
+        let surface = unsafe { instance.create_surface(&window) };
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        }))
+        .ok_or("No suitable GPU adapter found")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        ))
+        .map_err(|err| err.to_string())?;
+        let formats = surface.get_supported_formats(&adapter);
+        // sRGB surface keeps configured colors exact
+        let format = formats
+            .iter()
+            .copied()
+            .find(|format| {
+                matches!(
+                    format,
+                    wgpu::TextureFormat::Bgra8UnormSrgb | wgpu::TextureFormat::Rgba8UnormSrgb
+                )
+            })
+            .or(formats.first().copied())
+            .ok_or("Surface isn't supported by the adapter")?;
+        let (width, height) = window.drawable_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: if is_vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            },
+        };
+        surface.configure(&device, &surface_config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("display"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("display.wgsl"))),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("display"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        // nearest neighbor scaling keeps pixels sharp
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            surface_config,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            frame: None,
+            background: wgpu::Color::BLACK,
+            target: Rect::new(0, 0, 1, 1),
+            rgba: Vec::new(),
+            window,
+            is_vsync,
+        })
+    }
+
+    fn create_frame(&self, size: USize) -> (wgpu::Texture, wgpu::BindGroup) {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("display"),
+            size: wgpu::Extent3d {
+                width: size.width as u32,
+                height: size.height as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        (texture, bind_group)
+    }
+
+    /// Surface follows the window size
+    fn update_surface(&mut self) {
+        let (width, height) = self.window.drawable_size();
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) != (self.surface_config.width, self.surface_config.height) {
+            self.surface_config.width = width;
+            self.surface_config.height = height;
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+}
+
+impl RendererBackend for WgpuRenderer {
+    fn window(&self) -> &Window {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        &mut self.window
+    }
+
+    fn is_vsync(&self) -> bool {
+        self.is_vsync
+    }
+
+    fn clear(&mut self, background: Rgb8) -> Result<(), String> {
+        // clear color is specified in linear space
+        let linear = |value: u8| {
+            let value = value as f64 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let [r, g, b] = background;
+        self.background = wgpu::Color {
+            r: linear(r),
+            g: linear(g),
+            b: linear(b),
+            a: 1.0,
+        };
+        Ok(())
+    }
+
+    fn draw_display(&mut self, pixels: &[u8], size: USize, target: Rect) -> Result<(), String> {
+        if self.frame.as_ref().map(|(_, _, size)| *size) != Some(size) {
+            let (texture, bind_group) = self.create_frame(size);
+            self.frame = Some((texture, bind_group, size));
+        }
+        let Some((texture, _, _)) = &self.frame else {
+            return Ok(());
+        };
+        self.rgba.clear();
+        for pixel in pixels.chunks_exact(3) {
+            self.rgba
+                .extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xff]);
+        }
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width as u32 * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: size.width as u32,
+                height: size.height as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.target = target;
+        Ok(())
+    }
+
+    fn canvas(&mut self) -> Option<&mut WindowCanvas> {
+        None
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        self.update_surface();
+        let output = self
+            .surface
+            .get_current_texture()
+            .map_err(|err| err.to_string())?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            if let Some((_, bind_group, _)) = &self.frame {
+                // viewport has to stay inside the surface
+                let (width, height) = (self.surface_config.width, self.surface_config.height);
+                let x = self.target.x().clamp(0, width as i32 - 1) as u32;
+                let y = self.target.y().clamp(0, height as i32 - 1) as u32;
+                let w = self.target.width().min(width - x);
+                let h = self.target.height().min(height - y);
+                pass.set_viewport(x as f32, y as f32, w as f32, h as f32, 0.0, 1.0);
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+//! Machines run by many threads at once, run with `cargo test`
+
+// the crate is a binary, so the core modules are compiled into the test directly
+#![allow(dead_code)]
+extern crate alloc;
+
+#[path = "../src/chip8.rs"]
+mod chip8;
+#[path = "../src/common.rs"]
+mod common;
+#[path = "../src/coverage.rs"]
+mod coverage;
+#[path = "../src/font.rs"]
+mod font;
+#[path = "../src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../src/journal.rs"]
+mod journal;
+#[cfg(feature = "megachip")]
+#[path = "../src/megachip.rs"]
+mod megachip;
+#[path = "../src/opcode.rs"]
+mod opcode;
+#[path = "../src/platform.rs"]
+mod platform;
+#[path = "../src/quirks.rs"]
+mod quirks;
+
+use std::thread;
+
+use chip8::Chip8;
+
+const STEPS: u64 = 10_000;
+const MACHINES_COUNT: usize = 100;
+
+// font sprites drawn all over the screen with random values in V3
+const DRAW_ROM: [u8; 14] = [
+    0x70, 0x01, // 200: ADD V0, 01
+    0xf0, 0x29, // 202: LD F, V0
+    0xd1, 0x25, // 204: DRW V1, V2, 5
+    0x71, 0x03, // 206: ADD V1, 03
+    0x72, 0x02, // 208: ADD V2, 02
+    0xc3, 0xff, // 20A: RND V3, FF
+    0x12, 0x00, // 20C: JP 200
+];
+
+fn run(machine: &mut Chip8) {
+    for _ in 0..STEPS {
+        machine.teak().expect("test program failed");
+    }
+}
+
+fn machine() -> Chip8 {
+    Chip8::builder(DRAW_ROM.to_vec())
+        .seed(0)
+        .undo_depth(100)
+        .build()
+        .expect("test program doesn't fit")
+}
+
+/// Each machine run by its own thread has to end up exactly like the machine run alone
+#[test]
+fn machines_run_by_threads_match_reference() {
+    let mut reference = machine();
+    run(&mut reference);
+    let mut machines = (0..MACHINES_COUNT).map(|_| machine()).collect::<Vec<_>>();
+    thread::scope(|scope| {
+        for machine in &mut machines {
+            scope.spawn(|| run(machine));
+        }
+    });
+    for machine in &machines {
+        assert_eq!(machine.get_video_ram(), reference.get_video_ram());
+        assert_eq!(machine.registers(), reference.registers());
+        assert_eq!(machine.cycles(), reference.cycles());
+    }
+}
+
+/// Machines with hooks are moved between threads
+#[test]
+fn machine_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Chip8>();
+}